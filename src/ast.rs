@@ -0,0 +1,663 @@
+//! The format-preserving syntax tree of a parsed TOML document, and the `Visitor` and `VisitorMut` traits that walk it.
+//!
+//! Every part of the original document is kept in the tree, including whitespace, comments, table headers and the
+//! separators in arrays and inline tables, so that displaying the tree gives back the original document. The tree of a
+//! `TOMLParser` is walked with `TOMLParser::visit` and `TOMLParser::visit_mut`.
+//!
+//! The nodes are read through their methods, which don't expose how the tree is stored. A `VisitorMut` can change
+//! keys, values, comments and whitespace in place with `KeyVal::set_key`, by assigning to a `TOMLValue`,
+//! `Comment::set_text`, and `WSSep::set_ws1` and `WSSep::set_ws2` on the whitespace returned by the `*_mut` methods.
+//!
+//! Each `visit_*` method of the traits has a default implementation that calls the matching `walk_*` function, which
+//! visits the node's children. A visitor that overrides a method can call the `walk_*` function itself to keep walking
+//! into the node's children.
+//!
+//! # Examples
+//!
+//! ```
+//! use tomllib::TOMLParser;
+//! use tomllib::ast::{Visitor, Comment};
+//!
+//! struct CommentCollector {
+//!   comments: Vec<String>,
+//! }
+//!
+//! impl<'a> Visitor<'a> for CommentCollector {
+//!   fn visit_comment(&mut self, comment: &Comment<'a>) {
+//!     self.comments.push(comment.text().to_string());
+//!   }
+//! }
+//!
+//! let parser = TOMLParser::new();
+//! let (parser, _) = parser.parse("# The title\ntitle = \"TOML\" # Inline\nnumbers = [\n  1, # One\n  2,\n]\n");
+//! let mut collector = CommentCollector{comments: vec![]};
+//! parser.visit(&mut collector);
+//! assert_eq!(vec![" The title", " Inline", " One"], collector.comments);
+//! ```
+
+use std::cell::Ref;
+use std::rc::Rc;
+pub use crate::internals::ast::structs::{Toml, NLExpression, Expression, KeyVal, TableType, Table, WSKeySep, WSSep,
+  Comment, TOMLValue, Array, ArrayValue, InlineTable, TableKeyVal, CommentOrNewLines, CommentNewLines};
+
+impl<'a> Toml<'a> {
+  /// Returns the lines of the document.
+  pub fn exprs(&self) -> &[NLExpression<'a>] {
+    &self.exprs
+  }
+}
+
+impl<'a> NLExpression<'a> {
+  /// Returns the newline that precedes the line, which is empty for the first line.
+  pub fn nl(&self) -> &str {
+    &self.nl
+  }
+
+  /// Returns the contents of the line.
+  pub fn expr(&self) -> &Expression<'a> {
+    &self.expr
+  }
+}
+
+impl<'a> Expression<'a> {
+  /// Returns the whitespace before the key/value pair or table header, and the whitespace after it.
+  pub fn ws(&self) -> &WSSep<'a> {
+    &self.ws
+  }
+
+  /// Returns the whitespace before and after the key/value pair or table header, to change it.
+  pub fn ws_mut(&mut self) -> &mut WSSep<'a> {
+    &mut self.ws
+  }
+
+  /// Returns the key/value pair of the line, if it has one.
+  pub fn keyval(&self) -> Option<&KeyVal<'a>> {
+    self.keyval.as_ref()
+  }
+
+  /// Returns the table header of the line, if it has one.
+  pub fn table(&self) -> Option<&TableType<'a>> {
+    self.table.as_deref()
+  }
+
+  /// Returns the comment at the end of the line, if it has one.
+  pub fn comment(&self) -> Option<&Comment<'a>> {
+    self.comment.as_ref()
+  }
+
+  /// Returns the text at the end of the line that couldn't be parsed, if there is any.
+  pub fn invalid(&self) -> Option<&str> {
+    self.invalid.as_deref()
+  }
+}
+
+impl<'a> KeyVal<'a> {
+  /// Returns the key as it's written in the document, including any quotes.
+  pub fn key(&self) -> &str {
+    &self.key
+  }
+
+  /// Replaces the key. `key` is written to the document as it is, so it needs any quotes the key requires.
+  pub fn set_key<S: Into<String>>(&mut self, key: S) {
+    self.key = key.into().into();
+  }
+
+  /// Returns the whitespace before and after the `=`.
+  pub fn keyval_sep(&self) -> &WSSep<'a> {
+    &self.keyval_sep
+  }
+
+  /// Returns the whitespace before and after the `=`, to change it.
+  pub fn keyval_sep_mut(&mut self) -> &mut WSSep<'a> {
+    &mut self.keyval_sep
+  }
+
+  /// Returns the value.
+  pub fn value(&self) -> Ref<'_, TOMLValue<'a>> {
+    self.val.borrow()
+  }
+}
+
+impl WSSep<'_> {
+  /// Returns the first whitespace, which comes before the separator or node it surrounds.
+  pub fn ws1(&self) -> &str {
+    &self.ws1
+  }
+
+  /// Returns the second whitespace, which comes after the separator or node it surrounds.
+  pub fn ws2(&self) -> &str {
+    &self.ws2
+  }
+
+  /// Replaces the first whitespace, which has to be whitespace for the document to stay valid.
+  pub fn set_ws1<S: Into<String>>(&mut self, ws: S) {
+    self.ws1 = ws.into().into();
+  }
+
+  /// Replaces the second whitespace, which has to be whitespace for the document to stay valid.
+  pub fn set_ws2<S: Into<String>>(&mut self, ws: S) {
+    self.ws2 = ws.into().into();
+  }
+}
+
+impl<'a> WSKeySep<'a> {
+  /// Returns the whitespace around the dot that precedes the key. For the first key of a table header, it's the
+  /// whitespace after the opening bracket and before the closing bracket instead.
+  pub fn ws(&self) -> &WSSep<'a> {
+    &self.ws
+  }
+
+  /// Returns the whitespace around the dot that precedes the key, to change it.
+  pub fn ws_mut(&mut self) -> &mut WSSep<'a> {
+    &mut self.ws
+  }
+
+  /// Returns the key as it's written in the document, including any quotes.
+  pub fn key(&self) -> &str {
+    &self.key
+  }
+}
+
+impl<'a> Table<'a> {
+  /// Returns the keys of the table header.
+  pub fn keys(&self) -> &[WSKeySep<'a>] {
+    &self.keys
+  }
+}
+
+impl Comment<'_> {
+  /// Returns the text of the comment, without the `#`.
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  /// Replaces the text of the comment, which goes after the `#` and can't contain a newline for the document to stay
+  /// valid.
+  pub fn set_text<S: Into<String>>(&mut self, text: S) {
+    self.text = text.into().into();
+  }
+}
+
+impl<'a> CommentNewLines<'a> {
+  /// Returns the whitespace and newlines before the comment.
+  pub fn pre_ws_nl(&self) -> &str {
+    &self.pre_ws_nl
+  }
+
+  /// Returns the comment.
+  pub fn comment(&self) -> &Comment<'a> {
+    &self.comment
+  }
+
+  /// Returns the newlines after the comment.
+  pub fn newlines(&self) -> &str {
+    &self.newlines
+  }
+}
+
+impl<'a> Array<'a> {
+  /// Returns the values of the array.
+  pub fn values(&self) -> &[ArrayValue<'a>] {
+    &self.values
+  }
+
+  /// Returns the comments and newlines after the opening bracket.
+  pub fn comment_nls1(&self) -> &[CommentOrNewLines<'a>] {
+    &self.comment_nls1
+  }
+
+  /// Returns the comments and newlines before the closing bracket.
+  pub fn comment_nls2(&self) -> &[CommentOrNewLines<'a>] {
+    &self.comment_nls2
+  }
+}
+
+impl<'a> ArrayValue<'a> {
+  /// Returns the value.
+  pub fn value(&self) -> Ref<'_, TOMLValue<'a>> {
+    self.val.borrow()
+  }
+
+  /// Returns the whitespace around the comma after the value, or `None` if there's no comma.
+  pub fn array_sep(&self) -> Option<&WSSep<'a>> {
+    self.array_sep.as_ref()
+  }
+
+  /// Returns the comments and newlines after the value.
+  pub fn comment_nls(&self) -> &[CommentOrNewLines<'a>] {
+    &self.comment_nls
+  }
+}
+
+impl<'a> InlineTable<'a> {
+  /// Returns the key/value pairs of the inline table.
+  pub fn keyvals(&self) -> &[TableKeyVal<'a>] {
+    &self.keyvals
+  }
+
+  /// Returns the whitespace after the opening brace and before the closing brace.
+  pub fn ws(&self) -> &WSSep<'a> {
+    &self.ws
+  }
+}
+
+impl<'a> TableKeyVal<'a> {
+  /// Returns the key/value pair.
+  pub fn keyval(&self) -> &KeyVal<'a> {
+    &self.keyval
+  }
+
+  /// Returns the whitespace around the comma after the key/value pair, or `None` if there's no comma.
+  pub fn kv_sep(&self) -> Option<&WSSep<'a>> {
+    self.kv_sep.as_ref()
+  }
+
+  /// Returns the comments and newlines after the key/value pair.
+  pub fn comment_nls(&self) -> &[CommentOrNewLines<'a>] {
+    &self.comment_nls
+  }
+}
+
+/// Walks the syntax tree of a TOML document by shared reference. Every method defaults to walking into the node's
+/// children, so implementations only need to override the methods for the nodes they're interested in.
+pub trait Visitor<'a> {
+  /// Visits the whole document.
+  fn visit_toml(&mut self, toml: &Toml<'a>) {
+    walk_toml(self, toml);
+  }
+
+  /// Visits a line of the document, along with the newline that precedes it.
+  fn visit_nl_expression(&mut self, nl_expr: &NLExpression<'a>) {
+    walk_nl_expression(self, nl_expr);
+  }
+
+  /// Visits the contents of a line: a key/value pair, a table header, a comment, or only whitespace.
+  fn visit_expression(&mut self, expr: &Expression<'a>) {
+    walk_expression(self, expr);
+  }
+
+  /// Visits a key/value pair.
+  fn visit_keyval(&mut self, keyval: &KeyVal<'a>) {
+    walk_keyval(self, keyval);
+  }
+
+  /// Visits a `[table]` or `[[array of tables]]` header.
+  fn visit_table(&mut self, table: &TableType<'a>) {
+    walk_table(self, table);
+  }
+
+  /// Visits a key of a table header, along with the whitespace around the dot that precedes it.
+  fn visit_table_key(&mut self, _key: &WSKeySep<'a>) {}
+
+  /// Visits a comment.
+  fn visit_comment(&mut self, _comment: &Comment<'a>) {}
+
+  /// Visits a value.
+  fn visit_value(&mut self, val: &TOMLValue<'a>) {
+    walk_value(self, val);
+  }
+
+  /// Visits an array.
+  fn visit_array(&mut self, array: &Array<'a>) {
+    walk_array(self, array);
+  }
+
+  /// Visits a value in an array, along with the comma, comments and newlines that follow it.
+  fn visit_array_value(&mut self, array_value: &ArrayValue<'a>) {
+    walk_array_value(self, array_value);
+  }
+
+  /// Visits an inline table.
+  fn visit_inline_table(&mut self, inline_table: &InlineTable<'a>) {
+    walk_inline_table(self, inline_table);
+  }
+
+  /// Visits a key/value pair in an inline table, along with the comma that follows it.
+  fn visit_table_keyval(&mut self, table_keyval: &TableKeyVal<'a>) {
+    walk_table_keyval(self, table_keyval);
+  }
+
+  /// Visits the comments and newlines inside an array.
+  fn visit_comment_or_newlines(&mut self, comment_nl: &CommentOrNewLines<'a>) {
+    walk_comment_or_newlines(self, comment_nl);
+  }
+}
+
+/// Visits every line of `toml`.
+pub fn walk_toml<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, toml: &Toml<'a>) {
+  for nl_expr in &toml.exprs {
+    visitor.visit_nl_expression(nl_expr);
+  }
+}
+
+/// Visits the expression of `nl_expr`.
+pub fn walk_nl_expression<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, nl_expr: &NLExpression<'a>) {
+  visitor.visit_expression(&nl_expr.expr);
+}
+
+/// Visits the key/value pair or table header of `expr`, then its comment.
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &Expression<'a>) {
+  if let Some(ref keyval) = expr.keyval {
+    visitor.visit_keyval(keyval);
+  }
+  if let Some(ref table) = expr.table {
+    visitor.visit_table(table);
+  }
+  if let Some(ref comment) = expr.comment {
+    visitor.visit_comment(comment);
+  }
+}
+
+/// Visits the value of `keyval`.
+pub fn walk_keyval<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, keyval: &KeyVal<'a>) {
+  visitor.visit_value(&keyval.val.borrow());
+}
+
+/// Visits every key of `table`.
+pub fn walk_table<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, table: &TableType<'a>) {
+  let t = match *table {
+    TableType::Standard(ref t) | TableType::Array(ref t) => t,
+  };
+  for key in &t.keys {
+    visitor.visit_table_key(key);
+  }
+}
+
+/// Visits the contents of `val` if it's an `Array` or `InlineTable`.
+pub fn walk_value<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, val: &TOMLValue<'a>) {
+  match *val {
+    TOMLValue::Array(ref arr) => visitor.visit_array(&arr.borrow()),
+    TOMLValue::InlineTable(ref it) => visitor.visit_inline_table(&it.borrow()),
+    _ => (),
+  }
+}
+
+/// Visits the comments and newlines after the opening bracket of `array`, its values, then the comments and newlines
+/// before the closing bracket.
+pub fn walk_array<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, array: &Array<'a>) {
+  for comment_nl in &array.comment_nls1 {
+    visitor.visit_comment_or_newlines(comment_nl);
+  }
+  for array_value in &array.values {
+    visitor.visit_array_value(array_value);
+  }
+  for comment_nl in &array.comment_nls2 {
+    visitor.visit_comment_or_newlines(comment_nl);
+  }
+}
+
+/// Visits the value of `array_value`, then the comments and newlines that follow it.
+pub fn walk_array_value<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, array_value: &ArrayValue<'a>) {
+  visitor.visit_value(&array_value.val.borrow());
+  for comment_nl in &array_value.comment_nls {
+    visitor.visit_comment_or_newlines(comment_nl);
+  }
+}
+
+/// Visits every key/value pair of `inline_table`.
+pub fn walk_inline_table<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, inline_table: &InlineTable<'a>) {
+  for table_keyval in &inline_table.keyvals {
+    visitor.visit_table_keyval(table_keyval);
+  }
+}
+
+/// Visits the key/value pair of `table_keyval`, then the comments and newlines that follow it.
+pub fn walk_table_keyval<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, table_keyval: &TableKeyVal<'a>) {
+  visitor.visit_keyval(&table_keyval.keyval);
+  for comment_nl in &table_keyval.comment_nls {
+    visitor.visit_comment_or_newlines(comment_nl);
+  }
+}
+
+/// Visits the comment of `comment_nl`, if it has one.
+pub fn walk_comment_or_newlines<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, comment_nl: &CommentOrNewLines<'a>) {
+  if let CommentOrNewLines::Comment(ref c) = *comment_nl {
+    visitor.visit_comment(&c.comment);
+  }
+}
+
+/// Walks the syntax tree of a TOML document by mutable reference, allowing whitespace, comments, keys and values to be
+/// changed in place. Every method defaults to walking into the node's children, so implementations only need to
+/// override the methods for the nodes they're interested in.
+pub trait VisitorMut<'a> {
+  /// Visits the whole document.
+  fn visit_toml_mut(&mut self, toml: &mut Toml<'a>) {
+    walk_toml_mut(self, toml);
+  }
+
+  /// Visits a line of the document, along with the newline that precedes it.
+  fn visit_nl_expression_mut(&mut self, nl_expr: &mut NLExpression<'a>) {
+    walk_nl_expression_mut(self, nl_expr);
+  }
+
+  /// Visits the contents of a line: a key/value pair, a table header, a comment, or only whitespace.
+  fn visit_expression_mut(&mut self, expr: &mut Expression<'a>) {
+    walk_expression_mut(self, expr);
+  }
+
+  /// Visits a key/value pair.
+  fn visit_keyval_mut(&mut self, keyval: &mut KeyVal<'a>) {
+    walk_keyval_mut(self, keyval);
+  }
+
+  /// Visits a `[table]` or `[[array of tables]]` header.
+  fn visit_table_mut(&mut self, table: &mut TableType<'a>) {
+    walk_table_mut(self, table);
+  }
+
+  /// Visits a key of a table header, along with the whitespace around the dot that precedes it.
+  fn visit_table_key_mut(&mut self, _key: &mut WSKeySep<'a>) {}
+
+  /// Visits a comment.
+  fn visit_comment_mut(&mut self, _comment: &mut Comment<'a>) {}
+
+  /// Visits a value.
+  fn visit_value_mut(&mut self, val: &mut TOMLValue<'a>) {
+    walk_value_mut(self, val);
+  }
+
+  /// Visits an array.
+  fn visit_array_mut(&mut self, array: &mut Array<'a>) {
+    walk_array_mut(self, array);
+  }
+
+  /// Visits a value in an array, along with the comma, comments and newlines that follow it.
+  fn visit_array_value_mut(&mut self, array_value: &mut ArrayValue<'a>) {
+    walk_array_value_mut(self, array_value);
+  }
+
+  /// Visits an inline table.
+  fn visit_inline_table_mut(&mut self, inline_table: &mut InlineTable<'a>) {
+    walk_inline_table_mut(self, inline_table);
+  }
+
+  /// Visits a key/value pair in an inline table, along with the comma that follows it.
+  fn visit_table_keyval_mut(&mut self, table_keyval: &mut TableKeyVal<'a>) {
+    walk_table_keyval_mut(self, table_keyval);
+  }
+
+  /// Visits the comments and newlines inside an array.
+  fn visit_comment_or_newlines_mut(&mut self, comment_nl: &mut CommentOrNewLines<'a>) {
+    walk_comment_or_newlines_mut(self, comment_nl);
+  }
+}
+
+/// Visits every line of `toml`.
+pub fn walk_toml_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, toml: &mut Toml<'a>) {
+  for nl_expr in &mut toml.exprs {
+    visitor.visit_nl_expression_mut(nl_expr);
+  }
+}
+
+/// Visits the expression of `nl_expr`.
+pub fn walk_nl_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, nl_expr: &mut NLExpression<'a>) {
+  visitor.visit_expression_mut(&mut nl_expr.expr);
+}
+
+/// Visits the key/value pair or table header of `expr`, then its comment. A table header that's shared with anything
+/// outside of the syntax tree is copied first, so only the syntax tree sees the changes.
+pub fn walk_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, expr: &mut Expression<'a>) {
+  if let Some(ref mut keyval) = expr.keyval {
+    visitor.visit_keyval_mut(keyval);
+  }
+  if let Some(ref mut table) = expr.table {
+    visitor.visit_table_mut(Rc::make_mut(table));
+  }
+  if let Some(ref mut comment) = expr.comment {
+    visitor.visit_comment_mut(comment);
+  }
+}
+
+/// Visits the value of `keyval`.
+pub fn walk_keyval_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, keyval: &mut KeyVal<'a>) {
+  visitor.visit_value_mut(&mut keyval.val.borrow_mut());
+}
+
+/// Visits every key of `table`.
+pub fn walk_table_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, table: &mut TableType<'a>) {
+  let t = match *table {
+    TableType::Standard(ref mut t) | TableType::Array(ref mut t) => t,
+  };
+  for key in &mut t.keys {
+    visitor.visit_table_key_mut(key);
+  }
+}
+
+/// Visits the contents of `val` if it's an `Array` or `InlineTable`.
+pub fn walk_value_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, val: &mut TOMLValue<'a>) {
+  match *val {
+    TOMLValue::Array(ref arr) => visitor.visit_array_mut(&mut arr.borrow_mut()),
+    TOMLValue::InlineTable(ref it) => visitor.visit_inline_table_mut(&mut it.borrow_mut()),
+    _ => (),
+  }
+}
+
+/// Visits the comments and newlines after the opening bracket of `array`, its values, then the comments and newlines
+/// before the closing bracket.
+pub fn walk_array_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, array: &mut Array<'a>) {
+  for comment_nl in &mut array.comment_nls1 {
+    visitor.visit_comment_or_newlines_mut(comment_nl);
+  }
+  for array_value in &mut array.values {
+    visitor.visit_array_value_mut(array_value);
+  }
+  for comment_nl in &mut array.comment_nls2 {
+    visitor.visit_comment_or_newlines_mut(comment_nl);
+  }
+}
+
+/// Visits the value of `array_value`, then the comments and newlines that follow it.
+pub fn walk_array_value_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, array_value: &mut ArrayValue<'a>) {
+  visitor.visit_value_mut(&mut array_value.val.borrow_mut());
+  for comment_nl in &mut array_value.comment_nls {
+    visitor.visit_comment_or_newlines_mut(comment_nl);
+  }
+}
+
+/// Visits every key/value pair of `inline_table`.
+pub fn walk_inline_table_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, inline_table: &mut InlineTable<'a>) {
+  for table_keyval in &mut inline_table.keyvals {
+    visitor.visit_table_keyval_mut(table_keyval);
+  }
+}
+
+/// Visits the key/value pair of `table_keyval`, then the comments and newlines that follow it.
+pub fn walk_table_keyval_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, table_keyval: &mut TableKeyVal<'a>) {
+  visitor.visit_keyval_mut(&mut table_keyval.keyval);
+  for comment_nl in &mut table_keyval.comment_nls {
+    visitor.visit_comment_or_newlines_mut(comment_nl);
+  }
+}
+
+/// Visits the comment of `comment_nl`, if it has one.
+pub fn walk_comment_or_newlines_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V,
+  comment_nl: &mut CommentOrNewLines<'a>) {
+  if let CommentOrNewLines::Comment(ref mut c) = *comment_nl {
+    visitor.visit_comment_mut(&mut c.comment);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::rc::Rc;
+  use crate::TOMLParser;
+  use crate::types::Value;
+  use crate::ast::{Visitor, VisitorMut, TOMLValue, KeyVal, TableType, Table, WSKeySep, WSSep, Expression, walk_keyval,
+    walk_expression_mut};
+
+  struct KeyCollector {
+    keys: Vec<String>,
+    tables: Vec<String>,
+    values: usize,
+  }
+
+  impl<'a> Visitor<'a> for KeyCollector {
+    fn visit_keyval(&mut self, keyval: &KeyVal<'a>) {
+      self.keys.push(keyval.key().to_string());
+      walk_keyval(self, keyval);
+    }
+
+    fn visit_table(&mut self, table: &TableType<'a>) {
+      self.tables.push(format!("{table}"));
+    }
+
+    fn visit_value(&mut self, val: &TOMLValue<'a>) {
+      self.values += 1;
+      crate::ast::walk_value(self, val);
+    }
+  }
+
+  struct Renamer;
+
+  impl<'a> VisitorMut<'a> for Renamer {
+    fn visit_keyval_mut(&mut self, keyval: &mut KeyVal<'a>) {
+      if keyval.key() == "old" {
+        keyval.set_key("new");
+      }
+      crate::ast::walk_keyval_mut(self, keyval);
+    }
+
+    fn visit_table_key_mut(&mut self, key: &mut WSKeySep<'a>) {
+      key.ws_mut().set_ws1("");
+      key.ws_mut().set_ws2("");
+    }
+
+    fn visit_value_mut(&mut self, val: &mut TOMLValue<'a>) {
+      if let TOMLValue::Integer(ref mut i) = *val {
+        *i = format!("{i}0").into();
+      }
+      crate::ast::walk_value_mut(self, val);
+    }
+  }
+
+  #[test]
+  fn test_visitor() {
+    let parser = TOMLParser::new();
+    let (parser, _) = parser.parse("a = 1\n[ b . c ]\nd = [2, {e = 3, f = [4]}]\n[[g]]\n");
+    let mut collector = KeyCollector{keys: vec![], tables: vec![], values: 0};
+    parser.visit(&mut collector);
+    assert_eq!(vec!["a", "d", "e", "f"], collector.keys);
+    assert_eq!(vec!["[ b . c ]", "[[g]]"], collector.tables);
+    assert_eq!(7, collector.values);
+  }
+
+  #[test]
+  fn test_visitor_mut() {
+    let parser = TOMLParser::new();
+    let (mut parser, _) = parser.parse("old = 1\n[ b . c ]\nold = [2, {old = 3}]\n");
+    parser.visit_mut(&mut Renamer);
+    assert_eq!("new = 10\n[b.c]\nnew = [20, {new = 30}]\n", format!("{parser}"));
+    assert_eq!(Value::int(10), parser.get_value("new").unwrap());
+    assert_eq!(Value::int(30), parser.get_value("b.c.new[1].new").unwrap());
+    assert_eq!(None, parser.get_value("old"));
+  }
+
+  #[test]
+  fn test_visitor_mut_shared_table() {
+    let table = Rc::new(TableType::Standard(Table::new_str(WSSep::new_str(" ", " "), "b", vec![])));
+    let mut expr = Expression::new(WSSep::new_str("", ""), None, Some(table.clone()), None);
+    walk_expression_mut(&mut Renamer, &mut expr);
+    assert_eq!("[b]", expr.to_string());
+    assert_eq!("[ b ]", table.to_string());
+  }
+}
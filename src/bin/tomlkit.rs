@@ -2,14 +2,26 @@ extern crate pirate;
 extern crate tomllib;
 extern crate csv;
 extern crate env_logger;
-use std::fs::File;
+extern crate serde_json;
+use std::fs;
+use std::fs::{File, OpenOptions};
 use std::env;
+use std::fmt;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::borrow::Cow;
 use std::io;
-use std::io::{Read, Error, Write};
+use std::io::{Read, Error, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use pirate::{Matches, Match, Vars, matches, usage, vars};
 use tomllib::TOMLParser;
-use tomllib::types::{ParseResult, Children, Value, TOMLError, TimeOffset, DateTime, Date, Time, TimeOffsetAmount,
-                     PosNeg};
+use tomllib::schema::Schema;
+use tomllib::lint::Linter;
+use tomllib::types::{ParseResult, ParseError, Children, Value, DocEntry, ValueKind, StrType, KeyPath, KeySegment,
+  FormatOptions, Conflict};
 use csv::Reader;
 
 macro_rules! usage(
@@ -43,11 +55,53 @@ fn main() {
     "/has-children#Given a key or comma separated list of keys, print \"true\" if the key has child keys and \"false\" \
       if the key doesn't have child keys. Optionally use the --set-true and --set-false flags to change what values \
       are printed instead of \"true\" and \"false\".:",
+    "/query#Given a query, print every matching key, one per line, followed by \" = \" and its value if it has one. \
+      A query is a key where \"*\" matches any child key, \"[*]\" matches any array index, \"..\" matches at any \
+      depth and \"[?key == value]\" or \"[?key != value]\" matches children by the value of one of their keys, e.g. \
+      \"dependencies.*.version\", \"bin[*].name\", \"..version\" or \"bin[?name == 'tomlkit']\".:",
     "#File-modifying commands",
     "s/set-value#Given a comma separated list of key followed by value followed by type, set the key's value to the \
       specified value. For instance: \"foo.bar,hello,basic-string,baz.qux,82374,int\" will set the key \"foo.bar\" to \
-      a basic string value of \"hello\" and the key \"baz.qux\" to an integer value of 82374. If multiple keys are \
-      specified and any one of them fails to set a value, the whole command will fail with an error message.:",
+      a basic string value of \"hello\" and the key \"baz.qux\" to an integer value of 82374. The type \"auto\" reads \
+      the value the way it would be written in a TOML file, so it can be any type, including an array or an inline \
+      table, e.g. \"ports,[80,443],auto\". If multiple keys are specified and any one of them fails to set a value, the \
+      whole command will fail with an error message.:",
+    "/insert-value#Given a comma separated list of key followed by value followed by type, add each key that doesn't \
+      exist yet with the specified value, adding any tables it needs. The types are the same as for set-value, plus \
+      \"table\" to add an empty table with the value left empty, e.g. \"owner.name,Tom,bs,servers,,table\". A key \
+      ending in an index one past the end of an array appends to the array, or adds a new table to an array of \
+      tables, e.g. \"ports[2],8080,int,bin[1],,table\". If any one of them fails, the whole command will fail with \
+      an error message.:",
+    "/delete#Given a key or comma separated list of keys, remove each key from the document. A key can be a key with a \
+      value, an array element, a table along with all of its sub-tables, an array of tables, or one table of an array \
+      of tables. If any one of them fails, the whole command will fail with an error message.:",
+    "/rename#Given a comma separated list of key followed by new name, rename the last part of each key to the new \
+      name, e.g. \"server.ip,address\" renames \"server.ip\" to \"server.address\". Renaming a table renames all of its \
+      sub-tables too. If any one of them fails, the whole command will fail with an error message.:",
+    "#Merging: tomlkit merge BASE OURS THEIRS [OUTPUT] three-way merges the changes from BASE to THEIRS into OURS \
+      and writes the result to OUTPUT, or back to OURS. The line of each conflicting key is put between <<<<<<< ours, \
+      ======= and >>>>>>> theirs markers with the line from THEIRS, like git does, the conflicts are printed to \
+      stderr and tomlkit exits with 1. To use it as a git merge driver for *.toml files, set the driver to \"tomlkit merge %O %A %B\".",
+    "#Validating: tomlkit validate --schema SCHEMA FILE... checks each FILE against SCHEMA, a JSON Schema if its name \
+      ends in \".json\", otherwise the same schema written in TOML. Every key that doesn't match the schema is \
+      printed as FILE:LINE: KEY: MESSAGE and makes tomlkit exit with 1.",
+    "#Applying scripts: tomlkit apply --script SCRIPT FILE... makes every edit of SCRIPT to each FILE, in order. A \
+      TOML script is an array of tables named edit, JSON lines have one object per line, and each edit has an op: \
+      set, insert, remove, rename or comment, and a key. set takes a value, insert a value or nothing to insert a \
+      table, rename the new name as to and comment the comment as text, e.g. [[edit]] op = \"set\" key = \
+      \"package.version\" value = \"0.2.0\". If any edit fails the file is left unchanged, the failed edit is printed \
+      and tomlkit exits with 1.",
+    "#Formatting: tomlkit fmt [--check] [--config CONFIG] FILE... rewrites the whitespace of each FILE in one style, \
+      keeping keys, values and comments, or formats stdin to stdout if FILE is -. The style is read from CONFIG, or \
+      else from the first .tomlkit-fmt.toml in the current directory or one of its parents, which can set indent, \
+      array_indent, blank_lines_between_tables and max_blank_lines to a number and space_around_eq, array_padding and \
+      inline_table_padding to true or false. With --check no file is changed, every file that isn't formatted is \
+      printed and tomlkit exits with 1 if there are any.",
+    "#Linting: tomlkit lint [--config CONFIG] FILE... checks each FILE, or stdin if FILE is -, for parse errors, keys \
+      defined twice with different quoting, tables reopened after other tables, strings quoted differently from the \
+      rest, unsorted dependencies, trailing whitespace and mixed line endings. Every problem is printed as \
+      FILE:LINE:COLUMN: MESSAGE (RULE) and makes tomlkit exit with 1. CONFIG, or else the first .tomlkit-lint.toml \
+      in the current directory or one of its parents, turns rules off by name, e.g. string-quotes = false.",
     "#Pre-command Options",
     "h/help#Show this screen.",
     "/set-true#For commands that print \"true\" or \"false\", this will change what value is printed for \"true\", \
@@ -55,40 +109,77 @@ fn main() {
     "/set-false#For commands that print \"true\" or \"false\", this will change what value is printed for \"false\", \
       i.e. --set-false=0 will cause tomlkit to print \"0\" for \"false\" instead of \"false\".:",
     "p/separator#Set the string that will separate multiple results. The default is \", \".:",
+    "f/format#For get-value and get-children, print each value through a format string instead of as it's written. \
+      The placeholders {key}, {value}, {text}, {type}, {style}, {file} and {line} are replaced by the key, its value \
+      as written, its text without quotes or escapes if it's a string, its type (table, array-of-tables, array, \
+      inline-table, string, integer, float, boolean or datetime), its string style (basic, ml-basic, literal or \
+      ml-literal), the path of the document and the line the key is on. Use {{ and }} for braces and \\n and \\t for \
+      newlines and tabs, e.g. --format='{file}:{line}: {key} = {text}'.:",
+    "/output#Print the results of get-value, has-value, get-children and has-children as machine-readable records \
+      instead, one for each key, or for each child key of get-children, made up of the path of the document, the key \
+      and its value, \"true\" or \"false\", or child key. Strings are printed without quotes or escapes. The format \
+      is one of json (a JSON object with \"file\", \"key\" and \"value\" members per line), csv, tsv, or nul (every \
+      field followed by a NUL character). Errors are printed to stderr.:",
+    "/type-format#Given a comma separated list of TYPE=FORMAT, use FORMAT instead of --format for values of that \
+      type, e.g. --type-format='string=\"{text}\",table=[{key}]'.:",
     "array-separator#Set the string that will separate multiple results within an array. The default is \", \". NOTE! \
-    This option is likely to go away in version 0.2.0 in favor of specifying a format string.:",
+    This option is deprecated in favor of --format.:",
     "array-begin#Set the string that will begin a set of results in an array. The default is \"[\". **NOTE!** \
-    This option is likely to go away in version 0.2.0 in favor of specifying a format string.:",
+    This option is deprecated in favor of --format.:",
     "array-end#Set the string that will end a set of results in an array. The default is \"]\". **NOTE!** \
-    This option is likely to go away in version 0.2.0 in favor of specifying a format string.:",
+    This option is deprecated in favor of --format.:",
     "array-separator#Set the string that will separate multiple results within an array. The default is \", \". \
-    **NOTE!** This option is likely to go away in version 0.2.0 in favor of specifying a format string.:",
+    **NOTE!** This option is deprecated in favor of --format.:",
     "array-length#Rather than beginning and ending an array with special characters, print the length of the array \
     as an unsigned 64 bit integer before printing the contents of the array, e.g. 3, \"Alice\", \"Bob\", \"Eve\".",
     "strip-quotes#All types of string values will be printed without single or double quotes. \
-    **NOTE!** This option is likely to go away in version 0.2.0 in favor of specifying a format string.:",
+    **NOTE!** This option is deprecated in favor of --format.:",
     "/strip-quotes#Removes all single and double quotes from returned string values. It does not strip quotes from \
     keys as that would make them unusable. Warning: This will make it impossible to distinguish between each type of \
     string and it can make it impossible to tell the return type e.g. Is 1234 without quotes an integer or some kind \
     of string? Is true a string or a boolean?",
+    "/dry-run#For commands that modify the document, don't write it, print a unified diff between the original \
+      document and the modified one instead.",
+    "/check#For commands that modify the document, don't write it, and exit with 1 if it would have changed. Use it \
+      with --dry-run to also see how it would have changed.",
+    "/backup#For commands that modify the document, keep a copy of the file being replaced with this suffix added to \
+      its name, e.g. --backup=.orig.:",
     "q/quiet#For commands that modify rather than return a result, turn off printing \"Success\" for each successful \
     modification.",
     "#Post-command Options",
-    "/print-doc#Print out the resultant TOML document after all requested changes have been made.",
+    "/print-doc#Print out the resultant TOML document after all requested changes have been made, unless it's already \
+      written to stdout.",
     "#Required arguments",
-    "i/input-file#The path to the TOML document to parse and manipulate. If this isn't used then tomlkit will expect \
-    the names of input files to come through stdin.:",
-    "o/output-file#The path to write the finished TOML document to. If not specified any changes will be \
-    written back to the INPUT_FILE.:",
+    "i/input-file#The path to the TOML document to parse and manipulate, or - to read the document from stdin. If \
+    this isn't used then tomlkit will expect the names of input files to come through stdin.:",
+    "o/output-file#The path to write the finished TOML document to, or - for stdout. If not specified any changes \
+    will be written back to the INPUT_FILE, or to stdout if it's -. When the document is written to stdout, \
+    everything else is printed to stderr and tomlkit exits with 1 if a command fails.:",
   ];
   let _ = env_logger::init();
 
+  let args: Vec<String> = env::args().collect();
+  if args.len() > 1 && args[1] == "merge" {
+    std::process::exit(merge(&args[2..]));
+  }
+  if args.len() > 1 && args[1] == "validate" {
+    std::process::exit(validate(&args[2..]));
+  }
+  if args.len() > 1 && args[1] == "apply" {
+    std::process::exit(apply(&args[2..]));
+  }
+  if args.len() > 1 && args[1] == "fmt" {
+    std::process::exit(fmt(&args[2..]));
+  }
+  if args.len() > 1 && args[1] == "lint" {
+    std::process::exit(lint(&args[2..]));
+  }
+
   let mut vars: Vars = match vars("tomlkit", &options) {
     Ok(v) => v,
     Err(e) => panic!("There was an error parsing argument definitions: {}", e)
   };
 
-  let args: Vec<String> = env::args().collect();
   let matches: Matches = match matches(&args, &mut vars) {
     Ok(m) => m,
     Err(e) => {
@@ -101,10 +192,12 @@ fn main() {
   }
 
   let opts = Options::new(&matches, &vars);
+  // Whether --check found a document that would change
+  let mut changed = false;
   // The file we're operating on
   if matches.has_match("input-file") {
     if let Some(f) = matches.get("input-file") {
-      process_document(f, &opts, &matches, &vars);
+      changed = process_document(f, &opts, &matches, &vars);
     } else {
       usage!(println!("Error: A required argument is missing for input-file."), &vars);
     }
@@ -117,7 +210,7 @@ fn main() {
           if n == 0 {
             break;
           }
-          process_document(&input.trim().to_string(), &opts, &matches, &vars);
+          changed |= process_document(input.trim(), &opts, &matches, &vars);
           input.clear();
         },
         Err(err) => {
@@ -127,6 +220,9 @@ fn main() {
       }
     }
   }
+  if opts.check && changed {
+    std::process::exit(1);
+  }
 }
 
 struct Options {
@@ -140,14 +236,27 @@ struct Options {
   arr_len: bool,
   strip_quotes: bool,
   print_doc: bool,
+  dry_run: bool,
+  check: bool,
+  backup: Option<String>,
+  format: Option<String>,
+  type_formats: Vec<(String, String)>,
+  output: Option<OutputMode>,
+}
+
+impl Default for Options {
+  fn default() -> Options {
+    Options{true_vals: "true".to_string(), false_vals: "false".to_string(),
+            separator: ", ".to_string(), arr_start: "[".to_string(),
+            arr_end: "]".to_string(), arr_sep: ", ".to_string(),
+            quiet: false, arr_len: false, strip_quotes: false, print_doc: false, dry_run: false, check: false,
+            backup: None, format: None, type_formats: vec![], output: None}
+  }
 }
 
 impl Options {
   fn new(matches: &Matches, vars: &Vars) -> Options {
-    let mut opts = Options{true_vals: "true".to_string(), false_vals: "false".to_string(),
-                   separator: ", ".to_string(), arr_start: "[".to_string(),
-                   arr_end: "]".to_string(), arr_sep: ", ".to_string(),
-                   quiet: false, arr_len: false, strip_quotes: false, print_doc: false};
+    let mut opts = Options::default();
     // Pre-command options
     if matches.has_match("set-true") {
       if let Some(t) = matches.get("set-true") {
@@ -197,59 +306,114 @@ impl Options {
     if matches.has_match("strip-quotes") {
       opts.strip_quotes = true;
     }
+    if matches.has_match("quiet") {
+      opts.quiet = true;
+    }
+    if matches.has_match("dry-run") {
+      opts.dry_run = true;
+    }
+    if matches.has_match("check") {
+      opts.check = true;
+    }
+    if matches.has_match("backup") {
+      if let Some(b) = matches.get("backup") {
+        if b.is_empty() {
+          usage!(println!("Error: The backup suffix can't be empty."), &vars);
+        }
+        opts.backup = Some(b.clone());
+      } else {
+        usage!(println!("Error: A required argument is missing for backup."), &vars);
+      }
+    }
+    if matches.has_match("format") {
+      if let Some(f) = matches.get("format") {
+        if let Err(err) = render(f, &Fields::default()) {
+          usage!(println!("Error: {}", err), &vars);
+        }
+        opts.format = Some(f.clone());
+      } else {
+        usage!(println!("Error: A required argument is missing for format."), &vars);
+      }
+    }
+    if matches.has_match("type-format") {
+      if let Some(list) = matches.get("type-format") {
+        match parse_type_formats(list) {
+          Ok(formats) => opts.type_formats = formats,
+          Err(err) => {
+            usage!(println!("Error: {}", err), &vars);
+          },
+        }
+      } else {
+        usage!(println!("Error: A required argument is missing for type-format."), &vars);
+      }
+    }
+    if matches.has_match("output") {
+      opts.output = match matches.get("output").map(|o| o.as_str()) {
+        Some("json") => Some(OutputMode::Json),
+        Some("csv") => Some(OutputMode::Csv),
+        Some("tsv") => Some(OutputMode::Tsv),
+        Some("nul") => Some(OutputMode::Nul),
+        Some(o) => {
+          usage!(println!("Error: Output format \"{}\" not recognized, expected json, csv, tsv or nul.", o), &vars);
+        },
+        None => {
+          usage!(println!("Error: A required argument is missing for output."), &vars);
+        },
+      };
+      if opts.formatted() {
+        usage!(println!("Error: --output can't be combined with --format or --type-format."), &vars);
+      }
+    }
     // Post-command options
     if matches.has_match("print-doc") {
       opts.print_doc = true;
     }
     opts
   }
-}
 
-fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &Vars) {
-  let mut file: String = "".to_string();
+  // True if values should be printed through --format or --type-format templates
+  fn formatted(&self) -> bool {
+    self.format.is_some() || !self.type_formats.is_empty()
+  }
 
-  match get_file(file_path, &mut file)  {
-    Ok(()) => (),
-    Err(err) => {
-      println!("Error \"{}\": Unable to open file: {}", file_path, err);
-      std::process::exit(-1);
+  // The template for values of type `kind`: its --type-format, otherwise --format, otherwise the value as written
+  fn template(&self, kind: &str) -> &str {
+    match self.type_formats.iter().find(|(t, _)| t == kind) {
+      Some((_, f)) => f,
+      None => self.format.as_ref().map_or("{value}", |f| f.as_str()),
     }
   }
-  // Parse the document
-  let parser: TOMLParser = TOMLParser::new();
-  let (mut parser, result) = parser.parse(&file);
-  match result {
-    ParseResult::Partial(_,_,_) => {
-      println!("Error \"{}\": Document only partially parsed. Please correct any errors before trying again.",
-        file_path);
-      std::process::exit(-1);
-    },
-    ParseResult::PartialError(_,_,_,_) => {
-      println!("Error \"{}\": Document only partially parsed with errors. Please correct any errors before trying \
-        again.", file_path);
-      std::process::exit(-1);
-    },
-    ParseResult::Failure(_,_) => {
-      println!("Error \"{}\": Completely failed to parse document. Please correct any error before trying again.",
-        file_path);
-      std::process::exit(-1);
-    },
-    ParseResult::FullError(errors) => {
-      println!("Error \"{}\": Parsed entire document, but with errors: {:?}.", file_path, errors);
-      std::process::exit(-1);
-    },
-    _ => (), // If verbose output Full or FullError
-  }
+}
+
+// The file name that stands for stdin as --input-file and stdout as --output-file
+const STDIO: &str = "-";
+
+// Runs the commands on one document. Returns true if the document was, or with --dry-run or --check would have been,
+// changed.
+fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &Vars) -> bool {
+  let mut file: String = "".to_string();
+  read_document(file_path, &mut file);
+  let mut parser = parse_document(file_path, &file);
 
   let mut command: bool = false;
   let mut result: Vec<Result<String, String>> = vec![Ok("".to_string())];
   let mut out_file = file_path;
   let mut hasval_keycount = 0;
+  let mut changed = false;
+  let mut diff = String::new();
+  // Whether the document goes to stdout, so everything else goes to stderr, and whether a command failed then
+  let (mut to_stdout, mut failed) = (false, false);
   // Commands only one command allowed per invocation for this version
   if matches.has_match("get-value") {
     command = true;
     if let Some(k) = matches.get("get-value") {
-      result.push(get_value(k, &opts.separator, opts.strip_quotes, &parser));
+      if let Some(mode) = opts.output {
+        result.push(records("get-value", k, file_path, &parser).and_then(|r| write_records(mode, &r)));
+      } else if opts.formatted() {
+        result.push(format_values(k, file_path, opts, &parser));
+      } else {
+        result.push(get_value(k, &opts.separator, opts.strip_quotes, &parser));
+      }
     } else {
       usage!(println!("Error \"{}\": A required argument is missing for g/get-value.", file_path), &vars);
     }
@@ -257,7 +421,11 @@ fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &V
   if result[result.len() - 1].is_ok() && matches.has_match("has-value") {
     command = true;
     if let Some(k) = matches.get("has-value") {
-      result.push(has_value(k, &opts.separator, &opts.true_vals, &opts.false_vals, &mut hasval_keycount, &parser));
+      if let Some(mode) = opts.output {
+        result.push(records("has-value", k, file_path, &parser).and_then(|r| write_records(mode, &r)));
+      } else {
+        result.push(has_value(k, &opts.separator, &opts.true_vals, &opts.false_vals, &mut hasval_keycount, &parser));
+      }
     } else {
       usage!(println!("Error \"{}\": A required argument is missing for has-value.", file_path), &vars);
     }
@@ -265,8 +433,14 @@ fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &V
   if result[result.len() - 1].is_ok() && matches.has_match("get-children") {
     command = true;
     if let Some(k) = matches.get("get-children") {
-      result.push(get_children(k, &opts.separator, &opts.arr_start, &opts.arr_end, &opts.arr_sep, opts.arr_len,
-        &parser));
+      if let Some(mode) = opts.output {
+        result.push(records("get-children", k, file_path, &parser).and_then(|r| write_records(mode, &r)));
+      } else if opts.formatted() {
+        result.push(format_children(k, file_path, opts, &parser));
+      } else {
+        result.push(get_children(k, &opts.separator, &opts.arr_start, &opts.arr_end, &opts.arr_sep, opts.arr_len,
+          &parser));
+      }
     } else {
       usage!(println!("Error \"{}\": A required argument is missing for c/get-children.", file_path), &vars);
     }
@@ -274,37 +448,93 @@ fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &V
   if result[result.len() - 1].is_ok() && matches.has_match("has-children") {
     command = true;
     if let Some(k) = matches.get("has-children") {
-      result.push(has_children(k, &opts.separator, &opts.true_vals, &opts.false_vals, &hasval_keycount, &parser));
+      if let Some(mode) = opts.output {
+        result.push(records("has-children", k, file_path, &parser).and_then(|r| write_records(mode, &r)));
+      } else {
+        result.push(has_children(k, &opts.separator, &opts.true_vals, &opts.false_vals, &hasval_keycount, &parser));
+      }
     } else {
       usage!(println!("Error \"{}\": A required argument is missing for has-children.", file_path), &vars);
     }
   }
+  if result[result.len() - 1].is_ok() && matches.has_match("query") {
+    command = true;
+    if let Some(q) = matches.get("query") {
+      result.push(query(q, opts.strip_quotes, &parser));
+    } else {
+      usage!(println!("Error \"{}\": A required argument is missing for query.", file_path), &vars);
+    }
+  }
+  let mut modified = false;
   if result[result.len() - 1].is_ok() && matches.has_match("set-value") {
     command = true;
+    modified = true;
     if let Some(kv) = matches.get("set-value") {
       result.push(set_value(kv, &opts.separator, opts.quiet, &mut parser));
-      if matches.has_match("output-file") {
-        match matches.get("output-file") {
-          Some(out) => out_file = out,
-          None => {
-            usage!(println!("Error \"{}\": A required argument is missing for output-file.", file_path), &vars);
-          },
-        }
-      }
-      if result[result.len() - 1].is_ok() {
-        // Write back out to the file
-        match write_to_file(out_file, &parser) {
-          Ok(()) => (),
-          Err(err) => {
-            println!("Error \"{}\": Unable to write to file: \"{}\". Reason: {}", file_path, out_file, err);
-            std::process::exit(-1);
-          },
-        }
-      }
     } else {
       usage!(println!("Error \"{}\": A required argument is missing for s/set-value.", file_path), &vars);
     }
   }
+  if result[result.len() - 1].is_ok() && matches.has_match("insert-value") {
+    command = true;
+    modified = true;
+    if let Some(kv) = matches.get("insert-value") {
+      result.push(insert_value(kv, &opts.separator, opts.quiet, &mut parser));
+    } else {
+      usage!(println!("Error \"{}\": A required argument is missing for insert-value.", file_path), &vars);
+    }
+  }
+  if result[result.len() - 1].is_ok() && matches.has_match("delete") {
+    command = true;
+    modified = true;
+    if let Some(k) = matches.get("delete") {
+      result.push(delete(k, &opts.separator, opts.quiet, &mut parser));
+    } else {
+      usage!(println!("Error \"{}\": A required argument is missing for delete.", file_path), &vars);
+    }
+  }
+  if result[result.len() - 1].is_ok() && matches.has_match("rename") {
+    command = true;
+    modified = true;
+    if let Some(kn) = matches.get("rename") {
+      result.push(rename(kn, &opts.separator, opts.quiet, &mut parser));
+    } else {
+      usage!(println!("Error \"{}\": A required argument is missing for rename.", file_path), &vars);
+    }
+  }
+  if modified {
+    if matches.has_match("output-file") {
+      match matches.get("output-file") {
+        Some(out) => out_file = out,
+        None => {
+          usage!(println!("Error \"{}\": A required argument is missing for output-file.", file_path), &vars);
+        },
+      }
+    }
+    let new_file = format!("{}", parser);
+    changed = result[result.len() - 1].is_ok() && new_file != file;
+    to_stdout = out_file == STDIO;
+    if opts.dry_run {
+      diff = unified_diff(&file, &new_file, file_path, out_file);
+    } else if opts.check && changed {
+      diff = format!("\"{}\" would be changed.\n", out_file);
+    }
+    if to_stdout && result[result.len() - 1].is_err() {
+      failed = true;
+    } else if to_stdout && !opts.dry_run && !opts.check {
+      print!("{}", new_file);
+    } else if result[result.len() - 1].is_ok() && !opts.dry_run && !opts.check {
+      // Write back out to the file
+      let original = if out_file == file_path { Some(file.as_str()) } else { None };
+      match write_to_file(out_file, &parser, original, opts.backup.as_deref()) {
+        Ok(()) => (),
+        Err(err) => {
+          println!("Error \"{}\": Unable to write to file: \"{}\". Reason: {}", file_path, out_file, err);
+          std::process::exit(-1);
+        },
+      }
+    }
+  }
   if !command {
     // No command specified print usage
     usage!(println!("Error \"{}\": No command was specified.", file_path), &vars);
@@ -312,39 +542,736 @@ fn process_document(file_path: &str, opts: &Options, matches: &Matches, vars: &V
 
   // ************** Print output here! *******************
   let _ = result.remove(0);
+  if opts.output.is_some() {
+    // Records end with their own terminators, and errors go to stderr so that stdout can always be parsed
+    for res in &result {
+      match *res {
+        Ok(ref records) => print!("{}", records),
+        Err(ref err) => eprintln!("Error \"{}\": {}", file_path, err),
+      }
+    }
+    result.clear();
+  }
+  let mut output = String::new();
   for i in 0..result.len() {
     match result[i]  {
-      Ok(ref val) => {
-        if !opts.quiet {
-          print!("{}", val);
-        }
-      },
+      Ok(ref val) => output.push_str(val),
       Err(ref err) => {
-        print!("Error \"{}\": {}", file_path, err);
+        output.push_str(&format!("Error \"{}\": {}", file_path, err));
       }
     }
     if i < result.len() - 1 {
-      print!("{}", opts.separator);
+      output.push_str(&opts.separator);
     }
     if i == result.len() - 1 {
-      println!();
+      output.push('\n');
     }
   }
+  if to_stdout {
+    eprint!("{}", output);
+  } else {
+    print!("{}", output);
+  }
+
+  print!("{}", diff);
+  if failed {
+    std::process::exit(1);
+  }
 
-  // Post-command options
-  if opts.print_doc {
+  // Post-command options. A document written to stdout has already been printed.
+  if opts.print_doc && !(to_stdout && !opts.dry_run && !opts.check) {
     print_doc(&parser);
   }
+  changed
 }
 
-fn write_to_file(file_path: &str, doc: &TOMLParser) -> Result<(), Error> {
-  let mut f = File::create(file_path)?;
-  f.write_all(format!("{}",doc).as_bytes())?;
-  f.sync_all()?;
+fn read_document(file_path: &str, contents: &mut String) {
+  match get_file(file_path, contents)  {
+    Ok(()) => (),
+    Err(err) => {
+      println!("Error \"{}\": Unable to open file: {}", file_path, err);
+      std::process::exit(-1);
+    }
+  }
+}
+
+fn parse_document<'a>(file_path: &str, contents: &'a str) -> TOMLParser<'a> {
+  let parser: TOMLParser = TOMLParser::new();
+  let (parser, result) = parser.parse(contents);
+  match result {
+    ParseResult::Partial(_,_,_) => {
+      println!("Error \"{}\": Document only partially parsed. Please correct any errors before trying again.",
+        file_path);
+      std::process::exit(-1);
+    },
+    ParseResult::PartialError(_,_,_,_) => {
+      println!("Error \"{}\": Document only partially parsed with errors. Please correct any errors before trying \
+        again.", file_path);
+      std::process::exit(-1);
+    },
+    ParseResult::Failure(_,_) => {
+      println!("Error \"{}\": Completely failed to parse document. Please correct any error before trying again.",
+        file_path);
+      std::process::exit(-1);
+    },
+    ParseResult::FullError(errors) => {
+      // Values that TOML doesn't allow but that were read anyway only get a warning, so documents that were accepted
+      // before these were reported still are
+      let errors = errors.borrow();
+      if !errors.iter().all(is_value_error) {
+        println!("Error \"{}\": Parsed entire document, but with errors: {:?}.", file_path, errors);
+        std::process::exit(-1);
+      }
+      for err in errors.iter() {
+        let (line, col) = err.position();
+        eprintln!("Warning \"{}\" line {}, column {}: {:?}", file_path, line, col, err);
+      }
+    },
+    _ => (), // If verbose output Full or FullError
+  }
+  parser
+}
+
+// Returns true if the error is about a value that was still read and is kept as written, like an out of range integer
+// or a string with an invalid escape sequence
+fn is_value_error(err: &ParseError) -> bool {
+  matches!(*err, ParseError::IntegerOverflow(..) | ParseError::IntegerUnderflow(..) | ParseError::InvalidInteger(..) |
+    ParseError::Infinity(..) | ParseError::NegativeInfinity(..) | ParseError::LossOfPrecision(..) |
+    ParseError::InvalidFloat(..) | ParseError::InvalidBoolean(..) | ParseError::InvalidString(..))
+}
+
+// Three-way merges THEIRS into OURS using BASE as the common ancestor and writes the result to OUTPUT, or back to
+// OURS. Works as a git merge driver: tomlkit merge %O %A %B. Returns the exit code, 1 if there were conflicts.
+fn merge(args: &[String]) -> i32 {
+  if args.len() < 3 || args.len() > 4 {
+    println!("Usage: tomlkit merge BASE OURS THEIRS [OUTPUT]");
+    return -1;
+  }
+  let (mut base_file, mut our_file, mut their_file) = (String::new(), String::new(), String::new());
+  read_document(&args[0], &mut base_file);
+  read_document(&args[1], &mut our_file);
+  read_document(&args[2], &mut their_file);
+  let base = parse_document(&args[0], &base_file);
+  let mut ours = parse_document(&args[1], &our_file);
+  let theirs = parse_document(&args[2], &their_file);
+  let conflicts = ours.merge(&base, &theirs);
+  let merged = conflict_markers(&ours, &their_file, &theirs, &conflicts);
+  let out_file = args.get(3).unwrap_or(&args[1]);
+  let original = if *out_file == args[1] { Some(our_file.as_str()) } else { None };
+  if let Err(err) = write_to_file(out_file, &merged, original, None) {
+    println!("Error \"{}\": Unable to write to file: \"{}\". Reason: {}", args[1], out_file, err);
+    return -1;
+  }
+  for conflict in &conflicts {
+    eprintln!("Conflict {}", conflict);
+  }
+  if conflicts.is_empty() { 0 } else { 1 }
+}
+
+// Returns the merged document with git conflict markers around the line of each conflicting key, followed by the line
+// of the key in THEIRS. A key that only one side has gets an empty section on the other side, which goes right after
+// the header of its table in OURS, or at the top for the root table. If OURS doesn't have the table at all, the
+// markers go at the end with the table's header from THEIRS.
+fn conflict_markers(ours: &TOMLParser, their_file: &str, theirs: &TOMLParser, conflicts: &[Conflict]) -> String {
+  let merged = format!("{}", ours);
+  let our_lines: Vec<&str> = merged.split_inclusive('\n').collect();
+  let their_lines: Vec<&str> = their_file.split_inclusive('\n').collect();
+  // The line of OURS each conflict goes on, whether it replaces that line or goes after it, and the lines of THEIRS
+  let mut blocks: Vec<(usize, bool, Vec<usize>)> = vec![];
+  for conflict in conflicts {
+    let their_line = match conflict_line(theirs, &conflict.key) {
+      Some((line, true)) => Some(line),
+      _ => None,
+    };
+    let (our_line, replace, their_header) = match conflict_line(ours, &conflict.key) {
+      Some((line, replace)) => (line, replace, None),
+      None => match table_line(theirs, &conflict.key) {
+        Some(header) => (usize::MAX, false, Some(header)),
+        None => (0, false, None),
+      },
+    };
+    let i = match blocks.iter().position(|b| b.0 == our_line && b.1 == replace) {
+      Some(i) => i,
+      None => {
+        blocks.push((our_line, replace, vec![]));
+        blocks.len() - 1
+      },
+    };
+    for line in their_header.into_iter().chain(their_line) {
+      if !blocks[i].2.contains(&line) {
+        blocks[i].2.push(line);
+      }
+    }
+  }
+  let block_text = |ours_text: &str, their_nums: &[usize]| {
+    let mut text = format!("<<<<<<< ours\n{}", ours_text);
+    if !text.ends_with('\n') {
+      text.push('\n');
+    }
+    text.push_str("=======\n");
+    for &line in their_nums {
+      text.push_str(their_lines[line - 1]);
+      if !text.ends_with('\n') {
+        text.push('\n');
+      }
+    }
+    text.push_str(">>>>>>> theirs\n");
+    text
+  };
+  let mut out = String::new();
+  for (_, _, their_nums) in blocks.iter().filter(|b| b.0 == 0) {
+    out.push_str(&block_text("", their_nums));
+  }
+  for (i, line) in our_lines.iter().enumerate() {
+    let block = blocks.iter().find(|b| b.0 == i + 1);
+    match block {
+      Some(&(_, true, ref their_nums)) => out.push_str(&block_text(line, their_nums)),
+      Some(&(_, false, ref their_nums)) => {
+        out.push_str(line);
+        if !line.ends_with('\n') {
+          out.push('\n');
+        }
+        out.push_str(&block_text("", their_nums));
+      },
+      None => out.push_str(line),
+    }
+  }
+  for (_, _, their_nums) in blocks.iter().filter(|b| b.0 == usize::MAX) {
+    if !out.is_empty() && !out.ends_with('\n') {
+      out.push('\n');
+    }
+    out.push_str(&block_text("", their_nums));
+  }
+  out
+}
+
+// Returns the line of the closest part of key that doc has, and whether that line holds the key's value: either the
+// key itself or the array or inline table that it's in. A line that doesn't is the header of the key's table.
+fn conflict_line(doc: &TOMLParser, key: &str) -> Option<(usize, bool)> {
+  let segments = KeyPath::parse(key).ok()?.segments().to_vec();
+  for len in (1..=segments.len()).rev() {
+    let prefix = KeyPath::from(segments[..len].to_vec()).to_string();
+    if let Some(line) = doc.get_line(prefix.as_str()) {
+      let holds_value = match doc.get_kind(prefix.as_str()) {
+        Some(ValueKind::Table) | Some(ValueKind::ArrayOfTables) => len == segments.len(),
+        _ => true,
+      };
+      return Some((line, holds_value));
+    }
+  }
+  None
+}
+
+// Returns the line of the header of the table that key is in, or None if it's in the root table
+fn table_line(doc: &TOMLParser, key: &str) -> Option<usize> {
+  let segments = KeyPath::parse(key).ok()?.segments().to_vec();
+  (1..segments.len()).rev().map(|len| KeyPath::from(segments[..len].to_vec()).to_string())
+    .find(|prefix| doc.get_kind(prefix.as_str()) == Some(ValueKind::Table))
+    .and_then(|prefix| doc.get_line(prefix))
+}
+
+// Validates each FILE against a schema: tomlkit validate --schema SCHEMA FILE... Returns the exit code, 1 if any file
+// doesn't match the schema.
+fn validate(args: &[String]) -> i32 {
+  let (schema_file, files) = match args.first().map(|a| a.as_str()) {
+    Some("--schema") if args.len() > 2 => (args[1].as_str(), &args[2..]),
+    Some(arg) if arg.starts_with("--schema=") && args.len() > 1 => (&arg["--schema=".len()..], &args[1..]),
+    _ => {
+      println!("Usage: tomlkit validate --schema SCHEMA FILE...");
+      return -1;
+    },
+  };
+  let mut schema_text = String::new();
+  read_document(schema_file, &mut schema_text);
+  let loaded = if schema_file.ends_with(".json") {
+    Schema::from_json(&schema_text)
+  } else {
+    Schema::from_toml(&schema_text)
+  };
+  let schema = match loaded {
+    Ok(schema) => schema,
+    Err(err) => {
+      println!("Error \"{}\": {}", schema_file, err);
+      return -1;
+    },
+  };
+  let mut code = 0;
+  for file_path in files {
+    let mut file = String::new();
+    read_document(file_path, &mut file);
+    let parser = parse_document(file_path, &file);
+    for err in parser.validate(&schema) {
+      match err.line {
+        Some(line) => println!("{}:{}: {}: {}", file_path, line, err.key, err.message),
+        None => println!("{}: {}: {}", file_path, err.key, err.message),
+      }
+      code = 1;
+    }
+  }
+  code
+}
+
+// One edit of an apply script
+enum Edit<'a> {
+  Set(String, Value<'a>),
+  Insert(String, Option<Value<'a>>),
+  Remove(String),
+  Rename(String, String),
+  Comment(String, String),
+}
+
+impl<'a> Edit<'a> {
+  // Makes the edit, returning false if it can't be made
+  fn run(&self, doc: &mut TOMLParser<'a>) -> bool {
+    match *self {
+      Edit::Set(ref key, ref val) => doc.set_value(key.as_str(), val.clone()),
+      Edit::Insert(ref key, Some(ref val)) => doc.insert_value(key.as_str(), val.clone()),
+      Edit::Insert(ref key, None) => doc.insert_table(key.as_str()),
+      Edit::Remove(ref key) => doc.remove_key(key.as_str()),
+      Edit::Rename(ref key, ref to) => doc.rename_key(key.as_str(), to.as_str()),
+      Edit::Comment(ref key, ref text) => doc.set_comment(key.as_str(), text.as_str()),
+    }
+  }
+
+  // Builds an edit from its operation name and fields, however the script spells them
+  fn new(op: &str, key: Option<String>, value: Option<Value<'a>>, to: Option<String>, text: Option<String>)
+    -> Result<Edit<'a>, String> {
+    let key = match key {
+      Some(key) => key,
+      None => return Err("missing \"key\"".to_string()),
+    };
+    match (op, value, to, text) {
+      ("set", Some(value), None, None) => Ok(Edit::Set(key, value)),
+      ("set", None, _, _) => Err("\"set\" needs a \"value\"".to_string()),
+      ("insert", value, None, None) => Ok(Edit::Insert(key, value)),
+      ("remove", None, None, None) => Ok(Edit::Remove(key)),
+      ("rename", None, Some(to), None) => Ok(Edit::Rename(key, to)),
+      ("rename", _, None, _) => Err("\"rename\" needs a \"to\"".to_string()),
+      ("comment", None, None, Some(text)) => Ok(Edit::Comment(key, text)),
+      ("comment", _, _, None) => Err("\"comment\" needs a \"text\"".to_string()),
+      ("set", ..) | ("insert", ..) | ("remove", ..) | ("rename", ..) | ("comment", ..) => {
+        Err(format!("unexpected fields for \"{}\"", op))
+      },
+      _ => Err(format!("operation \"{}\" not recognized, expected set, insert, remove, rename or comment", op)),
+    }
+  }
+}
+
+impl<'a> Display for Edit<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Edit::Set(ref key, ref val) => write!(f, "set \"{}\" to {}", key, val),
+      Edit::Insert(ref key, Some(ref val)) => write!(f, "insert \"{}\" = {}", key, val),
+      Edit::Insert(ref key, None) => write!(f, "insert table \"{}\"", key),
+      Edit::Remove(ref key) => write!(f, "remove \"{}\"", key),
+      Edit::Rename(ref key, ref to) => write!(f, "rename \"{}\" to \"{}\"", key, to),
+      Edit::Comment(ref key, ref text) => write!(f, "comment \"{}\" with \"{}\"", key, text),
+    }
+  }
+}
+
+// Reads the edits of a TOML script, an array of tables named "edit" with the fields "op", "key", "value", "to" and
+// "text"
+fn toml_edits<'a>(doc: &TOMLParser<'a>) -> Result<Vec<Edit<'a>>, String> {
+  let count = match doc.get_children("edit") {
+    Some(Children::Count(c)) if doc.get_kind("edit") == Some(ValueKind::ArrayOfTables) => c.get(),
+    _ => return Err("Expected an array of tables named \"edit\".".to_string()),
+  };
+  let mut edits = vec![];
+  for i in 0..count {
+    let field = |name: &str| {
+      let key = format!("edit[{}].{}", i, name);
+      match doc.get_kind(key.as_str()) {
+        Some(ValueKind::Table) | Some(ValueKind::ArrayOfTables) => {
+          Err(format!("Edit {}: \"{}\" must be a value.", i + 1, name))
+        },
+        Some(_) => Ok(doc.get_value(key)),
+        None => Ok(None),
+      }
+    };
+    let text_field = |name: &str| match field(name)? {
+      Some(ref val) => match val.string_value() {
+        Some(s) => Ok(Some(s)),
+        None => Err(format!("Edit {}: \"{}\" must be a string.", i + 1, name)),
+      },
+      None => Ok(None),
+    };
+    let op = text_field("op")?.unwrap_or_default();
+    let edit = Edit::new(&op, text_field("key")?, field("value")?, text_field("to")?, text_field("text")?);
+    edits.push(edit.map_err(|err| format!("Edit {}: {}.", i + 1, err))?);
+  }
+  Ok(edits)
+}
+
+// Reads the edits of a JSON lines script, one object per line with the members "op", "key", "value", "to" and "text"
+fn json_edits(script: &str) -> Result<Vec<Edit<'static>>, String> {
+  let mut edits = vec![];
+  for (i, line) in script.lines().enumerate().filter(|&(_, l)| !l.trim().is_empty()) {
+    let json: serde_json::Value = match serde_json::from_str(line) {
+      Ok(json) => json,
+      Err(err) => return Err(format!("Line {}: {}.", i + 1, err)),
+    };
+    let obj = match json.as_object() {
+      Some(obj) => obj,
+      None => return Err(format!("Line {}: expected an object.", i + 1)),
+    };
+    let text_field = |name: &str| match obj.get(name) {
+      Some(serde_json::Value::String(s)) => Ok(Some(s.clone())),
+      Some(_) => Err(format!("Line {}: \"{}\" must be a string.", i + 1, name)),
+      None => Ok(None),
+    };
+    let value = match obj.get("value") {
+      Some(json) => Some(json_to_value(json).map_err(|err| format!("Line {}: {}.", i + 1, err))?),
+      None => None,
+    };
+    let op = text_field("op")?.unwrap_or_default();
+    let edit = Edit::new(&op, text_field("key")?, value, text_field("to")?, text_field("text")?);
+    edits.push(edit.map_err(|err| format!("Line {}: {}.", i + 1, err))?);
+  }
+  Ok(edits)
+}
+
+// Converts JSON to a value. Strings become basic strings and objects inline tables.
+fn json_to_value(json: &serde_json::Value) -> Result<Value<'static>, String> {
+  match *json {
+    serde_json::Value::String(ref s) => Ok(Value::string(s)),
+    serde_json::Value::Bool(b) => Ok(Value::bool(b)),
+    serde_json::Value::Number(ref n) => match (n.as_i64(), n.as_f64()) {
+      (Some(i), _) => Ok(Value::int(i)),
+      (None, Some(f)) => Value::float_from_str(format!("{:?}", f)).map_err(|err| format!("{}", err)),
+      (None, None) => Err(format!("number {} doesn't fit in a TOML integer or float", n)),
+    },
+    serde_json::Value::Array(ref values) => {
+      let values: Result<Vec<Value>, String> = values.iter().map(json_to_value).collect();
+      Ok(Value::Array(Rc::new(values?)))
+    },
+    serde_json::Value::Object(ref obj) => {
+      let keyvals: Result<Vec<(Cow<str>, Value)>, String> = obj.iter()
+        .map(|(key, json)| Ok((KeyPath::new().key(key.as_str()).to_string().into(), json_to_value(json)?)))
+        .collect();
+      Ok(Value::InlineTable(Rc::new(keyvals?)))
+    },
+    serde_json::Value::Null => Err("TOML has no null".to_string()),
+  }
+}
+
+// Applies the edits of a script to each FILE: tomlkit apply --script SCRIPT FILE... The script is JSON lines if its
+// name ends in ".json" or ".jsonl", otherwise TOML. Each file gets either all of the edits or, if any of them fails,
+// none. Returns the exit code, 1 if any file was left alone because an edit failed.
+fn apply(args: &[String]) -> i32 {
+  let (script_file, files) = match args.first().map(|a| a.as_str()) {
+    Some("--script") if args.len() > 2 => (args[1].as_str(), &args[2..]),
+    Some(arg) if arg.starts_with("--script=") && args.len() > 1 => (&arg["--script=".len()..], &args[1..]),
+    _ => {
+      println!("Usage: tomlkit apply --script SCRIPT FILE...");
+      return -1;
+    },
+  };
+  let mut script_text = String::new();
+  read_document(script_file, &mut script_text);
+  let script;
+  let loaded = if script_file.ends_with(".json") || script_file.ends_with(".jsonl") {
+    json_edits(&script_text)
+  } else {
+    script = parse_document(script_file, &script_text);
+    toml_edits(&script)
+  };
+  let edits = match loaded {
+    Ok(edits) => edits,
+    Err(err) => {
+      println!("Error \"{}\": {}", script_file, err);
+      return -1;
+    },
+  };
+  let mut code = 0;
+  for file_path in files {
+    let mut file = String::new();
+    read_document(file_path, &mut file);
+    let mut parser = parse_document(file_path, &file);
+    match edits.iter().position(|edit| !edit.run(&mut parser)) {
+      Some(i) => {
+        println!("Error \"{}\": Edit {} failed: {}. The file was left unchanged.", file_path, i + 1, edits[i]);
+        code = 1;
+      },
+      None => {
+        if let Err(err) = write_to_file(file_path, &parser, Some(&file), None) {
+          println!("Error \"{}\": Unable to write to file. Reason: {}", file_path, err);
+          return -1;
+        }
+      },
+    }
+  }
+  code
+}
+
+// The name of the file tomlkit fmt reads its options from when it isn't given --config
+const FMT_CONFIG: &str = ".tomlkit-fmt.toml";
+
+fn fmt(args: &[String]) -> i32 {
+  let (mut check, mut config, mut files) = (false, None, vec![]);
+  let mut i = 0;
+  while i < args.len() {
+    match args[i].as_str() {
+      "--check" => check = true,
+      "--config" if i + 1 < args.len() => {
+        config = Some(args[i + 1].clone());
+        i += 1;
+      },
+      arg if arg.starts_with("--config=") => config = Some(arg["--config=".len()..].to_string()),
+      arg => files.push(arg.to_string()),
+    }
+    i += 1;
+  }
+  if files.is_empty() {
+    println!("Usage: tomlkit fmt [--check] [--config CONFIG] FILE...");
+    return -1;
+  }
+  let opts = match config.or_else(|| find_config(FMT_CONFIG)) {
+    Some(ref config_file) => {
+      let mut config_text = String::new();
+      read_document(config_file, &mut config_text);
+      match FormatOptions::from_toml(&config_text) {
+        Ok(opts) => opts,
+        Err(err) => {
+          println!("Error \"{}\": {}", config_file, err);
+          return -1;
+        },
+      }
+    },
+    None => FormatOptions::default(),
+  };
+  let mut code = 0;
+  for file_path in &files {
+    let mut file = String::new();
+    read_document(file_path, &mut file);
+    let mut parser = parse_document(file_path, &file);
+    parser.format(&opts);
+    let formatted = format!("{}", parser);
+    if check {
+      if formatted != file {
+        println!("\"{}\" isn't formatted.", file_path);
+        code = 1;
+      }
+    } else if file_path == STDIO {
+      print!("{}", formatted);
+    } else if formatted != file {
+      if let Err(err) = write_to_file(file_path, &parser, Some(&file), None) {
+        println!("Error \"{}\": Unable to write to file. Reason: {}", file_path, err);
+        return -1;
+      }
+    }
+  }
+  code
+}
+
+// The name of the file tomlkit lint reads its rules from when it isn't given --config
+const LINT_CONFIG: &str = ".tomlkit-lint.toml";
+
+// Lints each FILE: tomlkit lint [--config CONFIG] FILE... Returns the exit code, 1 if any file has lints.
+fn lint(args: &[String]) -> i32 {
+  let (mut config, mut files) = (None, vec![]);
+  let mut i = 0;
+  while i < args.len() {
+    match args[i].as_str() {
+      "--config" if i + 1 < args.len() => {
+        config = Some(args[i + 1].clone());
+        i += 1;
+      },
+      arg if arg.starts_with("--config=") => config = Some(arg["--config=".len()..].to_string()),
+      arg => files.push(arg.to_string()),
+    }
+    i += 1;
+  }
+  if files.is_empty() {
+    println!("Usage: tomlkit lint [--config CONFIG] FILE...");
+    return -1;
+  }
+  let linter = match config.or_else(|| find_config(LINT_CONFIG)) {
+    Some(ref config_file) => {
+      let mut config_text = String::new();
+      read_document(config_file, &mut config_text);
+      match Linter::from_toml(&config_text) {
+        Ok(linter) => linter,
+        Err(err) => {
+          println!("Error \"{}\": {}", config_file, err);
+          return -1;
+        },
+      }
+    },
+    None => Linter::new(),
+  };
+  let mut code = 0;
+  for file_path in &files {
+    let mut file = String::new();
+    read_document(file_path, &mut file);
+    for lint in linter.lint(&file) {
+      println!("{}:{}", file_path, lint);
+      code = 1;
+    }
+  }
+  code
+}
+
+// Returns the path of the first file called name in the current directory or one of its parents
+fn find_config(name: &str) -> Option<String> {
+  let dir = env::current_dir().ok()?;
+  dir.ancestors().map(|d| d.join(name)).find(|p| p.is_file()).map(|p| p.to_string_lossy().into_owned())
+}
+
+// The number of unchanged lines shown around each change by --dry-run
+const DIFF_CONTEXT: usize = 3;
+
+// A line of a diff: unchanged, only in the old text or only in the new text
+#[derive(Clone, Copy, PartialEq)]
+enum DiffOp {
+  Equal,
+  Delete,
+  Insert,
+}
+
+// Returns a unified diff of two texts, or an empty string if they're the same
+fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+  let a: Vec<&str> = old.split_inclusive('\n').collect();
+  let b: Vec<&str> = new.split_inclusive('\n').collect();
+  // Only the lines between the common prefix and suffix need the quadratic LCS table, which keeps it small for the
+  // few changed lines of an edit
+  let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+  let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+  let (a_end, b_end) = (a.len() - suffix, b.len() - suffix);
+  let (n, m) = (a_end - prefix, b_end - prefix);
+  // lcs[i][j] is the length of the longest common subsequence of a[prefix + i..a_end] and b[prefix + j..b_end]
+  let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+  for i in (0..n).rev() {
+    for j in (0..m).rev() {
+      lcs[i][j] = if a[prefix + i] == b[prefix + j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+    }
+  }
+  let mut ops: Vec<_> = (0..prefix).map(|i| (DiffOp::Equal, i, i)).collect();
+  let (mut i, mut j) = (0, 0);
+  while i < n || j < m {
+    if i < n && j < m && a[prefix + i] == b[prefix + j] {
+      ops.push((DiffOp::Equal, prefix + i, prefix + j));
+      i += 1;
+      j += 1;
+    } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+      ops.push((DiffOp::Delete, prefix + i, prefix + j));
+      i += 1;
+    } else {
+      ops.push((DiffOp::Insert, prefix + i, prefix + j));
+      j += 1;
+    }
+  }
+  ops.extend((0..suffix).map(|k| (DiffOp::Equal, a_end + k, b_end + k)));
+  let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != DiffOp::Equal).collect();
+  if changes.is_empty() {
+    return String::new();
+  }
+  let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+  let mut first = 0;
+  while first < changes.len() {
+    // Changes closer together than twice the context share a hunk
+    let mut last = first;
+    while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * DIFF_CONTEXT + 1 {
+      last += 1;
+    }
+    let start = changes[first].saturating_sub(DIFF_CONTEXT);
+    let end = (changes[last] + DIFF_CONTEXT + 1).min(ops.len());
+    let hunk = &ops[start..end];
+    let old_len = hunk.iter().filter(|op| op.0 != DiffOp::Insert).count();
+    let new_len = hunk.iter().filter(|op| op.0 != DiffOp::Delete).count();
+    out.push_str(&format!("@@ -{} +{} @@\n", hunk_range(hunk[0].1, old_len), hunk_range(hunk[0].2, new_len)));
+    for &(op, i, j) in hunk {
+      let (prefix, line) = match op {
+        DiffOp::Equal => (' ', a[i]),
+        DiffOp::Delete => ('-', a[i]),
+        DiffOp::Insert => ('+', b[j]),
+      };
+      out.push(prefix);
+      out.push_str(line);
+      if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+      }
+    }
+    first = last + 1;
+  }
+  out
+}
+
+// Formats the start line and length of one side of a hunk header, where start is the 0-based index of its first line
+fn hunk_range(start: usize, len: usize) -> String {
+  match len {
+    0 => format!("{},0", start),
+    1 => format!("{}", start + 1),
+    _ => format!("{},{}", start + 1, len),
+  }
+}
+
+// Replaces file_path with the document without ever leaving it half written: the document is written to a temporary
+// file in the same directory, which already has the permissions of the file it replaces, synced to disk and renamed
+// over it. If original is the contents file_path had when it was read and it has changed since, nothing is written.
+// If backup is a suffix, the file being replaced is first copied to its path with the suffix added.
+fn write_to_file<T: Display + ?Sized>(file_path: &str, doc: &T, original: Option<&str>, backup: Option<&str>)
+  -> Result<(), Error> {
+  // Write through symlinks rather than replacing them
+  let path = fs::canonicalize(file_path).unwrap_or_else(|_| Path::new(file_path).to_path_buf());
+  let name = match path.file_name() {
+    Some(name) => name.to_string_lossy().into_owned(),
+    None => return Err(Error::new(ErrorKind::InvalidInput, "not a file name")),
+  };
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+    _ => Path::new(".").to_path_buf(),
+  };
+  let metadata = match fs::metadata(&path) {
+    Ok(metadata) => Some(metadata),
+    Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+    Err(err) => return Err(err),
+  };
+  let tmp_path = dir.join(format!(".{}.tomlkit-{}.tmp", name, process::id()));
+  let written = (|| {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Never let the temporary file be readable by anyone the file it replaces isn't, not even before it's written to
+    #[cfg(unix)]
+    {
+      if let Some(ref metadata) = metadata {
+        options.mode(metadata.permissions().mode());
+      }
+    }
+    let mut f = options.open(&tmp_path)?;
+    // Creating the file applies the umask, so set the exact permissions too
+    if let Some(ref metadata) = metadata {
+      fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+    f.write_all(format!("{}", doc).as_bytes())?;
+    f.sync_all()?;
+    if let Some(original) = original {
+      let mut current = String::new();
+      if metadata.is_none() || get_file(file_path, &mut current).is_err() || current != original {
+        return Err(Error::other("the file changed on disk since it was read"));
+      }
+    }
+    if let (Some(suffix), true) = (backup, metadata.is_some()) {
+      fs::copy(&path, format!("{}{}", path.display(), suffix))?;
+    }
+    fs::rename(&tmp_path, &path)
+  })();
+  if written.is_err() {
+    let _ = fs::remove_file(&tmp_path);
+    return written;
+  }
+  // Make the rename itself durable. Directories can't be opened as files on every platform, so this is best effort.
+  if let Ok(d) = File::open(&dir) {
+    let _ = d.sync_all();
+  }
   Ok(())
 }
 
 fn get_file(file_path: &str, out_file: &mut String) -> Result<(), Error> {
+  if file_path == STDIO {
+    io::stdin().read_to_string(out_file)?;
+    return Ok(());
+  }
   let mut f = File::open(file_path)?;
   f.read_to_string(out_file)?;
   Ok(())
@@ -377,6 +1304,27 @@ fn get_value(csv: &str, sep: &str, strip_quotes: bool, doc: &TOMLParser) -> Resu
   Err(format!("Could not parse keys: \"{:?}\".", csv))
 }
 
+fn query(q: &str, strip_quotes: bool, doc: &TOMLParser) -> Result<String, String> {
+  let matched = match doc.query(q) {
+    Ok(m) => m,
+    Err(err) => return Err(format!("{}", err)),
+  };
+  if matched.is_empty() {
+    return Err(format!("No keys match query: \"{}\".", q));
+  }
+  let mut lines = vec![];
+  for (key, entry) in matched {
+    match entry {
+      DocEntry::Value(ref value) if strip_quotes => {
+        lines.push(format!("{} = {}", key, format!("{}", value).trim_matches(|c| c == '\'' || c == '\"')));
+      },
+      DocEntry::Value(ref value) => lines.push(format!("{} = {}", key, value)),
+      _ => lines.push(key),
+    }
+  }
+  Ok(lines.join("\n"))
+}
+
 fn has_value(csv: &str, sep: &str, true_vals: &str, false_vals: &str, keycount: &mut usize, doc: &TOMLParser)
   -> Result<String, String> {
   let key_results = csv_to_vec(csv);
@@ -534,9 +1482,8 @@ fn has_children(csv: &str, sep: &str, true_vals: &str, false_vals: &str, keycoun
   Err(format!("Could not parse keys: \"{}\".", csv))
 }
 
-#[allow(clippy::collapsible_match)]
 fn set_value(kvs: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<String, String> {
-  let keyval_results = csv_to_vec(kvs);
+  let keyval_results = csv_to_triples(kvs);
   if let Ok(keyvals) = keyval_results {
     if keyvals.len() % 3 != 0 || keyvals.is_empty() {
       return Err(format!("No keys or wrong number of keys specified (must be a multiple of 3): \"{}\".", kvs));
@@ -546,88 +1493,13 @@ fn set_value(kvs: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<
       let key: &str = &keyvals[i*3];
       let val: &str = &keyvals[i*3+1];
       let typ: &str = &keyvals[i*3+2];
-      let val_result: Result<Value, TOMLError>;
-      match typ {
-        "basic-string" | "bs" => val_result = Value::basic_string(val),
-        "ml-basic-string" | "mbs" => val_result = Value::ml_basic_string(val),
-        "literal-string" | "ls" => val_result = Value::literal_string(val),
-        "ml-literal-string" | "mls" => val_result = Value::ml_literal_string(val),
-        "integer" | "int" => val_result = Value::int_from_str(val),
-        "float" | "flt" => val_result = Value::float_from_str(val),
-        "boolean" | "bool" => val_result = Value::bool_from_str(val),
-        "datetime" | "dt" => {
-          let str_val: &str = &val;
-          let tmp_result = Value::datetime_parse(str_val);
-          let mut new_dt: DateTime = DateTime{date: Date{year: "".into(), month: "".into(), day: "".into()}, time: None};
-          let (year, month, day);
-          let (mut hour, mut minute, mut second, mut fraction) = ("".into(), "".into(), "".into(), "".into());
-          let (mut off_hour, mut off_minute, mut pos_neg) = ("".into(), "".into(), PosNeg::Pos);
-          let (mut has_time, mut has_fraction, mut has_offset) = (false, false, false);
-          if let Ok(dtval) = tmp_result {
-            if let Value::DateTime(dt) = dtval {
-              year = dt.date.year.to_string().into();
-              month = dt.date.month.to_string().into();
-              day = dt.date.day.to_string().into();
-              if let Some(ref time) = dt.time {
-                has_time = true;
-                hour = time.hour.to_string().into();
-                minute = time.minute.to_string().into();
-                second = time.second.to_string().into();
-                if let Some(ref frac) = time.fraction {
-                  has_fraction = true;
-                  fraction = frac.to_string().into();
-                }
-                if let Some(ref offset) = time.offset {
-                  if let TimeOffset::Time(ref amount) = *offset {
-                    has_offset = true;
-                    pos_neg = amount.pos_neg;
-                    off_hour = amount.hour.to_string().into();
-                    off_minute = amount.minute.to_string().into();
-                  }
-                }
-              }
-
-              let newoffset = if has_offset {
-                Some(TimeOffset::Time(TimeOffsetAmount{pos_neg, hour: off_hour, minute: off_minute}))
-              } else {
-                None
-              };
-              let newfraction = if has_fraction {
-                Some(fraction)
-              } else {
-                None
-              };
-              let newtime = if has_time {
-                Some(Time{hour, minute, second, fraction: newfraction, offset: newoffset})
-              } else {
-                None
-              };
-              new_dt = DateTime{
-                date: Date{
-                  year,
-                  month,
-                  day,
-                },
-                time: newtime,
-              };
-            }
-            val_result = Ok(Value::DateTime(new_dt));
-          } else {
-            return Err(format!("Unable to parse value: \"{}\" as type: \"{}\" for key: \"{}\"", val, typ, key));
-          }
-        },
-        _ => return Err(format!("Type \"{}\" not recognized for key: \"{}\"", typ, key)),
-      }
-      if let Ok(value) = val_result {
-        if doc.set_value(key, value) {
-          if !quiet {
-            result.push_str("Success");
-          }
-        } else {
-          return Err(format!("Could not set value of key: \"{}\" to value: \"{}\", with type \"{}\"", key, val, typ));
+      let value = parse_value(key, val, typ)?;
+      if doc.set_value(key, value) {
+        if !quiet {
+          result.push_str("Success");
         }
       } else {
-        return Err(format!("Unable to parse value: \"{}\" as type: \"{}\" for key: \"{}\"", val, typ, key));
+        return Err(format!("Could not set value of key: \"{}\" to value: \"{}\", with type \"{}\"", key, val, typ));
       }
       if !quiet && i * 3  < keyvals.len() - 3 {
         result.push_str(sep);
@@ -638,6 +1510,392 @@ fn set_value(kvs: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<
   Err(format!("Could not parse keys: \"{}\".", kvs))
 }
 
+fn insert_value(kvs: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<String, String> {
+  let keyvals = match csv_to_triples(kvs) {
+    Ok(keyvals) => keyvals,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", kvs)),
+  };
+  if keyvals.len() % 3 != 0 || keyvals.is_empty() {
+    return Err(format!("No keys or wrong number of keys specified (must be a multiple of 3): \"{}\".", kvs));
+  }
+  let mut results = vec![];
+  for kvt in keyvals.chunks(3) {
+    let (key, val, typ) = (kvt[0].as_str(), kvt[1].as_str(), kvt[2].as_str());
+    let inserted = if typ == "table" {
+      if !val.is_empty() {
+        return Err(format!("Table \"{}\" can't have a value: \"{}\"", key, val));
+      }
+      doc.insert_table(key)
+    } else {
+      let value = parse_value(key, val, typ)?;
+      doc.insert_value(key, value)
+    };
+    if !inserted {
+      return Err(format!("Could not insert key: \"{}\" with value: \"{}\", with type \"{}\"", key, val, typ));
+    }
+    results.push("Success");
+  }
+  Ok(if quiet { String::new() } else { results.join(sep) })
+}
+
+fn delete(csv: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<String, String> {
+  let keys = match csv_to_vec(csv) {
+    Ok(keys) => keys,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", csv)),
+  };
+  if keys.is_empty() {
+    return Err(format!("No keys specified: \"{}\".", csv));
+  }
+  let mut results = vec![];
+  for key in &keys {
+    if !doc.remove_key(key.as_str()) {
+      return Err(format!("Could not delete key: \"{}\"", key));
+    }
+    results.push("Success");
+  }
+  Ok(if quiet { String::new() } else { results.join(sep) })
+}
+
+fn rename(kns: &str, sep: &str, quiet: bool, doc: &mut TOMLParser) -> Result<String, String> {
+  let keynames = match csv_to_vec(kns) {
+    Ok(keynames) => keynames,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", kns)),
+  };
+  if keynames.len() % 2 != 0 || keynames.is_empty() {
+    return Err(format!("No keys or wrong number of keys specified (must be a multiple of 2): \"{}\".", kns));
+  }
+  let mut results = vec![];
+  for kn in keynames.chunks(2) {
+    if !doc.rename_key(kn[0].as_str(), kn[1].as_str()) {
+      return Err(format!("Could not rename key: \"{}\" to \"{}\"", kn[0], kn[1]));
+    }
+    results.push("Success");
+  }
+  Ok(if quiet { String::new() } else { results.join(sep) })
+}
+
+// Parses the value of a key, value and type triple of set-value or insert-value
+fn parse_value<'a>(key: &str, val: &str, typ: &str) -> Result<Value<'a>, String> {
+  let val_result = match typ {
+    "basic-string" | "bs" => Value::basic_string(val),
+    "ml-basic-string" | "mbs" => Value::ml_basic_string(val),
+    "literal-string" | "ls" => Value::literal_string(val),
+    "ml-literal-string" | "mls" => Value::ml_literal_string(val),
+    "integer" | "int" => Value::int_from_str(val),
+    "float" | "flt" => Value::float_from_str(val),
+    "boolean" | "bool" => Value::bool_from_str(val),
+    "datetime" | "dt" => Value::datetime_parse(val).map(Value::into_owned),
+    "auto" => Value::parse(val),
+    _ => return Err(format!("Type \"{}\" not recognized for key: \"{}\"", typ, key)),
+  };
+  match val_result {
+    Ok(value) => Ok(value),
+    Err(_) => Err(format!("Unable to parse value: \"{}\" as type: \"{}\" for key: \"{}\"", val, typ, key)),
+  }
+}
+
+// What the placeholders of a --format template are replaced with for one key
+#[derive(Default)]
+struct Fields<'a> {
+  key: &'a str,
+  value: String,
+  text: String,
+  kind: &'static str,
+  style: &'static str,
+  file: &'a str,
+  line: String,
+}
+
+const KINDS: [&str; 9] = ["table", "array-of-tables", "array", "inline-table", "string", "integer", "float", "boolean",
+                          "datetime"];
+
+impl<'a> Fields<'a> {
+  fn new(file: &'a str, key: &'a str, doc: &TOMLParser) -> Fields<'a> {
+    let kind = doc.get_kind(key);
+    // Tables have no value, and get_value panics on them
+    let value = match kind {
+      Some(ValueKind::Table) | Some(ValueKind::ArrayOfTables) | None => None,
+      _ => doc.get_value(key),
+    };
+    let (kind, style) = match kind {
+      Some(ValueKind::Table) | None => ("table", ""),
+      Some(ValueKind::ArrayOfTables) => ("array-of-tables", ""),
+      Some(ValueKind::Array) => ("array", ""),
+      Some(ValueKind::InlineTable) => ("inline-table", ""),
+      Some(ValueKind::String(StrType::Basic)) => ("string", "basic"),
+      Some(ValueKind::String(StrType::MLBasic)) => ("string", "ml-basic"),
+      Some(ValueKind::String(StrType::Literal)) => ("string", "literal"),
+      Some(ValueKind::String(StrType::MLLiteral)) => ("string", "ml-literal"),
+      Some(ValueKind::Integer) => ("integer", ""),
+      Some(ValueKind::Float) => ("float", ""),
+      Some(ValueKind::Boolean) => ("boolean", ""),
+      Some(ValueKind::DateTime) => ("datetime", ""),
+    };
+    let value_text = value.as_ref().map(|v| format!("{}", v)).unwrap_or_default();
+    Fields{
+      key,
+      text: value.as_ref().and_then(Value::string_value).unwrap_or_else(|| value_text.clone()),
+      value: value_text,
+      kind,
+      style,
+      file,
+      line: doc.get_line(key).map(|line| line.to_string()).unwrap_or_default(),
+    }
+  }
+}
+
+// Fills in the placeholders of a --format template. "{{" and "}}" stand for braces, and "\n", "\t" and "\\" for a
+// newline, a tab and a backslash.
+fn render(template: &str, fields: &Fields) -> Result<String, String> {
+  let mut result = String::new();
+  let mut chars = template.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '{' if chars.peek() == Some(&'{') => {
+        chars.next();
+        result.push('{');
+      },
+      '}' if chars.peek() == Some(&'}') => {
+        chars.next();
+        result.push('}');
+      },
+      '{' => {
+        let mut name = String::new();
+        loop {
+          match chars.next() {
+            Some('}') => break,
+            Some(c) => name.push(c),
+            None => return Err(format!("Unclosed placeholder \"{{{}\" in format string: \"{}\".", name, template)),
+          }
+        }
+        result.push_str(match name.as_str() {
+          "key" => fields.key,
+          "value" => &fields.value,
+          "text" => &fields.text,
+          "type" => fields.kind,
+          "style" => fields.style,
+          "file" => fields.file,
+          "line" => &fields.line,
+          _ => return Err(format!("Unknown placeholder \"{{{}}}\" in format string: \"{}\".", name, template)),
+        });
+      },
+      '}' => return Err(format!("Unmatched \"}}\" in format string: \"{}\".", template)),
+      '\\' => match chars.next() {
+        Some('n') => result.push('\n'),
+        Some('t') => result.push('\t'),
+        Some('\\') => result.push('\\'),
+        Some(c) => {
+          result.push('\\');
+          result.push(c);
+        },
+        None => result.push('\\'),
+      },
+      c => result.push(c),
+    }
+  }
+  Ok(result)
+}
+
+// Parses the comma separated TYPE=FORMAT list of --type-format
+fn parse_type_formats(list: &str) -> Result<Vec<(String, String)>, String> {
+  let items = match csv_to_vec(list) {
+    Ok(items) => items,
+    Err(_) => return Err(format!("Could not parse type formats: \"{}\".", list)),
+  };
+  let mut formats = vec![];
+  for item in items {
+    let (kind, format) = match item.find('=') {
+      Some(i) => (&item[..i], &item[i + 1..]),
+      None => return Err(format!("Type format \"{}\" is not of the form TYPE=FORMAT.", item)),
+    };
+    if !KINDS.contains(&kind) {
+      return Err(format!("Type \"{}\" not recognized, expected one of: {}.", kind, KINDS.join(", ")));
+    }
+    render(format, &Fields::default())?;
+    formats.push((kind.to_string(), format.to_string()));
+  }
+  Ok(formats)
+}
+
+// Like get_value, but prints each value through the --format and --type-format templates
+fn format_values(csv: &str, file: &str, opts: &Options, doc: &TOMLParser) -> Result<String, String> {
+  let keys = match csv_to_vec(csv) {
+    Ok(keys) => keys,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", csv)),
+  };
+  if keys.is_empty() {
+    return Err(format!("No keys specified: \"{}\".", csv));
+  }
+  let mut results = vec![];
+  for key in &keys {
+    if doc.get_value(key.as_str()).is_none() {
+      return Err(format!("Key \"{}\" not found.", key));
+    }
+    let fields = Fields::new(file, key, doc);
+    results.push(render(opts.template(fields.kind), &fields)?);
+  }
+  Ok(results.join(&opts.separator))
+}
+
+// Like get_children, but prints every child key through the --format and --type-format templates
+fn format_children(csv: &str, file: &str, opts: &Options, doc: &TOMLParser) -> Result<String, String> {
+  let keys = match csv_to_vec(csv) {
+    Ok(keys) => keys,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", csv)),
+  };
+  if keys.is_empty() {
+    return Err(format!("No keys specified: \"{}\".", csv));
+  }
+  let mut results = vec![];
+  for key in &keys {
+    let children = match doc.get_children(key.as_str()) {
+      Some(Children::Count(c)) if c.get() == 0 => return Err(format!("Key \"{}\" has no children.", key)),
+      Some(children) => children,
+      None => return Err(format!("Key \"{}\" not found.", key)),
+    };
+    for child in children.combine_child_keys(key.as_str()) {
+      let fields = Fields::new(file, &child, doc);
+      results.push(render(opts.template(fields.kind), &fields)?);
+    }
+  }
+  Ok(results.join(&opts.separator))
+}
+
+// The machine-readable formats of --output
+#[derive(Clone, Copy)]
+enum OutputMode {
+  Json,
+  Csv,
+  Tsv,
+  Nul,
+}
+
+// What was found for one key in an --output record
+enum Found<'a> {
+  Value(Value<'a>),
+  Bool(bool),
+  Key(String),
+}
+
+// One result of get-value, has-value, get-children or has-children for --output
+struct Record<'a> {
+  file: &'a str,
+  key: String,
+  found: Found<'a>,
+}
+
+// Returns the records of get-value, has-value, get-children or has-children for each key of a comma separated list
+fn records<'a>(command: &str, csv: &str, file: &'a str, doc: &TOMLParser<'a>) -> Result<Vec<Record<'a>>, String> {
+  let keys = match csv_to_vec(csv) {
+    Ok(keys) => keys,
+    Err(_) => return Err(format!("Could not parse keys: \"{}\".", csv)),
+  };
+  if keys.is_empty() {
+    return Err(format!("No keys specified: \"{}\".", csv));
+  }
+  let mut records = vec![];
+  for key in keys {
+    match command {
+      "get-value" => match doc.get_value_ref(key.as_str()) {
+        Some(value) => records.push(Record{file, key, found: Found::Value(value.to_value())}),
+        None => return Err(format!("Key \"{}\" not found.", key)),
+      },
+      "has-value" => {
+        let found = Found::Bool(doc.get_value_ref(key.as_str()).is_some());
+        records.push(Record{file, key, found});
+      },
+      "get-children" => {
+        let children = match doc.get_children(key.as_str()) {
+          Some(Children::Count(c)) if c.get() == 0 => return Err(format!("Key \"{}\" has no children.", key)),
+          Some(children) => children.combine_child_keys(key.as_str()),
+          None => return Err(format!("Key \"{}\" not found.", key)),
+        };
+        for child in children {
+          records.push(Record{file, key: key.clone(), found: Found::Key(child)});
+        }
+      },
+      _ => {
+        let found = Found::Bool(match doc.get_children(key.as_str()) {
+          Some(Children::Count(c)) => c.get() > 0,
+          Some(Children::Keys(keys)) => !keys.borrow().is_empty(),
+          None => false,
+        });
+        records.push(Record{file, key, found});
+      },
+    }
+  }
+  Ok(records)
+}
+
+// Writes records as JSON lines, CSV or TSV rows of file, key and value, or as NUL terminated fields
+fn write_records(mode: OutputMode, records: &[Record]) -> Result<String, String> {
+  let mut out = String::new();
+  let mut writer = match mode {
+    OutputMode::Tsv => csv::Writer::from_memory().delimiter(b'\t'),
+    _ => csv::Writer::from_memory(),
+  };
+  for record in records {
+    match mode {
+      OutputMode::Json => {
+        let mut obj = serde_json::Map::new();
+        obj.insert("file".to_string(), serde_json::Value::from(record.file));
+        obj.insert("key".to_string(), serde_json::Value::from(record.key.as_str()));
+        obj.insert("value".to_string(), match record.found {
+          Found::Value(ref value) => value_to_json(value),
+          Found::Bool(b) => serde_json::Value::Bool(b),
+          Found::Key(ref key) => serde_json::Value::from(key.as_str()),
+        });
+        out.push_str(&serde_json::Value::Object(obj).to_string());
+        out.push('\n');
+      },
+      OutputMode::Csv | OutputMode::Tsv => {
+        if let Err(err) = writer.write(vec![record.file, &record.key, &found_text(&record.found)].into_iter()) {
+          return Err(format!("Could not write the record for key \"{}\": {}.", record.key, err));
+        }
+      },
+      OutputMode::Nul => {
+        for field in &[record.file, &record.key, &found_text(&record.found)] {
+          out.push_str(field);
+          out.push('\0');
+        }
+      },
+    }
+  }
+  out.push_str(writer.as_string());
+  Ok(out)
+}
+
+// The text of a record's value in CSV, TSV and NUL output: strings without quotes or escapes, anything else as written
+fn found_text(found: &Found) -> String {
+  match *found {
+    Found::Value(ref value) => value.string_value().unwrap_or_else(|| format!("{}", value)),
+    Found::Bool(b) => b.to_string(),
+    Found::Key(ref key) => key.clone(),
+  }
+}
+
+// Converts a value to JSON. Datetimes become strings, and so do integers that don't fit in 64 bits and floats that JSON
+// can't represent, like inf and nan, which are written as they are in the document.
+fn value_to_json(value: &Value) -> serde_json::Value {
+  match *value {
+    Value::Integer(ref n) => n.replace('_', "").parse::<i64>()
+      .map_or_else(|_| serde_json::Value::from(n.to_string()), serde_json::Value::from),
+    Value::Float(ref n) => n.replace('_', "").parse::<f64>().ok().and_then(serde_json::Number::from_f64)
+      .map_or_else(|| serde_json::Value::from(n.to_string()), serde_json::Value::Number),
+    Value::Boolean(b) => serde_json::Value::Bool(b),
+    Value::String(..) => serde_json::Value::from(value.string_value().unwrap_or_default()),
+    Value::DateTime(ref dt) => serde_json::Value::from(dt.to_string()),
+    Value::Array(ref values) => serde_json::Value::Array(values.iter().map(value_to_json).collect()),
+    Value::InlineTable(ref keyvals) => serde_json::Value::Object(keyvals.iter().map(|(key, value)| {
+      let name = match KeyPath::parse(key).ok().as_ref().map(KeyPath::segments) {
+        Some([KeySegment::Key(name)]) => name.clone(),
+        _ => key.to_string(),
+      };
+      (name, value_to_json(value))
+    }).collect()),
+  }
+}
+
 fn csv_to_vec(csv: &str) -> Result<Vec<String>, csv::Error> {
   let mut fields = vec![];
   let mut rdr = Reader::from_string(csv).has_headers(false).escape(Some(b'\\')).quote(b'\0');
@@ -652,7 +1910,368 @@ fn csv_to_vec(csv: &str) -> Result<Vec<String>, csv::Error> {
   Ok(fields)
 }
 
+// Like csv_to_vec for key, value, type triples, but keeps the commas of arrays and inline tables in "auto" values, so
+// that a value like "[1,2]" or "{a=1,b=2}" stays one field. Values of any other type are never joined.
+fn csv_to_triples(csv: &str) -> Result<Vec<String>, csv::Error> {
+  let fields = csv_to_vec(csv)?;
+  let mut triples = vec![];
+  let mut i = 0;
+  while i + 2 < fields.len() {
+    // The value is the fields up to where its brackets are closed, if the type after them is auto
+    let mut value = fields[i + 1].clone();
+    let mut end = i + 1;
+    while nesting_depth(&value) > 0 && end + 2 < fields.len() {
+      end += 1;
+      value.push(',');
+      value.push_str(&fields[end]);
+    }
+    if end > i + 1 && (nesting_depth(&value) > 0 || fields[end + 1] != "auto") {
+      value = fields[i + 1].clone();
+      end = i + 1;
+    }
+    triples.push(fields[i].clone());
+    triples.push(value);
+    triples.push(fields[end + 1].clone());
+    i = end + 2;
+  }
+  triples.extend_from_slice(&fields[i..]);
+  Ok(triples)
+}
+
+// Returns how many more "[" and "{" than "]" and "}" there are in text, outside of strings
+fn nesting_depth(text: &str) -> i32 {
+  let (mut depth, mut quote, mut escaped) = (0, None, false);
+  for c in text.chars() {
+    match (quote, c) {
+      (Some('"'), '\\') => escaped = !escaped,
+      (Some(q), _) => {
+        if c == q && !escaped {
+          quote = None;
+        }
+        escaped = false;
+      },
+      (None, '"') | (None, '\'') => quote = Some(c),
+      (None, '[') | (None, '{') => depth += 1,
+      (None, ']') | (None, '}') => depth -= 1,
+      _ => (),
+    }
+  }
+  depth
+}
+
 fn print_doc(doc: &TOMLParser) {
   //unimplemented!();
   println!("{}", doc);
 }
+
+#[cfg(test)]
+mod test {
+  use tomllib::TOMLParser;
+  use super::{conflict_markers, render, parse_type_formats, records, write_records, unified_diff, hunk_range, Fields,
+              write_to_file, apply, csv_to_triples, json_edits, toml_edits, json_to_value, Edit, Options, OutputMode};
+  use tomllib::types::Value;
+  use std::fs;
+  use std::path::PathBuf;
+
+  #[test]
+  fn test_conflict_markers() {
+    let base_file = "name = \"app\"\nport = 80\n[db]\nhost = \"localhost\"\n[cache]\nsize = 32\n";
+    let our_file = "name = \"mine\"\nport = 80\n[db]\nhost = \"db.local\"\n";
+    let their_file = "name = \"theirs\"\nport = 8080\n[db]\nhost = \"127.0.0.1\"\nuser = \"admin\"\n[cache]\nsize = 64";
+    let (base, _) = TOMLParser::new().parse(base_file);
+    let (mut ours, _) = TOMLParser::new().parse(our_file);
+    let (theirs, _) = TOMLParser::new().parse(their_file);
+    let conflicts = ours.merge(&base, &theirs);
+    assert_eq!(3, conflicts.len());
+    assert_eq!("<<<<<<< ours\nname = \"mine\"\n=======\nname = \"theirs\"\n>>>>>>> theirs\nport = 8080\n[db]\n\
+      <<<<<<< ours\nhost = \"db.local\"\n=======\nhost = \"127.0.0.1\"\n>>>>>>> theirs\nuser = \"admin\"\n\
+      <<<<<<< ours\n=======\n[cache]\nsize = 64\n>>>>>>> theirs\n",
+      conflict_markers(&ours, their_file, &theirs, &conflicts));
+  }
+
+  #[test]
+  fn test_conflict_markers_missing_key() {
+    let (base, _) = TOMLParser::new().parse("a = 1\n[t]\nb = 1\nc = { d = 1 }\n");
+    let (mut ours, _) = TOMLParser::new().parse("[t]\nb = 2\nc = { d = 2 }\n");
+    let their_file = "a = 2\n[t]\nc = {}\n";
+    let (theirs, _) = TOMLParser::new().parse(their_file);
+    let conflicts = ours.merge(&base, &theirs);
+    assert_eq!("<<<<<<< ours\n=======\na = 2\n>>>>>>> theirs\n[t]\n<<<<<<< ours\nb = 2\n=======\n>>>>>>> theirs\n\
+      <<<<<<< ours\nc = { d = 2 }\n=======\nc = {}\n>>>>>>> theirs\n",
+      conflict_markers(&ours, their_file, &theirs, &conflicts));
+  }
+
+  #[test]
+  fn test_render() {
+    let (doc, _) = TOMLParser::new().parse("[server]\nname = \"web\"\nports = [80, 443]\n");
+    let fields = Fields::new("app.toml", "server.name", &doc);
+    assert_eq!(Ok("server.name = \"web\" (web, string/basic)".to_string()),
+      render("{key} = {value} ({text}, {type}/{style})", &fields));
+    assert_eq!(Ok("app.toml:2: {key}".to_string()), render("{file}:{line}: {{key}}", &fields));
+    assert_eq!(Ok("a\nb\tc\\d\\q\\".to_string()), render("a\\nb\\tc\\\\d\\q\\", &fields));
+    let fields = Fields::new("app.toml", "server.ports", &doc);
+    assert_eq!(Ok("[80, 443] array".to_string()), render("{text} {type}", &fields));
+    let fields = Fields::new("app.toml", "server", &doc);
+    assert_eq!(Ok("table 1".to_string()), render("{type} {line}", &fields));
+  }
+
+  #[test]
+  fn test_render_errors() {
+    let fields = Fields::default();
+    assert_eq!(Err("Unknown placeholder \"{name}\" in format string: \"{name}\".".to_string()),
+      render("{name}", &fields));
+    assert_eq!(Err("Unclosed placeholder \"{key\" in format string: \"{key\".".to_string()), render("{key", &fields));
+    assert_eq!(Err("Unmatched \"}\" in format string: \"key}\".".to_string()), render("key}", &fields));
+  }
+
+  #[test]
+  fn test_parse_type_formats() {
+    assert_eq!(Ok(vec![("string".to_string(), "\"{text}\"".to_string()), ("table".to_string(), "[{key}]".to_string())]),
+      parse_type_formats("string=\"{text}\",table=[{key}]"));
+    assert_eq!(Ok(vec![("integer".to_string(), "{key}={value}".to_string())]),
+      parse_type_formats("integer={key}={value}"));
+    assert_eq!(Err("Type format \"string\" is not of the form TYPE=FORMAT.".to_string()), parse_type_formats("string"));
+    assert!(parse_type_formats("number={value}").unwrap_err().starts_with("Type \"number\" not recognized"));
+    assert_eq!(Err("Unknown placeholder \"{val}\" in format string: \"{val}\".".to_string()),
+      parse_type_formats("string={val}"));
+  }
+
+  #[test]
+  fn test_type_format_fallback() {
+    let mut opts = Options::default();
+    assert!(!opts.formatted());
+    assert_eq!("{value}", opts.template("string"));
+    opts.type_formats = parse_type_formats("string={text}").unwrap();
+    assert!(opts.formatted());
+    assert_eq!("{text}", opts.template("string"));
+    assert_eq!("{value}", opts.template("integer"));
+    opts.format = Some("{key}: {value}".to_string());
+    assert_eq!("{text}", opts.template("string"));
+    assert_eq!("{key}: {value}", opts.template("integer"));
+  }
+
+  #[test]
+  fn test_records() {
+    let (doc, _) = TOMLParser::new().parse("name = \"app\"\n[deps]\nfoo = \"1.0\"\nbar = { version = \"2\" }\n");
+    let found = records("get-value", "name,deps.foo", "app.toml", &doc).unwrap();
+    assert_eq!(vec!["name", "deps.foo"], found.iter().map(|r| r.key.as_str()).collect::<Vec<_>>());
+    assert_eq!(Ok("app.toml,name,app\napp.toml,deps.foo,1.0\n".to_string()), write_records(OutputMode::Csv, &found));
+    let found = records("get-children", "deps", "app.toml", &doc).unwrap();
+    assert_eq!(Ok("app.toml\tdeps\tdeps.foo\napp.toml\tdeps\tdeps.bar\n".to_string()),
+      write_records(OutputMode::Tsv, &found));
+    let found = records("has-value", "name,deps", "app.toml", &doc).unwrap();
+    assert_eq!(Ok("app.toml\0name\0true\0app.toml\0deps\0false\0".to_string()), write_records(OutputMode::Nul, &found));
+    let found = records("has-children", "deps,name,missing", "app.toml", &doc).unwrap();
+    assert_eq!(Ok("{\"file\":\"app.toml\",\"key\":\"deps\",\"value\":true}\n\
+      {\"file\":\"app.toml\",\"key\":\"name\",\"value\":false}\n\
+      {\"file\":\"app.toml\",\"key\":\"missing\",\"value\":false}\n".to_string()),
+      write_records(OutputMode::Json, &found));
+    assert_eq!(Err("Key \"missing\" not found.".to_string()),
+      records("get-value", "name,missing", "app.toml", &doc).map(|r| r.len()));
+    assert_eq!(Err("Key \"name\" has no children.".to_string()),
+      records("get-children", "name", "app.toml", &doc).map(|r| r.len()));
+  }
+
+  #[test]
+  fn test_write_records_json() {
+    let (doc, _) = TOMLParser::new().parse("int = 1_000\nbig = 9_223_372_036_854_775_808\nflt = 2.5\ninf = 1e400\n\
+      when = 1979-05-27T07:32:00Z\nlist = [1, \"a\"]\nt = { \"a.b\" = 1e400, c = true }\n");
+    let found = records("get-value", "int,big,flt,inf,when,list,t", "f", &doc).unwrap();
+    assert_eq!(Ok("{\"file\":\"f\",\"key\":\"int\",\"value\":1000}\n\
+      {\"file\":\"f\",\"key\":\"big\",\"value\":\"9_223_372_036_854_775_808\"}\n\
+      {\"file\":\"f\",\"key\":\"flt\",\"value\":2.5}\n\
+      {\"file\":\"f\",\"key\":\"inf\",\"value\":\"1e400\"}\n\
+      {\"file\":\"f\",\"key\":\"when\",\"value\":\"1979-05-27T07:32:00Z\"}\n\
+      {\"file\":\"f\",\"key\":\"list\",\"value\":[1,\"a\"]}\n\
+      {\"file\":\"f\",\"key\":\"t\",\"value\":{\"a.b\":\"1e400\",\"c\":true}}\n".to_string()),
+      write_records(OutputMode::Json, &found));
+  }
+
+  #[test]
+  fn test_unified_diff() {
+    assert_eq!("", unified_diff("a\nb\n", "a\nb\n", "old", "new"));
+    let old: String = (1..=20).map(|n| format!("{n}\n")).collect();
+    // Changes at lines 5 and 11 are 6 lines apart, so their contexts touch and they share a hunk, the one at 19 doesn't
+    let new: String = (1..=20).map(|n| match n {
+      5 => "five\n".to_string(),
+      11 => String::new(),
+      19 => "19\nnineteen\n".to_string(),
+      _ => format!("{n}\n"),
+    }).collect();
+    assert_eq!("--- a.toml\n+++ b.toml\n\
+      @@ -2,13 +2,12 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n-11\n 12\n 13\n 14\n\
+      @@ -17,4 +16,5 @@\n 17\n 18\n 19\n+nineteen\n 20\n", unified_diff(&old, &new, "a.toml", "b.toml"));
+  }
+
+  #[test]
+  fn test_unified_diff_edges() {
+    assert_eq!("--- old\n+++ new\n@@ -0,0 +1 @@\n+a = 1\n", unified_diff("", "a = 1\n", "old", "new"));
+    assert_eq!("--- old\n+++ new\n@@ -1,2 +0,0 @@\n-a = 1\n-b = 2\n", unified_diff("a = 1\nb = 2\n", "", "old", "new"));
+    assert_eq!("--- old\n+++ new\n@@ -1 +1 @@\n-a = 1\n\\ No newline at end of file\n+a = 1\n",
+      unified_diff("a = 1", "a = 1\n", "old", "new"));
+    assert_eq!("--- old\n+++ new\n@@ -1,2 +1,2 @@\n a = 1\n-b = 2\n+b = 3\n\\ No newline at end of file\n",
+      unified_diff("a = 1\nb = 2\n", "a = 1\nb = 3", "old", "new"));
+    assert_eq!("--- old\n+++ new\n@@ -1,3 +1,2 @@\n a\n a\n-a\n", unified_diff("a\na\na\n", "a\na\n", "old", "new"));
+  }
+
+  #[test]
+  fn test_hunk_range() {
+    assert_eq!("4,0", hunk_range(4, 0));
+    assert_eq!("5", hunk_range(4, 1));
+    assert_eq!("5,3", hunk_range(4, 3));
+  }
+
+  // Returns a path in an empty directory of its own in the temp directory, for the write_to_file tests
+  fn temp_path(test: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tomlkit-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+  }
+
+  #[test]
+  fn test_write_to_file() {
+    let path = temp_path("write", "a.toml");
+    let file = path.to_str().unwrap();
+    write_to_file(file, "a = 1\n", None, None).unwrap();
+    assert_eq!("a = 1\n", fs::read_to_string(&path).unwrap());
+    write_to_file(file, "a = 2\n", Some("a = 1\n"), None).unwrap();
+    assert_eq!("a = 2\n", fs::read_to_string(&path).unwrap());
+    // No temporary file is left behind
+    assert_eq!(1, fs::read_dir(path.parent().unwrap()).unwrap().count());
+  }
+
+  #[test]
+  fn test_write_to_file_changed_on_disk() {
+    let path = temp_path("changed", "a.toml");
+    let file = path.to_str().unwrap();
+    fs::write(&path, "a = 3\n").unwrap();
+    let err = write_to_file(file, "a = 2\n", Some("a = 1\n"), Some(".bak")).unwrap_err();
+    assert_eq!("the file changed on disk since it was read", err.to_string());
+    assert_eq!("a = 3\n", fs::read_to_string(&path).unwrap());
+    assert_eq!(1, fs::read_dir(path.parent().unwrap()).unwrap().count());
+  }
+
+  #[test]
+  fn test_write_to_file_backup() {
+    let path = temp_path("backup", "a.toml");
+    let file = path.to_str().unwrap();
+    fs::write(&path, "a = 1\n").unwrap();
+    write_to_file(file, "a = 2\n", Some("a = 1\n"), Some(".bak")).unwrap();
+    assert_eq!("a = 2\n", fs::read_to_string(&path).unwrap());
+    assert_eq!("a = 1\n", fs::read_to_string(format!("{}.bak", file)).unwrap());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_write_to_file_keeps_mode() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = temp_path("mode", "a.toml");
+    let file = path.to_str().unwrap();
+    fs::write(&path, "a = 1\n").unwrap();
+    for &mode in &[0o600, 0o640, 0o444] {
+      fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+      let contents = fs::read_to_string(&path).unwrap();
+      write_to_file(file, &format!("mode = {:o}\n", mode), Some(&contents), None).unwrap();
+      assert_eq!(mode, fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+    }
+    assert_eq!("mode = 444\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn test_edit_new() {
+    let edit = |op: &str, value: Option<Value<'static>>, to: Option<&str>, text: Option<&str>| {
+      Edit::new(op, Some("a.b".to_string()), value, to.map(String::from), text.map(String::from)).map(|e| e.to_string())
+    };
+    assert_eq!(Ok("set \"a.b\" to 1".to_string()), edit("set", Some(Value::int(1)), None, None));
+    assert_eq!(Ok("insert \"a.b\" = 1".to_string()), edit("insert", Some(Value::int(1)), None, None));
+    assert_eq!(Ok("insert table \"a.b\"".to_string()), edit("insert", None, None, None));
+    assert_eq!(Ok("remove \"a.b\"".to_string()), edit("remove", None, None, None));
+    assert_eq!(Ok("rename \"a.b\" to \"c\"".to_string()), edit("rename", None, Some("c"), None));
+    assert_eq!(Ok("comment \"a.b\" with \"# hi\"".to_string()), edit("comment", None, None, Some("# hi")));
+    assert_eq!(Err("\"set\" needs a \"value\"".to_string()), edit("set", None, None, None));
+    assert_eq!(Err("\"rename\" needs a \"to\"".to_string()), edit("rename", None, None, None));
+    assert_eq!(Err("\"comment\" needs a \"text\"".to_string()), edit("comment", None, None, None));
+    assert_eq!(Err("unexpected fields for \"remove\"".to_string()), edit("remove", None, Some("c"), None));
+    assert_eq!(Err("operation \"add\" not recognized, expected set, insert, remove, rename or comment".to_string()),
+      edit("add", None, None, None));
+    assert_eq!(Err("missing \"key\"".to_string()), Edit::new("remove", None, None, None, None).map(|e| e.to_string()));
+  }
+
+  #[test]
+  fn test_json_to_value() {
+    let json = |text: &str| json_to_value(&serde_json::from_str(text).unwrap());
+    assert_eq!(Ok(Value::int(-3)), json("-3"));
+    assert_eq!(Ok(Value::float(2.5)), json("2.5"));
+    assert_eq!(Ok(Value::bool(true)), json("true"));
+    assert_eq!(Ok(Value::string("a\"b")), json("\"a\\\"b\""));
+    assert_eq!(Ok("[1, \"x\", [true]]".to_string()), json("[1, \"x\", [true]]").map(|v| v.to_string()));
+    assert_eq!(Ok("{\"a.b\" = {port = 80}, name = \"db\"}".to_string()),
+      json("{\"name\": \"db\", \"a.b\": {\"port\": 80}}").map(|v| v.to_string()));
+    assert!(matches!(json("{}"), Ok(Value::InlineTable(_))));
+    assert_eq!(Err("TOML has no null".to_string()), json("null"));
+  }
+
+  #[test]
+  fn test_json_edits() {
+    let edits = json_edits("{\"op\": \"set\", \"key\": \"a\", \"value\": {\"b\": 1}}\n\n\
+      {\"op\": \"rename\", \"key\": \"c\", \"to\": \"d\"}\n").unwrap();
+    assert_eq!(vec!["set \"a\" to {b = 1}", "rename \"c\" to \"d\""],
+      edits.iter().map(|e| e.to_string()).collect::<Vec<_>>());
+    assert_eq!(Err("Line 2: expected an object.".to_string()),
+      json_edits("{\"op\": \"remove\", \"key\": \"a\"}\n[]").map(|e| e.len()));
+    assert_eq!(Err("Line 1: \"key\" must be a string.".to_string()),
+      json_edits("{\"op\": \"remove\", \"key\": 1}").map(|e| e.len()));
+    assert_eq!(Err("Line 1: TOML has no null.".to_string()),
+      json_edits("{\"op\": \"set\", \"key\": \"a\", \"value\": null}").map(|e| e.len()));
+    assert_eq!(Err("Line 1: \"set\" needs a \"value\".".to_string()),
+      json_edits("{\"op\": \"set\", \"key\": \"a\"}").map(|e| e.len()));
+    assert!(json_edits("{\"op\": ").map(|e| e.len()).unwrap_err().starts_with("Line 1: "));
+  }
+
+  #[test]
+  fn test_toml_edits() {
+    let (script, _) = TOMLParser::new().parse("[[edit]]\nop = \"set\"\nkey = \"a\"\nvalue = [1, 2]\n\
+      [[edit]]\nop = \"insert\"\nkey = \"t\"\n[[edit]]\nop = \"comment\"\nkey = \"a\"\ntext = \"# list\"\n");
+    let edits = toml_edits(&script).unwrap();
+    assert_eq!(vec!["set \"a\" to [1, 2]", "insert table \"t\"", "comment \"a\" with \"# list\""],
+      edits.iter().map(|e| e.to_string()).collect::<Vec<_>>());
+    let (script, _) = TOMLParser::new().parse("[edit]\nop = \"remove\"\nkey = \"a\"\n");
+    assert_eq!(Err("Expected an array of tables named \"edit\".".to_string()), toml_edits(&script).map(|e| e.len()));
+    let (script, _) = TOMLParser::new().parse("[[edit]]\nop = \"remove\"\nkey = 1\n");
+    assert_eq!(Err("Edit 1: \"key\" must be a string.".to_string()), toml_edits(&script).map(|e| e.len()));
+    let (script, _) = TOMLParser::new().parse("[[edit]]\nop = \"remove\"\nkey = \"a\"\n[[edit]]\nop = \"set\"\n\
+      key = \"a\"\n[edit.value]\nb = 1\n");
+    assert_eq!(Err("Edit 2: \"value\" must be a value.".to_string()), toml_edits(&script).map(|e| e.len()));
+  }
+
+  #[test]
+  fn test_apply() {
+    let path = temp_path("apply", "a.toml");
+    let file = path.to_str().unwrap().to_string();
+    let script = path.with_file_name("edits.jsonl");
+    fs::write(&path, "a = 1\nb = 2\n").unwrap();
+    fs::write(&script, "{\"op\": \"set\", \"key\": \"a\", \"value\": {\"x\": \"y\"}}\n\
+      {\"op\": \"remove\", \"key\": \"b\"}\n").unwrap();
+    assert_eq!(0, apply(&["--script".to_string(), script.to_str().unwrap().to_string(), file.clone()]));
+    assert_eq!("a = { x = \"y\" }\n", fs::read_to_string(&path).unwrap());
+    // The first edit would succeed, but the second fails, so none of them are made
+    fs::write(&script, "{\"op\": \"set\", \"key\": \"a\", \"value\": 2}\n\
+      {\"op\": \"remove\", \"key\": \"b\"}\n").unwrap();
+    assert_eq!(1, apply(&[format!("--script={}", script.display()), file]));
+    assert_eq!("a = { x = \"y\" }\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn test_csv_to_triples() {
+    let triples = |csv: &str| csv_to_triples(csv).unwrap();
+    assert_eq!(vec!["ports", "[80,443]", "auto", "t", "{a=1,b=[2,3]}", "auto"],
+      triples("ports,[80,443],auto,t,{a=1,b=[2,3]},auto"));
+    assert_eq!(vec!["s", "[\"a,]\"]", "auto", "n", "1", "int"], triples("s,[\"a,]\"],auto,n,1,int"));
+    // Only auto values are joined, so a string with an unbalanced bracket stays one field
+    assert_eq!(vec!["msg", "hello [world", "bs"], triples("msg,hello [world,bs"));
+    assert_eq!(vec!["msg", "{x", "bs", "n", "2", "int"], triples("msg,{x,bs,n,2,int"));
+    assert_eq!(vec!["a", "[1", "auto", "b"], triples("a,[1,auto,b"));
+    assert_eq!(vec!["a", "1"], triples("a,1"));
+  }
+}
\ No newline at end of file
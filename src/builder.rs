@@ -0,0 +1,390 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use crate::TOMLParser;
+use crate::types::{Value, TOMLError};
+use crate::internals::parser::Parser;
+use crate::internals::ast::structs::{Expression, KeyVal, Comment, Table, TableType, WSKeySep, WSSep};
+use crate::internals::build::{split_key, format_key};
+use crate::internals::walk::{TableTracker, join_key};
+
+/// Builds a TOML document from tables, arrays of tables, key/value pairs and comments without parsing a string.
+///
+/// Each method appends a new line to the end of the document and returns the builder so calls can be chained. Key/value
+/// pairs belong to the most recently added table, or to the root table if no table has been added yet. Keys that aren't
+/// valid bare keys are wrapped in double quotes. Errors, such as invalid keys or values, or a key or table that's defined
+/// twice, are reported when `build` is called.
+///
+/// The default formatting is one space on each side of the equals sign in a key/value pair, a blank line before every
+/// table header, except at the very start of the document, and a newline at the end of the document.
+///
+/// # Examples
+///
+/// ```
+/// use tomllib::builder::TOMLBuilder;
+/// use tomllib::types::Value;
+///
+/// let parser = TOMLBuilder::new()
+///   .comment("Generated configuration")
+///   .keyval("title", Value::basic_string("TOML Example").unwrap())
+///   .table("owner")
+///   .keyval("name", Value::basic_string("Tom").unwrap())
+///   .array_of_tables("products")
+///   .keyval("sku", Value::int(738594937))
+///   .build().unwrap();
+/// assert_eq!(r#"# Generated configuration
+/// title = "TOML Example"
+///
+/// [owner]
+/// name = "Tom"
+///
+/// [[products]]
+/// sku = 738594937
+/// "#, format!("{}", parser));
+/// assert_eq!(Value::int(738594937), parser.get_value("products[0].sku").unwrap());
+/// ```
+pub struct TOMLBuilder<'a> {
+  parser: Parser<'a>,
+  tracker: TableTracker,
+  current: String,
+  defined: HashSet<String>,
+  arrays: HashSet<String>,
+  error: Option<TOMLError>,
+}
+
+impl<'a> TOMLBuilder<'a> {
+  /// Constructs a new `TOMLBuilder` for an empty document.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  ///
+  /// let builder = TOMLBuilder::new();
+  /// ```
+  pub fn new() -> TOMLBuilder<'a> {
+    TOMLBuilder{parser: Parser::new(), tracker: TableTracker::new(), current: String::new(), defined: HashSet::new(),
+      arrays: HashSet::new(), error: None}
+  }
+
+  /// Appends a comment line containing `text`. A `text` with several lines adds a comment line for each of them.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  ///
+  /// let parser = TOMLBuilder::new().comment("First line\nSecond line").build().unwrap();
+  /// assert_eq!("# First line\n# Second line\n", format!("{}", parser));
+  /// ```
+  #[must_use]
+  pub fn comment<S>(mut self, text: S) -> TOMLBuilder<'a> where S: Into<String> {
+    if self.error.is_none() {
+      for line in text.into().lines() {
+        let comment = if line.is_empty() { String::new() } else { format!(" {line}") };
+        debug!("Building comment: #{comment}");
+        self.parser.append_expression(Expression::new(WSSep::new_str("", ""), None, None,
+          Some(Comment::new_string(comment))));
+      }
+    }
+    self
+  }
+
+  /// Appends a blank line.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  /// use tomllib::types::Value;
+  ///
+  /// let parser = TOMLBuilder::new()
+  ///   .keyval("a", Value::int(1))
+  ///   .blank_line()
+  ///   .keyval("b", Value::int(2))
+  ///   .build().unwrap();
+  /// assert_eq!("a = 1\n\nb = 2\n", format!("{}", parser));
+  /// ```
+  #[must_use]
+  pub fn blank_line(mut self) -> TOMLBuilder<'a> {
+    if self.error.is_none() {
+      self.parser.append_expression(Expression::new(WSSep::new_str("", ""), None, None, None));
+    }
+    self
+  }
+
+  /// Appends the key/value pair `key` = `val` to the most recently added table.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  /// use tomllib::types::Value;
+  ///
+  /// let parser = TOMLBuilder::new()
+  ///   .keyval("ports", Value::Array(vec![Value::int(8001), Value::int(8002)].into()))
+  ///   .keyval("server name", Value::basic_string("alpha").unwrap())
+  ///   .build().unwrap();
+  /// assert_eq!("ports = [8001, 8002]\n\"server name\" = \"alpha\"\n", format!("{}", parser));
+  /// ```
+  #[must_use]
+  pub fn keyval<S>(mut self, key: S, val: Value<'a>) -> TOMLBuilder<'a> where S: Into<String> {
+    if self.error.is_some() {
+      return self;
+    }
+    let s_key: String = key.into();
+    let Some(key) = format_key(&s_key) else {
+      return self.fail(format!("Invalid key: {s_key}"));
+    };
+    let full_key = join_key(&self.current, &key);
+    if !self.defined.insert(full_key.clone()) {
+      return self.fail(format!("Key defined more than once: {full_key}"));
+    }
+    let Some(tval) = Parser::convert_vector(&val) else {
+      return self.fail(format!("Invalid value for key {full_key}: {val}"));
+    };
+    debug!("Building keyval: {full_key} = {val}");
+    let keyval = KeyVal::new_string(key, WSSep::new_str(" ", " "), Rc::new(RefCell::new(tval)));
+    self.parser.append_expression(Expression::new(WSSep::new_str("", ""), Some(keyval), None, None));
+    self
+  }
+
+  /// Appends the standard table header `[key]`. `key` is a dotted key, e.g. `servers.alpha`, and each of its segments
+  /// is quoted if necessary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  /// use tomllib::types::Value;
+  ///
+  /// let parser = TOMLBuilder::new()
+  ///   .table("servers.alpha")
+  ///   .keyval("ip", Value::basic_string("10.0.0.1").unwrap())
+  ///   .build().unwrap();
+  /// assert_eq!("[servers.alpha]\nip = \"10.0.0.1\"\n", format!("{}", parser));
+  /// assert_eq!(Value::basic_string("10.0.0.1").unwrap(), parser.get_value("servers.alpha.ip").unwrap());
+  /// ```
+  #[must_use]
+  pub fn table<S>(self, key: S) -> TOMLBuilder<'a> where S: Into<String> {
+    self.table_header(key.into(), false)
+  }
+
+  /// Appends the array of tables header `[[key]]`, which adds a new table to the array `key`. `key` is a dotted key,
+  /// e.g. `fruit.variety`, and each of its segments is quoted if necessary.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  /// use tomllib::types::{Value, Children};
+  /// use std::cell::Cell;
+  ///
+  /// let parser = TOMLBuilder::new()
+  ///   .array_of_tables("fruit")
+  ///   .keyval("name", Value::basic_string("apple").unwrap())
+  ///   .array_of_tables("fruit")
+  ///   .keyval("name", Value::basic_string("banana").unwrap())
+  ///   .build().unwrap();
+  /// assert_eq!(&Children::Count(Cell::new(2)), parser.get_children("fruit").unwrap());
+  /// assert_eq!(Value::basic_string("banana").unwrap(), parser.get_value("fruit[1].name").unwrap());
+  /// ```
+  #[must_use]
+  pub fn array_of_tables<S>(self, key: S) -> TOMLBuilder<'a> where S: Into<String> {
+    self.table_header(key.into(), true)
+  }
+
+  /// Finishes the document and returns it as a `TOMLParser`, ready to be queried, changed or displayed, or returns
+  /// the first error encountered while building it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::builder::TOMLBuilder;
+  /// use tomllib::types::Value;
+  ///
+  /// let result = TOMLBuilder::new()
+  ///   .keyval("key", Value::int(1))
+  ///   .keyval("key", Value::int(2))
+  ///   .build();
+  /// assert!(result.is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if a key or value is invalid, or if a key or table is defined more than once.
+  pub fn build(mut self) -> Result<TOMLParser<'a>, TOMLError> {
+    if let Some(err) = self.error {
+      return Result::Err(err);
+    }
+    if !self.parser.root.borrow().exprs.is_empty() {
+      self.parser.append_expression(Expression::new(WSSep::new_str("", ""), None, None, None));
+    }
+    self.parser.rebuild_map();
+    Result::Ok(TOMLParser{parser: self.parser})
+  }
+
+  fn table_header(mut self, key: String, is_array: bool) -> TOMLBuilder<'a> {
+    if self.error.is_some() {
+      return self;
+    }
+    let mut keys = vec![];
+    for segment in split_key(&key) {
+      match format_key(&segment) {
+        Some(k) => keys.push(WSKeySep::new_string(WSSep::new_str("", ""), k)),
+        None => return self.fail(format!("Invalid table key: {key}")),
+      }
+    }
+    let table = Table{keys};
+    let table_type = if is_array { TableType::Array(table) } else { TableType::Standard(table) };
+    let resolved = self.tracker.resolve(&table_type);
+    match resolved.array_key {
+      Some(array_key) => {
+        if self.defined.contains(&array_key) && !self.arrays.contains(&array_key) {
+          return self.fail(format!("Array of tables has the same key as a table or value: {array_key}"));
+        }
+        self.defined.insert(array_key.clone());
+        self.arrays.insert(array_key);
+      },
+      None => {
+        if !self.defined.insert(resolved.key.clone()) {
+          return self.fail(format!("Table defined more than once: {}", resolved.key));
+        }
+      },
+    }
+    debug!("Building table: {table_type}");
+    if self.needs_blank_line() {
+      self.parser.append_expression(Expression::new(WSSep::new_str("", ""), None, None, None));
+    }
+    self.parser.append_expression(Expression::new(WSSep::new_str("", ""), None, Some(Rc::new(table_type)), None));
+    self.current = resolved.key;
+    self
+  }
+
+  fn needs_blank_line(&self) -> bool {
+    match self.parser.root.borrow().exprs.last() {
+      Some(nl_expr) => {
+        let expr = &nl_expr.expr;
+        expr.keyval.is_some() || expr.table.is_some() || expr.comment.is_some()
+      },
+      None => false,
+    }
+  }
+
+  fn fail(mut self, msg: String) -> TOMLBuilder<'a> {
+    self.error = Some(TOMLError::new(msg));
+    self
+  }
+}
+
+impl Default for TOMLBuilder<'_> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::cell::{Cell, RefCell};
+  use crate::TOMLParser;
+  use crate::builder::TOMLBuilder;
+  use crate::types::{Value, Children, StrType};
+
+  fn build_example<'a>() -> TOMLParser<'a> {
+    TOMLBuilder::new()
+      .comment("This is a TOML document.")
+      .keyval("title", Value::basic_string("TOML Example").unwrap())
+      .table("owner")
+      .keyval("name", Value::basic_string("Tom Preston-Werner").unwrap())
+      .keyval("dob", Value::datetime_parse("1979-05-27T07:32:00-08:00").unwrap())
+      .table("database")
+      .keyval("ports", Value::Array(vec![Value::int(8001), Value::int(8001), Value::int(8002)].into()))
+      .keyval("connection limits", Value::InlineTable(vec![("max".into(), Value::int(5000)),
+        ("min".into(), Value::int(1))].into()))
+      .keyval("enabled", Value::bool(true))
+      .table("servers.alpha")
+      .keyval("ip", Value::basic_string("10.0.0.1").unwrap())
+      .array_of_tables("products")
+      .keyval("name", Value::basic_string("Hammer").unwrap())
+      .array_of_tables("products")
+      .array_of_tables("products.\"parts list\"")
+      .keyval("name", Value::literal_string("nail").unwrap())
+      .build().unwrap()
+  }
+
+  #[test]
+  fn test_build_display() {
+    assert_eq!(r#"# This is a TOML document.
+title = "TOML Example"
+
+[owner]
+name = "Tom Preston-Werner"
+dob = 1979-05-27T07:32:00-08:00
+
+[database]
+ports = [8001, 8001, 8002]
+"connection limits" = { max = 5000, min = 1 }
+enabled = true
+
+[servers.alpha]
+ip = "10.0.0.1"
+
+[[products]]
+name = "Hammer"
+
+[[products]]
+
+[[products."parts list"]]
+name = 'nail'
+"#, format!("{}", build_example()));
+  }
+
+  #[test]
+  fn test_build_round_trip() {
+    let built = build_example();
+    let doc = format!("{built}");
+    let (parsed, _) = TOMLParser::new().parse(&doc);
+    let mut built_keys: Vec<&String> = built.parser.map.keys().collect();
+    let mut parsed_keys: Vec<&String> = parsed.parser.map.keys().collect();
+    built_keys.sort();
+    parsed_keys.sort();
+    assert_eq!(parsed_keys, built_keys);
+    for (key, hv) in &parsed.parser.map {
+      let parsed_value = hv.value.as_ref().map(|v| format!("{}", *v.borrow()));
+      let built_value = built.parser.map[key].value.as_ref().map(|v| format!("{}", *v.borrow()));
+      assert_eq!(parsed_value, built_value, "value of key {key}");
+      assert_eq!(hv.subkeys, built.parser.map[key].subkeys, "children of key {key}");
+    }
+    assert_eq!(Children::Count(Cell::new(2)), *built.get_children("products").unwrap());
+    assert_eq!(Children::Keys(RefCell::new(vec!["alpha".to_string()])), *built.get_children("servers").unwrap());
+    assert_eq!(Value::literal_string("nail").unwrap(), built.get_value("products[1].\"parts list\"[0].name").unwrap());
+  }
+
+  #[test]
+  fn test_build_empty() {
+    let parser = TOMLBuilder::new().build().unwrap();
+    assert_eq!("", format!("{parser}"));
+    assert_eq!(Children::Keys(RefCell::new(vec![])), *parser.get_children("$Root$").unwrap());
+  }
+
+  #[test]
+  fn test_build_set_value() {
+    let mut parser = TOMLBuilder::new()
+      .table("table")
+      .keyval("key", Value::int(1))
+      .build().unwrap();
+    assert!(parser.set_value("table.key", Value::Array(vec![Value::int(1), Value::int(2)].into())));
+    assert_eq!("[table]\nkey = [1, 2]\n", format!("{parser}"));
+  }
+
+  #[test]
+  fn test_build_errors() {
+    assert!(TOMLBuilder::new().keyval("", Value::int(1)).build().is_err());
+    assert!(TOMLBuilder::new().keyval("key", Value::String("a\nb".into(), StrType::Basic)).build().is_err());
+    assert!(TOMLBuilder::new().table("a").table("a").build().is_err());
+    assert!(TOMLBuilder::new().keyval("a", Value::int(1)).table("a").build().is_err());
+    assert!(TOMLBuilder::new().table("a").array_of_tables("a").build().is_err());
+    assert!(TOMLBuilder::new().table("a.b").table("a").build().is_ok());
+    assert!(TOMLBuilder::new().array_of_tables("a").array_of_tables("a").build().is_ok());
+  }
+}
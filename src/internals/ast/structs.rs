@@ -25,7 +25,7 @@ pub enum ErrorCode {
 
 #[derive(Debug, Eq)]
 pub struct Toml<'a> {
-  pub exprs: Vec<NLExpression<'a>>,
+  pub(crate) exprs: Vec<NLExpression<'a>>,
 }
 
 impl<'a> PartialEq for Toml<'a> {
@@ -36,24 +36,24 @@ impl<'a> PartialEq for Toml<'a> {
 
 impl<'a> Display for Toml<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-      for i in 0..self.exprs.len()-1 {
-        write!(f, "{}", self.exprs[i])?;
+      for expr in &self.exprs {
+        write!(f, "{expr}")?;
       }
-    write!(f, "{}", self.exprs[self.exprs.len()-1])
+      Ok(())
    }
 }
 
 #[allow(dead_code)]
 impl<'a> Toml<'a> {
-  pub fn new(exprs: Vec<NLExpression<'a>>) -> Toml<'a> {
+  pub(crate) fn new(exprs: Vec<NLExpression<'a>>) -> Toml<'a> {
     Toml{exprs}
   }
 }
 
 #[derive(Debug, Eq)]
 pub struct NLExpression<'a> {
-  pub nl: Cow<'a, str>,
-  pub expr: Expression<'a>,
+  pub(crate) nl: Cow<'a, str>,
+  pub(crate) expr: Expression<'a>,
 }
 
 impl<'a> PartialEq for NLExpression<'a> {
@@ -71,10 +71,10 @@ impl<'a> Display for NLExpression<'a> {
 
 #[allow(dead_code)]
 impl<'a> NLExpression<'a> {
-  pub fn new_str(nl: &'a str, expr: Expression<'a>) -> NLExpression<'a> {
+  pub(crate) fn new_str(nl: &'a str, expr: Expression<'a>) -> NLExpression<'a> {
     NLExpression{nl: nl.into(), expr}
   }
-  pub fn new_string(nl: String, expr: Expression<'a>) -> NLExpression<'a> {
+  pub(crate) fn new_string(nl: String, expr: Expression<'a>) -> NLExpression<'a> {
     NLExpression{nl: nl.into(), expr}
   }
 }
@@ -85,10 +85,12 @@ impl<'a> NLExpression<'a> {
 // <ws.ws1><table><ws.ws2><comment?>
 #[derive(Debug, Eq)]
 pub struct Expression<'a> {
-  pub ws: WSSep<'a>,
-  pub keyval: Option<KeyVal<'a>>,
-  pub table: Option<Rc<TableType<'a>>>,
-  pub comment: Option<Comment<'a>>,
+  pub(crate) ws: WSSep<'a>,
+  pub(crate) keyval: Option<KeyVal<'a>>,
+  pub(crate) table: Option<Rc<TableType<'a>>>,
+  pub(crate) comment: Option<Comment<'a>>,
+  /// Text at the end of the line that couldn't be parsed, kept so that the document still displays as written.
+  pub(crate) invalid: Option<Cow<'a, str>>,
 }
 
 impl<'a> PartialEq for Expression<'a> {
@@ -96,7 +98,8 @@ impl<'a> PartialEq for Expression<'a> {
     self.ws == other.ws &&
     comp_opt(&self.keyval, &other.keyval) &&
     comp_opt(&self.table, &other.table) &&
-    comp_opt(&self.comment, &other.comment)
+    comp_opt(&self.comment, &other.comment) &&
+    self.invalid == other.invalid
   }
 }
 
@@ -111,14 +114,18 @@ impl<'a> Display for Expression<'a> {
         (ws, &None, &Some(ref t), &None) => write!(f, "{}{}{}", ws.ws1, t, ws.ws2),
         _ => panic!("Invalid expression: ws1: \"{}\", ws2: \"{}\", keyval: {:?}, table: {:?}, comment: {:?}",
           self.ws.ws1, self.ws.ws2, self.keyval, self.table, self.comment),
+      }?;
+      match self.invalid {
+        Some(ref invalid) => write!(f, "{invalid}"),
+        None => Ok(()),
       }
     }
 }
 
 impl<'a> Expression<'a> {
-  pub fn new(ws: WSSep<'a>, keyval: Option<KeyVal<'a>>, table: Option<Rc<TableType<'a>>>,
+  pub(crate) fn new(ws: WSSep<'a>, keyval: Option<KeyVal<'a>>, table: Option<Rc<TableType<'a>>>,
     comment: Option<Comment<'a>>) -> Expression<'a> {
-    Expression{ws, keyval, table, comment}
+    Expression{ws, keyval, table, comment, invalid: None}
   }
 }
 
@@ -127,14 +134,25 @@ impl<'a> Expression<'a> {
 pub enum TOMLValue<'a> {
   Integer(Cow<'a, str>),
   Float(Cow<'a, str>),
-  Boolean(bool),
+  /// A boolean, with the text it was written as if that wasn't lowercase, like `True`.
+  Boolean(bool, Option<Cow<'a, str>>),
   DateTime(DateTime<'a>),
   Array(Rc<RefCell<Array<'a>>>),
   String(Cow<'a, str>, StrType),
   InlineTable(Rc<RefCell<InlineTable<'a>>>),
+  // Stands in for a table in the parser's map of keys and values, and never appears in the syntax tree
+  #[doc(hidden)]
   Table,
 }
 
+impl<'a> TOMLValue<'a> {
+  // Reads a boolean written in any casing, keeping the text unless it's lowercase
+  pub(crate) fn boolean(text: &'a str) -> TOMLValue<'a> {
+    let original = if text == "true" || text == "false" { None } else { Some(text.into()) };
+    TOMLValue::Boolean(text.eq_ignore_ascii_case("true"), original)
+  }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum ArrayType {
   Integer,
@@ -229,7 +247,7 @@ impl<'a> PartialEq for TOMLValue<'a> {
     match (self, other) {
       (&TOMLValue::Integer(ref i), &TOMLValue::Integer(ref j)) if i == j => true,
       (&TOMLValue::Float(ref i), &TOMLValue::Float(ref j)) if i == j => true,
-      (&TOMLValue::Boolean(ref i), &TOMLValue::Boolean(ref j)) if i == j => true,
+      (&TOMLValue::Boolean(i, _), &TOMLValue::Boolean(j, _)) if i == j => true,
       (&TOMLValue::DateTime(ref i), &TOMLValue::DateTime(ref j)) if i == j => true,
       (&TOMLValue::Array(ref i), &TOMLValue::Array(ref j)) if i == j => true,
       (&TOMLValue::String(ref i, _), &TOMLValue::String(ref j, _)) if i == j => true,
@@ -244,7 +262,8 @@ impl<'a> Display for TOMLValue<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match *self {
       TOMLValue::Integer(ref i) | TOMLValue::Float(ref i)  => write!(f, "{}", i),
-      TOMLValue::Boolean(ref i) => write!(f, "{}", i),
+      TOMLValue::Boolean(_, Some(ref text)) => write!(f, "{text}"),
+      TOMLValue::Boolean(b, None) => write!(f, "{b}"),
       TOMLValue::DateTime(ref i) => write!(f, "{}", i),
       TOMLValue::Array(ref i) => write!(f, "{}", *i.borrow()),
       TOMLValue::InlineTable(ref i) => write!(f, "{}", *i.borrow()),
@@ -261,7 +280,7 @@ impl<'a> Display for TOMLValue<'a> {
   }
 }
 
-#[derive(Debug, Eq)]
+#[derive(Debug, Eq, Clone)]
 pub enum TableType<'a>{
   Standard(Table<'a>),
   Array(Table<'a>),
@@ -289,7 +308,7 @@ impl<'a> Display for TableType<'a> {
 // #<text>
 #[derive(Debug, Eq)]
 pub struct Comment<'a> {
-  pub text: Cow<'a, str>,
+  pub(crate) text: Cow<'a, str>,
 }
 
 impl<'a> PartialEq for Comment<'a> {
@@ -306,18 +325,18 @@ impl<'a> Display for Comment<'a> {
 
 #[allow(dead_code)]
 impl<'a> Comment<'a> {
-  pub fn new_str(text: &'a str) -> Comment<'a> {
+  pub(crate) fn new_str(text: &'a str) -> Comment<'a> {
     Comment{text: text.into()}
   }
-  pub fn new_string(text: String) -> Comment<'a> {
+  pub(crate) fn new_string(text: String) -> Comment<'a> {
     Comment{text:text.into()}
   }
 }
 
-#[derive(Debug, Eq)]
+#[derive(Debug, Eq, Clone)]
 pub struct WSSep<'a> {
-  pub ws1: Cow<'a, str>,
-  pub ws2: Cow<'a, str>,
+  pub(crate) ws1: Cow<'a, str>,
+  pub(crate) ws2: Cow<'a, str>,
 }
 
 impl<'a> PartialEq for WSSep<'a> {
@@ -329,10 +348,10 @@ impl<'a> PartialEq for WSSep<'a> {
 
 #[allow(dead_code)]
 impl<'a> WSSep<'a> {
-  pub fn new_str(ws1: &'a str, ws2: &'a str) -> WSSep<'a> {
+  pub(crate) fn new_str(ws1: &'a str, ws2: &'a str) -> WSSep<'a> {
     WSSep{ws1: ws1.into(), ws2: ws2.into()}
   }
-  pub fn new_string(ws1: String, ws2: String) -> WSSep<'a> {
+  pub(crate) fn new_string(ws1: String, ws2: String) -> WSSep<'a> {
     WSSep{ws1: ws1.into(), ws2: ws2.into()}
   }
 }
@@ -340,9 +359,9 @@ impl<'a> WSSep<'a> {
 // <key><keyval_sep.ws1>=<keyval_sep.ws2><val>
 #[derive(Debug, Eq)]
 pub struct KeyVal<'a> {
-  pub key: Cow<'a, str>,
-  pub keyval_sep: WSSep<'a>,
-  pub val: Rc<RefCell<TOMLValue<'a>>>,
+  pub(crate) key: Cow<'a, str>,
+  pub(crate) keyval_sep: WSSep<'a>,
+  pub(crate) val: Rc<RefCell<TOMLValue<'a>>>,
 }
 
 impl<'a> PartialEq for KeyVal<'a> {
@@ -361,19 +380,19 @@ impl<'a> Display for KeyVal<'a> {
 
 #[allow(dead_code)]
 impl<'a> KeyVal<'a> {
-    pub fn new_str(key: &'a str, keyval_sep: WSSep<'a>, val: Rc<RefCell<TOMLValue<'a>>>) -> KeyVal<'a> {
+    pub(crate) fn new_str(key: &'a str, keyval_sep: WSSep<'a>, val: Rc<RefCell<TOMLValue<'a>>>) -> KeyVal<'a> {
       KeyVal{key: key.into(), keyval_sep, val}
     }
-    pub fn new_string(key: String, keyval_sep: WSSep<'a>, val: Rc<RefCell<TOMLValue<'a>>>) -> KeyVal<'a> {
+    pub(crate) fn new_string(key: String, keyval_sep: WSSep<'a>, val: Rc<RefCell<TOMLValue<'a>>>) -> KeyVal<'a> {
       KeyVal{key: key.into(), keyval_sep, val}
     }
 }
 
 // <ws.ws1>.<ws.ws2><key>
-#[derive(Debug, Eq)]
+#[derive(Debug, Eq, Clone)]
 pub struct WSKeySep<'a> {
-  pub ws: WSSep<'a>,
-  pub key: Cow<'a, str>,
+  pub(crate) ws: WSSep<'a>,
+  pub(crate) key: Cow<'a, str>,
 }
 
 impl<'a> PartialEq for WSKeySep<'a> {
@@ -391,10 +410,10 @@ impl<'a> Display for WSKeySep<'a> {
 
 #[allow(dead_code)]
 impl<'a> WSKeySep<'a> {
-    pub fn new_str(ws: WSSep<'a>, key: &'a str) -> WSKeySep<'a> {
+    pub(crate) fn new_str(ws: WSSep<'a>, key: &'a str) -> WSKeySep<'a> {
       WSKeySep{ws, key: key.into()}
     }
-    pub fn new_string(ws: WSSep<'a>, key: String) -> WSKeySep<'a> {
+    pub(crate) fn new_string(ws: WSSep<'a>, key: String) -> WSKeySep<'a> {
       WSKeySep{ws, key: key.into()}
     }
 }
@@ -450,9 +469,9 @@ pub fn format_tt_keys(tabletype: &TableType) -> String {
 
 // Standard: [<ws.ws1><key><subkeys*><ws.ws2>]
 // Array: [[<ws.ws1><key><subkeys*><ws.ws2>]]
-#[derive(Debug, Eq)]
+#[derive(Debug, Eq, Clone)]
 pub struct Table<'a> {
-  pub keys: Vec<WSKeySep<'a>>,
+  pub(crate) keys: Vec<WSKeySep<'a>>,
 }
 
 impl<'a> PartialEq for Table<'a> {
@@ -477,18 +496,18 @@ impl<'a> Display for Table<'a> {
 
 #[allow(dead_code)]
 impl<'a> Table<'a> {
-  pub fn new_str(ws: WSSep<'a>, key: &'a str, mut subkeys: Vec<WSKeySep<'a>>) -> Table<'a> {
+  pub(crate) fn new_str(ws: WSSep<'a>, key: &'a str, mut subkeys: Vec<WSKeySep<'a>>) -> Table<'a> {
     subkeys.insert(0, WSKeySep::new_str(ws, key));
     Table{keys: subkeys}
   }
-  pub fn new_string(ws: WSSep<'a>, key: String, mut subkeys: Vec<WSKeySep<'a>>) -> Table<'a> {
+  pub(crate) fn new_string(ws: WSSep<'a>, key: String, mut subkeys: Vec<WSKeySep<'a>>) -> Table<'a> {
     subkeys.insert(0, WSKeySep::new_string(ws, key));
     Table{keys: subkeys}
   }
 }
 
 impl<'a> TableType<'a> {
-  pub fn is_subtable_of(&self, prev: &TableType<'a>) -> bool {
+  pub(crate) fn is_subtable_of(&self, prev: &TableType<'a>) -> bool {
     match *self {
       TableType::Standard(ref t) | TableType::Array(ref t) => {
         match *prev {
@@ -509,9 +528,9 @@ impl<'a> TableType<'a> {
 // <comment><newlines+>
 #[derive(Debug, Eq)]
 pub struct CommentNewLines<'a> {
-  pub pre_ws_nl: Cow<'a, str>,
-  pub comment: Comment<'a>,
-  pub newlines: Cow<'a, str>,
+  pub(crate) pre_ws_nl: Cow<'a, str>,
+  pub(crate) comment: Comment<'a>,
+  pub(crate) newlines: Cow<'a, str>,
 }
 
 impl<'a> PartialEq for CommentNewLines<'a> {
@@ -530,12 +549,12 @@ impl<'a> Display for CommentNewLines<'a> {
 
 #[allow(dead_code)]
 impl<'a> CommentNewLines<'a> {
-    pub fn new_str(pre_ws_nl: &'a str, comment: Comment<'a>, newlines: &'a str)
+    pub(crate) fn new_str(pre_ws_nl: &'a str, comment: Comment<'a>, newlines: &'a str)
       -> CommentNewLines<'a> {
       CommentNewLines{pre_ws_nl: pre_ws_nl.into(), comment,
         newlines: newlines.into()}
     }
-    pub fn new_string(pre_ws_nl: String, comment: Comment<'a>, newlines: String)
+    pub(crate) fn new_string(pre_ws_nl: String, comment: Comment<'a>, newlines: String)
       -> CommentNewLines<'a> {
       CommentNewLines{pre_ws_nl: pre_ws_nl.into(), comment,
         newlines: newlines.into()}
@@ -600,9 +619,9 @@ impl<'a> TimeOffsetAmount<'a> {
 // <val><<array_sep.ws1>,<array_sep.ws2>?><comment_nl?><array_vals?>
 #[derive(Debug, Eq)]
 pub struct ArrayValue<'a> {
-  pub val: Rc<RefCell<TOMLValue<'a>>>,
-  pub array_sep: Option<WSSep<'a>>,
-  pub comment_nls: Vec<CommentOrNewLines<'a>>,
+  pub(crate) val: Rc<RefCell<TOMLValue<'a>>>,
+  pub(crate) array_sep: Option<WSSep<'a>>,
+  pub(crate) comment_nls: Vec<CommentOrNewLines<'a>>,
 }
 
 impl<'a> PartialEq for ArrayValue<'a> {
@@ -634,14 +653,14 @@ impl<'a> Display for ArrayValue<'a> {
 }
 
 impl<'a> ArrayValue<'a> {
-  pub fn new(val: Rc<RefCell<TOMLValue<'a>>>, array_sep: Option<WSSep<'a>>,
+  pub(crate) fn new(val: Rc<RefCell<TOMLValue<'a>>>, array_sep: Option<WSSep<'a>>,
     comment_nls: Vec<CommentOrNewLines<'a>>) -> ArrayValue<'a> {
     ArrayValue{val, array_sep, comment_nls}
   }
-  pub fn default(val: Rc<RefCell<TOMLValue<'a>>>) -> ArrayValue<'a> {
+  pub(crate) fn default(val: Rc<RefCell<TOMLValue<'a>>>) -> ArrayValue<'a> {
     ArrayValue{val, array_sep: Some(WSSep::new_str("", " ")), comment_nls: vec![]}
   }
-  pub fn last(val: Rc<RefCell<TOMLValue<'a>>>) -> ArrayValue<'a> {
+  pub(crate) fn last(val: Rc<RefCell<TOMLValue<'a>>>) -> ArrayValue<'a> {
     ArrayValue{val, array_sep: None, comment_nls: vec![]}
   }
 }
@@ -649,9 +668,9 @@ impl<'a> ArrayValue<'a> {
 // [<ws.ws1><values?><ws.ws2>]
 #[derive(Debug, Eq)]
 pub struct Array<'a> {
-  pub values: Vec<ArrayValue<'a>>,
-  pub comment_nls1: Vec<CommentOrNewLines<'a>>,
-  pub comment_nls2: Vec<CommentOrNewLines<'a>>,
+  pub(crate) values: Vec<ArrayValue<'a>>,
+  pub(crate) comment_nls1: Vec<CommentOrNewLines<'a>>,
+  pub(crate) comment_nls2: Vec<CommentOrNewLines<'a>>,
 }
 
 impl<'a> PartialEq for Array<'a> {
@@ -679,7 +698,7 @@ impl<'a> Display for Array<'a> {
 }
 
 impl<'a> Array<'a> {
-  pub fn new(values: Vec<ArrayValue<'a>>, comment_nls1: Vec<CommentOrNewLines<'a>>,
+  pub(crate) fn new(values: Vec<ArrayValue<'a>>, comment_nls1: Vec<CommentOrNewLines<'a>>,
     comment_nls2: Vec<CommentOrNewLines<'a>>,) -> Array<'a> {
     Array{values, comment_nls1, comment_nls2}
   }
@@ -688,9 +707,9 @@ impl<'a> Array<'a> {
 // <key><keyval_sep.ws1>=<keyval_sep.ws2><val><<table_sep.ws1>,<table_sep.ws2>?><keyvals?>
 #[derive(Debug, Eq)]
 pub struct TableKeyVal<'a> {
-  pub keyval: KeyVal<'a>,
-  pub kv_sep: Option<WSSep<'a>>,
-  pub comment_nls: Vec<CommentOrNewLines<'a>>,
+  pub(crate) keyval: KeyVal<'a>,
+  pub(crate) kv_sep: Option<WSSep<'a>>,
+  pub(crate) comment_nls: Vec<CommentOrNewLines<'a>>,
 }
 
 impl<'a> PartialEq for TableKeyVal<'a> {
@@ -721,14 +740,14 @@ impl<'a> Display for TableKeyVal<'a> {
 }
 
 impl<'a> TableKeyVal<'a> {
-    pub fn new(keyval: KeyVal<'a>, kv_sep: Option<WSSep<'a>>, comment_nls: Vec<CommentOrNewLines<'a>>) -> TableKeyVal<'a> {
+    pub(crate) fn new(keyval: KeyVal<'a>, kv_sep: Option<WSSep<'a>>, comment_nls: Vec<CommentOrNewLines<'a>>) -> TableKeyVal<'a> {
       TableKeyVal{keyval, kv_sep, comment_nls}
     }
-    pub fn default<S>(key: S, val: Rc<RefCell<TOMLValue<'a>>>) -> TableKeyVal<'a> where S: Into<String> {
+    pub(crate) fn default<S>(key: S, val: Rc<RefCell<TOMLValue<'a>>>) -> TableKeyVal<'a> where S: Into<String> {
       let keyval = KeyVal::new_string(key.into(), WSSep::new_str(" ", " "), val);
       TableKeyVal{keyval, kv_sep: Some(WSSep::new_str("", " ")), comment_nls: vec![]}
     }
-    pub fn last<S>(key: S, val: Rc<RefCell<TOMLValue<'a>>>) -> TableKeyVal<'a> where S: Into<String> {
+    pub(crate) fn last<S>(key: S, val: Rc<RefCell<TOMLValue<'a>>>) -> TableKeyVal<'a> where S: Into<String> {
       let keyval = KeyVal::new_string(key.into(), WSSep::new_str(" ", " "), val);
       TableKeyVal{keyval, kv_sep: None, comment_nls: vec![]}
     }
@@ -737,8 +756,8 @@ impl<'a> TableKeyVal<'a> {
 // {<ws.ws1><keyvals><ws.ws2>}
 #[derive(Debug, Eq)]
 pub struct InlineTable<'a> {
-  pub keyvals: Vec<TableKeyVal<'a>>,
-  pub ws: WSSep<'a>,
+  pub(crate) keyvals: Vec<TableKeyVal<'a>>,
+  pub(crate) ws: WSSep<'a>,
 }
 
 impl<'a> PartialEq for InlineTable<'a> {
@@ -751,7 +770,7 @@ impl<'a> PartialEq for InlineTable<'a> {
 impl<'a> Display for InlineTable<'a> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{{{}", self.ws.ws1)?;
-    for i in 0..self.keyvals.len() - 1 {
+    for i in 0..self.keyvals.len().saturating_sub(1) {
       write!(f, "{}", self.keyvals[i])?;
     }
     if !self.keyvals.is_empty() {
@@ -762,7 +781,7 @@ impl<'a> Display for InlineTable<'a> {
 }
 
 impl<'a> InlineTable<'a> {
-  pub fn new(keyvals: Vec<TableKeyVal<'a>>, ws: WSSep<'a>) -> InlineTable<'a> {
+  pub(crate) fn new(keyvals: Vec<TableKeyVal<'a>>, ws: WSSep<'a>) -> InlineTable<'a> {
     InlineTable{keyvals, ws}
   }
 }
@@ -789,7 +808,8 @@ mod test {
         table: None,
         comment: Some(Comment{
           text: "hello, this is a comment".into()
-        })
+        }),
+        invalid: None,
       }
     }]};
   }
@@ -0,0 +1,220 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::internals::ast::structs::{HashValue, TOMLValue, Expression, NLExpression};
+use crate::internals::parser::Parser;
+use crate::internals::walk::{TableTracker, join_key, map_key};
+use crate::types::Children;
+use crate::internals::primitives::is_keychar;
+use nom::IResult;
+
+impl<'a> Parser<'a> {
+  /// Appends `expr` to the end of the document on a new line.
+  pub fn append_expression(self: &mut Parser<'a>, expr: Expression<'a>) {
+    let mut root = self.root.borrow_mut();
+    let nl = if root.exprs.is_empty() { "" } else { "\n" };
+    root.exprs.push(NLExpression::new_str(nl, expr));
+  }
+
+  /// Throws away the key map and rebuilds it from the AST. Used after the AST has been changed structurally, e.g. when
+  /// tables or key/value pairs are added or removed.
+  pub fn rebuild_map(self: &mut Parser<'a>) {
+    let mut map = HashMap::new();
+    map.insert("$Root$".to_string(), HashValue::none_keys());
+    let mut tracker = TableTracker::new();
+    let mut current = String::new();
+    for nl_expr in &self.root.borrow().exprs {
+      if let Some(ref table) = nl_expr.expr.table {
+        let resolved = tracker.resolve(table);
+        for (parent, segment, key) in resolved.implicit {
+          Parser::add_child_key(&mut map, &parent, segment);
+          map.entry(key).or_insert_with(HashValue::none_keys);
+        }
+        if let Some(array_key) = resolved.array_key {
+          if !map.contains_key(&array_key) {
+            Parser::add_child_key(&mut map, &resolved.parent, resolved.segment);
+            map.insert(array_key.clone(), HashValue::none_count());
+          }
+          if let Some(HashValue{subkeys: Children::Count(ref c), ..}) = map.get(&array_key) {
+            c.set(c.get() + 1);
+          }
+          map.insert(resolved.key.clone(), HashValue::none_keys());
+        } else {
+          Parser::add_child_key(&mut map, &resolved.parent, resolved.segment);
+          map.entry(resolved.key.clone()).or_insert_with(HashValue::none_keys).value =
+            Some(Rc::new(RefCell::new(TOMLValue::Table)));
+        }
+        current = resolved.key;
+      } else if let Some(ref keyval) = nl_expr.expr.keyval {
+        let full_key = join_key(&current, &keyval.key);
+        if !map.contains_key(&full_key) {
+          Parser::add_child_key(&mut map, &current, keyval.key.clone().into_owned());
+          Parser::insert_value_into_map(&mut map, full_key, keyval.val.clone());
+        }
+      }
+    }
+    self.map = map;
+  }
+
+  fn add_child_key(map: &mut HashMap<String, HashValue<'a>>, parent: &str, child: String) {
+    if let Some(hv) = map.get(&map_key(parent)) {
+      if let Children::Keys(ref keys) = hv.subkeys {
+        Parser::insert(keys, child);
+      }
+    }
+  }
+
+  /// Inserts `val` into `map` under `key`, along with all of its child keys if it is an `Array` or `InlineTable`.
+  pub fn insert_value_into_map(map: &mut HashMap<String, HashValue<'a>>, key: String,
+    val: Rc<RefCell<TOMLValue<'a>>>) {
+    match *val.borrow() {
+      TOMLValue::Array(ref arr) => {
+        let len = arr.borrow().values.len();
+        for i in 0..len {
+          let subkey = format!("{key}[{i}]");
+          Parser::insert_value_into_map(map, subkey, arr.borrow().values[i].val.clone());
+        }
+        map.insert(key, HashValue{value: Some(val.clone()), subkeys: Children::Count(Cell::new(len))});
+      },
+      TOMLValue::InlineTable(ref it) => {
+        let hash_value = HashValue::new_keys(val.clone());
+        if let Children::Keys(ref keys) = hash_value.subkeys {
+          for kv in &it.borrow().keyvals {
+            Parser::insert(keys, kv.keyval.key.clone().into_owned());
+          }
+        }
+        for kv in &it.borrow().keyvals {
+          let subkey = format!("{}.{}", key, kv.keyval.key);
+          Parser::insert_value_into_map(map, subkey, kv.keyval.val.clone());
+        }
+        map.insert(key, hash_value);
+      },
+      _ => {
+        map.insert(key, HashValue::new_count(val.clone()));
+      },
+    }
+  }
+}
+
+/// Splits a dotted `key` into its segments, ignoring dots inside quoted segments. Segments are returned as written.
+pub fn split_key(key: &str) -> Vec<String> {
+  let mut segments = vec![];
+  let mut segment = String::new();
+  let mut quoted = false;
+  let mut escaped = false;
+  for c in key.chars() {
+    match c {
+      '.' if !quoted => {
+        segments.push(segment.trim().to_string());
+        segment = String::new();
+        continue;
+      },
+      '"' if !escaped => quoted = !quoted,
+      _ => (),
+    }
+    escaped = c == '\\' && !escaped;
+    segment.push(c);
+  }
+  segments.push(segment.trim().to_string());
+  segments
+}
+
+/// Returns `key` as it should be written in a document: unchanged if it's a valid bare or quoted key, wrapped in
+/// double quotes if it's a valid basic string, or `None` if it can't be used as a key.
+pub fn format_key(key: &str) -> Option<String> {
+  if key.is_empty() {
+    return None;
+  }
+  if key.chars().all(is_keychar) {
+    return Some(key.to_string());
+  }
+  let (inner, quoted) = if key.len() >= 2 && key.starts_with('"') && key.ends_with('"') {
+    (&key[1..key.len() - 1], true)
+  } else {
+    (key, false)
+  };
+  match Parser::quoteless_basic_string(inner) {
+    IResult::Done(i, _) if i.is_empty() && !inner.is_empty() => {
+      if quoted {
+        Some(key.to_string())
+      } else {
+        Some(format!("\"{key}\""))
+      }
+    },
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::fs;
+  use std::ffi::OsStr;
+  use std::collections::HashMap;
+  use crate::internals::ast::structs::HashValue;
+  use crate::internals::parser::Parser;
+  use crate::internals::build::{split_key, format_key};
+
+  fn assert_same_map<'a>(expected: &HashMap<String, HashValue<'a>>, actual: &HashMap<String, HashValue<'a>>) {
+    let mut expected_keys: Vec<&String> = expected.keys().collect();
+    let mut actual_keys: Vec<&String> = actual.keys().collect();
+    expected_keys.sort();
+    actual_keys.sort();
+    assert_eq!(expected_keys, actual_keys);
+    for (key, hv) in expected {
+      assert_eq!(hv, &actual[key], "value of key {key}");
+      assert_eq!(hv.subkeys, actual[key].subkeys, "children of key {key}");
+    }
+  }
+
+  #[test]
+  fn test_rebuild_map() {
+    let p = Parser::new();
+    let (mut p, _) = p.parse(r#"animal = "bear"
+[[car.owners]]
+Name = """Bob Jones"""
+[[car.owners]]
+Name = 'Jane Doe'
+[car.interior.seats]
+type = '''fabric'''
+[car]
+drivers = ["Bob", "Jane", { disallowed = "Chris", banned="Sally"}, [1, 2]]
+properties = { color = "red", accident_dates = [2008-09-29, 2011-01-16]}
+"#);
+    let parsed = p.map.clone();
+    p.rebuild_map();
+    assert_same_map(&parsed, &p.map);
+  }
+
+  #[test]
+  fn test_rebuild_map_valid_assets() {
+    for entry in fs::read_dir("./assets/valid/").unwrap() {
+      let path = entry.unwrap().path();
+      // The parser doesn't key the indented tables in hard_example.toml correctly, so its map can't be compared
+      if path.extension() != Some(OsStr::new("toml")) || path.ends_with("hard_example.toml") {
+        continue;
+      }
+      let contents = fs::read_to_string(&path).unwrap();
+      let p = Parser::new();
+      let (mut p, _) = p.parse(&contents);
+      let parsed = p.map.clone();
+      p.rebuild_map();
+      assert_same_map(&parsed, &p.map);
+    }
+  }
+
+  #[test]
+  fn test_split_key() {
+    assert_eq!(vec!["a", "\"b.c\"", "d"], split_key("a.\"b.c\".d"));
+    assert_eq!(vec!["a", "\"b\\\".c\""], split_key("a . \"b\\\".c\""));
+    assert_eq!(vec!["key"], split_key("key"));
+  }
+
+  #[test]
+  fn test_format_key() {
+    assert_eq!(Some("bare-Key_1".to_string()), format_key("bare-Key_1"));
+    assert_eq!(Some("\"quoted key\"".to_string()), format_key("\"quoted key\""));
+    assert_eq!(Some("\"needs quotes\"".to_string()), format_key("needs quotes"));
+    assert_eq!(None, format_key(""));
+    assert_eq!(None, format_key("bad\nkey"));
+  }
+}
@@ -0,0 +1,343 @@
+use std::char;
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::collections::{HashMap, HashSet};
+use crate::internals::parser::Parser;
+use crate::internals::build::split_key;
+use crate::internals::primitives::is_keychar;
+use crate::internals::walk::join_key;
+use crate::types::{Value, StrType, DocEntry, Change};
+
+impl<'a> Parser<'a> {
+  /// Returns every value in the document that isn't a table, as (normalized key, key, `Value`) tuples in document
+  /// order. `InlineTable`s are broken down into their key/value pairs so they compare equal to the same keys defined
+  /// with a table header, while `Array`s are kept whole.
+  pub fn leaves(self: &Parser<'a>) -> Vec<(String, String, Value<'a>)> {
+    let mut leaves = vec![];
+    for (key, entry) in self.entries() {
+      if let DocEntry::Value(val) = entry {
+        push_leaves(key, val, &mut leaves);
+      }
+    }
+    leaves
+  }
+
+  /// Returns the changes needed to turn this document into `other`, comparing keys and values by meaning rather than
+  /// by how they're written.
+  pub fn diff(self: &Parser<'a>, other: &Parser<'a>) -> Vec<Change<'a>> {
+    let old_leaves = self.leaves();
+    let new_leaves = other.leaves();
+    let mut new_index = HashMap::new();
+    for (i, leaf) in new_leaves.iter().enumerate() {
+      new_index.entry(leaf.0.clone()).or_insert(i);
+    }
+    let mut changes = vec![];
+    let mut seen = HashSet::new();
+    for (norm_key, key, val) in old_leaves {
+      if !seen.insert(norm_key.clone()) {
+        continue;
+      }
+      if let Some(&i) = new_index.get(&norm_key) {
+        let new_val = &new_leaves[i].2;
+        if !values_eq(&val, new_val) {
+          debug!("Changed key {key}: {val} -> {new_val}");
+          changes.push(Change::Changed(key, val, new_val.clone()));
+        }
+      } else {
+        debug!("Removed key {key}: {val}");
+        changes.push(Change::Removed(key, val));
+      }
+    }
+    for (i, (norm_key, key, val)) in new_leaves.iter().enumerate() {
+      if !seen.contains(norm_key) && new_index.get(norm_key) == Some(&i) {
+        debug!("Added key {key}: {val}");
+        changes.push(Change::Added(key.clone(), val.clone()));
+      }
+    }
+    changes
+  }
+}
+
+fn push_leaves<'a>(key: String, val: Value<'a>, leaves: &mut Vec<(String, String, Value<'a>)>) {
+  if let Value::InlineTable(ref it) = val {
+    if !it.is_empty() {
+      for (subkey, subval) in it.iter() {
+        push_leaves(join_key(&key, subkey), subval.clone(), leaves);
+      }
+      return;
+    }
+  }
+  leaves.push((normalize_key(&key), key, val));
+}
+
+/// Converts a full key to a canonical form, so that keys that are written differently but mean the same thing are
+/// equal. Quoted segments are unescaped and written as bare keys when possible.
+pub fn normalize_key(key: &str) -> String {
+  let mut normalized = String::new();
+  for segment in split_key(key) {
+    let (name, index) = if segment.starts_with('"') {
+      match segment[1..].rfind('"') {
+        Some(end) => segment.split_at(end + 2),
+        None => (segment.as_str(), ""),
+      }
+    } else {
+      match segment.find('[') {
+        Some(start) => segment.split_at(start),
+        None => (segment.as_str(), ""),
+      }
+    };
+    let name = if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') {
+      unescape(&name[1..name.len() - 1]).unwrap_or_else(|| name[1..name.len() - 1].to_string())
+    } else {
+      name.to_string()
+    };
+    if !normalized.is_empty() {
+      normalized.push('.');
+    }
+    if !name.is_empty() && name.chars().all(is_keychar) {
+      normalized.push_str(&name);
+    } else {
+      normalized.push('"');
+      normalized.push_str(&escape(&name));
+      normalized.push('"');
+    }
+    normalized.push_str(index);
+  }
+  normalized
+}
+
+/// Returns true if `left` and `right` mean the same thing, regardless of how they're written.
+pub fn values_eq(left: &Value, right: &Value) -> bool {
+  match (left, right) {
+    (Value::Integer(i), Value::Integer(j)) => {
+      let (i, j) = (i.replace('_', ""), j.replace('_', ""));
+      match (i.parse::<i64>(), j.parse::<i64>()) {
+        (Ok(i), Ok(j)) => i == j,
+        _ => i.trim_start_matches('+') == j.trim_start_matches('+'),
+      }
+    },
+    (Value::Float(i), Value::Float(j)) => {
+      let (i, j) = (i.replace('_', ""), j.replace('_', ""));
+      match (i.parse::<f64>(), j.parse::<f64>()) {
+        // Exactly the same number, so -0.0 equals 0.0 but nan equals nothing
+        (Ok(i), Ok(j)) => i.partial_cmp(&j) == Some(Ordering::Equal),
+        _ => i == j,
+      }
+    },
+    (&Value::Boolean(i), &Value::Boolean(j)) => i == j,
+    (Value::DateTime(i), Value::DateTime(j)) => normalize_datetime(i) == normalize_datetime(j),
+    (&Value::String(ref s, st), &Value::String(ref t, tt)) => string_value(s, st) == string_value(t, tt),
+    (Value::Array(i), Value::Array(j)) => {
+      i.len() == j.len() && i.iter().zip(j.iter()).all(|(v, w)| values_eq(v, w))
+    },
+    (Value::InlineTable(i), Value::InlineTable(j)) => {
+      i.len() == j.len() && i.iter().all(|(k, v)| {
+        let k = normalize_key(k);
+        j.iter().any(|(l, w)| normalize_key(l) == k && values_eq(v, w))
+      })
+    },
+    _ => false,
+  }
+}
+
+fn normalize_datetime(dt: &crate::types::DateTime) -> String {
+  let s = format!("{dt}");
+  if s.ends_with("+00:00") || s.ends_with("-00:00") {
+    format!("{}Z", &s[..s.len() - 6])
+  } else {
+    s
+  }
+}
+
+/// Returns the text a string value stands for, with escapes replaced and line ending backslashes and the newline
+/// that may follow the opening delimiter of a multi-line string removed.
+pub fn string_value(s: &str, st: StrType) -> String {
+  match st {
+    StrType::Basic => unescape(s).unwrap_or_else(|| s.to_string()),
+    StrType::MLBasic => {
+      let trimmed = trim_ml_newline(s);
+      unescape(&join_continued_lines(trimmed)).unwrap_or_else(|| trimmed.to_string())
+    },
+    StrType::Literal => s.to_string(),
+    StrType::MLLiteral => trim_ml_newline(s).to_string(),
+  }
+}
+
+/// Returns true if the text of a basic or multi-line basic string only has valid escape sequences and none of the
+/// control characters that have to be escaped. Literal strings have no escapes, so they're always valid.
+pub fn valid_string(s: &str, st: StrType) -> bool {
+  let text = match st {
+    StrType::Basic => s.to_string(),
+    StrType::MLBasic => join_continued_lines(trim_ml_newline(s)),
+    StrType::Literal | StrType::MLLiteral => return true,
+  };
+  text.chars().all(|c| c >= ' ' || (st == StrType::MLBasic && (c == '\n' || c == '\r' || c == '\t'))) &&
+    unescape(&text).is_some()
+}
+
+fn trim_ml_newline(s: &str) -> &str {
+  if s.starts_with("\r\n") {
+    &s[2..]
+  } else if s.starts_with('\n') {
+    &s[1..]
+  } else {
+    s
+  }
+}
+
+// Removes every backslash at the end of a line along with the whitespace and newlines that follow it
+fn join_continued_lines(s: &str) -> String {
+  let mut result = String::new();
+  let mut chars = s.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '\\' {
+      match chars.peek() {
+        Some(&'\\') => {
+          result.push_str("\\\\");
+          chars.next();
+          continue;
+        },
+        Some(&next) if next == '\n' || next == '\r' || next == ' ' || next == '\t' => {
+          let rest: String = chars.clone().take_while(|c| *c == ' ' || *c == '\t').collect();
+          let after = chars.clone().nth(rest.chars().count());
+          if after == Some('\n') || after == Some('\r') {
+            while let Some(&w) = chars.peek() {
+              if w == ' ' || w == '\t' || w == '\n' || w == '\r' {
+                chars.next();
+              } else {
+                break;
+              }
+            }
+            continue;
+          }
+        },
+        _ => (),
+      }
+    }
+    result.push(c);
+  }
+  result
+}
+
+/// Replaces the escape sequences of a basic string with the characters they stand for. Returns `None` if `s` contains
+/// an invalid escape sequence.
+pub fn unescape(s: &str) -> Option<String> {
+  let mut result = String::new();
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      result.push(c);
+      continue;
+    }
+    match chars.next()? {
+      'b' => result.push('\u{8}'),
+      't' => result.push('\t'),
+      'n' => result.push('\n'),
+      'f' => result.push('\u{c}'),
+      'r' => result.push('\r'),
+      '"' => result.push('"'),
+      '\\' => result.push('\\'),
+      '/' => result.push('/'),
+      'u' => result.push(unicode_escape(&mut chars, 4)?),
+      'U' => result.push(unicode_escape(&mut chars, 8)?),
+      _ => return None,
+    }
+  }
+  Some(result)
+}
+
+fn unicode_escape(chars: &mut std::str::Chars, len: usize) -> Option<char> {
+  let hex: String = chars.take(len).collect();
+  if hex.len() != len || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+  u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+}
+
+/// Escapes `s` so it can be written inside a basic string.
+pub fn escape(s: &str) -> String {
+  let mut result = String::new();
+  for c in s.chars() {
+    match c {
+      '"' => result.push_str("\\\""),
+      '\\' => result.push_str("\\\\"),
+      '\u{8}' => result.push_str("\\b"),
+      '\t' => result.push_str("\\t"),
+      '\n' => result.push_str("\\n"),
+      '\u{c}' => result.push_str("\\f"),
+      '\r' => result.push_str("\\r"),
+      c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+        let _ = write!(result, "\\u{:04X}", c as u32);
+      },
+      c => result.push(c),
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod test {
+  use crate::internals::parser::Parser;
+  use crate::internals::compare::{normalize_key, values_eq, string_value, valid_string, unescape, escape};
+  use crate::types::{Value, StrType, Change};
+
+  #[test]
+  fn test_normalize_key() {
+    assert_eq!("a.b[0].c", normalize_key("\"a\".b[0].\"c\""));
+    assert_eq!("\"a b\"[1].\"c.d\"", normalize_key("\"a b\"[1].\"c.d\""));
+    assert_eq!("\"é\".x", normalize_key("\"\\u00e9\".x"));
+    assert_eq!("\"tab\\there\"", normalize_key("\"tab\\u0009here\""));
+  }
+
+  #[test]
+  fn test_values_eq() {
+    assert!(values_eq(&Value::Integer("1_000".into()), &Value::Integer("+1000".into())));
+    assert!(!values_eq(&Value::Integer("1000".into()), &Value::Float("1000.0".into())));
+    assert!(values_eq(&Value::Float("1e3".into()), &Value::Float("1_000.0".into())));
+    assert!(values_eq(&Value::Float("-0.0".into()), &Value::Float("0.0".into())));
+    assert!(!values_eq(&Value::Float("1.0".into()), &Value::Float("1.0000000000000002".into())));
+    assert!(values_eq(&Value::String("a\\tb".into(), StrType::Basic), &Value::String("a\tb".into(), StrType::Literal)));
+    assert!(values_eq(&Value::datetime_parse("1979-05-27T07:32:00Z").unwrap(),
+      &Value::datetime_parse("1979-05-27T07:32:00+00:00").unwrap()));
+    assert!(values_eq(&Value::InlineTable(vec![("a".into(), Value::int(1)), ("\"b\"".into(), Value::int(2))].into()),
+      &Value::InlineTable(vec![("b".into(), Value::int(2)), ("a".into(), Value::int(1))].into())));
+    assert!(!values_eq(&Value::Array(vec![Value::int(1), Value::int(2)].into()),
+      &Value::Array(vec![Value::int(2), Value::int(1)].into())));
+  }
+
+  #[test]
+  fn test_string_value() {
+    assert_eq!("The quick brown fox", string_value("\nThe quick \\\n\n  brown \\\n  fox", StrType::MLBasic));
+    assert_eq!("C:\\path", string_value("\nC:\\path", StrType::MLLiteral));
+    assert_eq!("a\\\nb", string_value("a\\\\\\nb", StrType::MLBasic));
+    assert_eq!(None, unescape("\\q"));
+    assert_eq!("\\\"\\u0001", escape("\"\u{1}"));
+  }
+
+  #[test]
+  fn test_valid_string() {
+    assert!(valid_string("tab\\t \\u00e9", StrType::Basic));
+    assert!(!valid_string("C:\\path", StrType::Basic));
+    assert!(!valid_string("raw\ttab", StrType::Basic));
+    assert!(valid_string("\nline \\\n  continued\ttab", StrType::MLBasic));
+    assert!(valid_string("C:\\path", StrType::Literal));
+  }
+
+  #[test]
+  fn test_diff() {
+    let p = Parser::new();
+    let (p, _) = p.parse("a = 1_000\nb = 'x'\nc = 3\n[t]\nd = {e = 1, f = 2}\n[[u]]\ng = 1\n");
+    let q = Parser::new();
+    let (q, _) = q.parse("\"b\" = \"x\"\na = 1000\n[t.d]\nf = 2\ne = 5\n[[u]]\ng = 1\n[[u]]\nh = true\n");
+    assert_eq!(vec![
+      Change::Removed("c".to_string(), Value::int(3)),
+      Change::Changed("t.d.e".to_string(), Value::int(1), Value::int(5)),
+      Change::Added("u[1].h".to_string(), Value::bool(true)),
+    ], p.diff(&q));
+    assert!(p.diff(&p).is_empty());
+    let (p, _) = Parser::new().parse("x = 1.0\n");
+    let (q, _) = Parser::new().parse("x = 1.0000000000000002\n");
+    let (old, new) = (Value::Float("1.0".into()), Value::Float("1.0000000000000002".into()));
+    assert_eq!(vec![Change::Changed("x".to_string(), old, new)], p.diff(&q));
+  }
+}
@@ -0,0 +1,636 @@
+use std::cell::RefCell;
+use std::borrow::Cow;
+use std::mem;
+use std::rc::Rc;
+use crate::internals::ast::structs::{HashValue, TOMLValue, Expression, NLExpression, KeyVal, WSSep, Table, TableType,
+                                     WSKeySep, TableKeyVal, Comment};
+use crate::internals::keypath::key_name;
+use crate::internals::parser::Parser;
+use crate::internals::build::{split_key, format_key};
+use crate::internals::walk::{TableTracker, join_key, map_key};
+use crate::types::{Value, Children, KeyPath, KeySegment};
+
+impl<'a> Parser<'a> {
+  /// Adds the key/value pair `key = val` to the table or inline table `table_key`, `""` being the root table. The new
+  /// key/value pair goes after the last one of the table. If the table doesn't have a header yet, one is added at the
+  /// end of the document, or at the end of the array of tables element it's part of. Returns false if the key already
+  /// exists, `key` or `val` is invalid, or `table_key` is a value or an array.
+  pub fn insert_keyval(self: &mut Parser<'a>, table_key: &str, key: &str, val: &Value<'a>) -> bool {
+    let Some(key) = format_key(key) else {
+      return false;
+    };
+    let full_key = join_key(table_key, &key);
+    if self.map.contains_key(&full_key) {
+      return false;
+    }
+    let Some(tval) = Parser::convert_vector(val) else {
+      return false;
+    };
+    debug!("Inserting keyval: {full_key} = {val}");
+    let val_rc = Rc::new(RefCell::new(tval));
+    let inline_table = match self.map.get(&map_key(table_key)) {
+      Some(&HashValue{value: Some(ref v), ..}) => match *v.borrow() {
+        TOMLValue::Table => None,
+        TOMLValue::InlineTable(ref it) => Some(it.clone()),
+        _ => return false,
+      },
+      _ => None,
+    };
+    if let Some(it) = inline_table {
+      let mut it = it.borrow_mut();
+      let mut new_last = TableKeyVal::last(key, val_rc);
+      if let Some(last) = it.keyvals.last_mut() {
+        last.kv_sep = Some(WSSep::new_str("", " "));
+        new_last.comment_nls = mem::take(&mut last.comment_nls);
+      }
+      it.keyvals.push(new_last);
+    } else if !self.insert_table_keyval(table_key, key, val_rc) {
+      return false;
+    }
+    self.rebuild_map();
+    true
+  }
+
+  /// Removes the key/value pair `key`, which can be defined on its own line or inside an inline table. Returns false
+  /// if the key doesn't exist or is a table.
+  pub fn remove_keyval(self: &mut Parser<'a>, key: &str) -> bool {
+    let tables = self.expression_tables();
+    let index = self.root.borrow().exprs.iter().zip(&tables).position(|(nl_expr, table)| {
+      match nl_expr.expr.keyval {
+        Some(ref keyval) => join_key(table, &keyval.key) == key,
+        None => false,
+      }
+    });
+    if let Some(i) = index {
+      debug!("Removing keyval: {key}");
+      let mut root = self.root.borrow_mut();
+      root.exprs.remove(i);
+      if i == 0 && !root.exprs.is_empty() {
+        root.exprs[0].nl = "".into();
+      }
+    } else if !self.remove_inline_keyval(key) {
+      return false;
+    }
+    self.rebuild_map();
+    true
+  }
+
+  /// Adds the new key `key` with the value `val`. Any tables on the way to `key` that don't exist yet are added, and
+  /// if `key` ends in an index one past the end of an array, `val` is appended to the array. Returns false if `key`
+  /// already exists or isn't a valid key path, `val` is invalid, or the table it belongs to is a value.
+  pub fn insert_value<S>(self: &mut Parser<'a>, key: S, val: Value<'a>) -> bool where S: Into<String> {
+    let Ok(path) = KeyPath::parse(&key.into()) else {
+      return false;
+    };
+    let (last, parent) = match path.segments().split_last() {
+      Some((last, parent)) => (last.clone(), KeyPath::from(parent.to_vec())),
+      None => return false,
+    };
+    match last {
+      KeySegment::Key(name) => {
+        let table_key = self.resolve_new_key_path(&parent);
+        self.insert_keyval(&table_key, &KeyPath::new().key(name).to_string(), &val)
+      },
+      KeySegment::Index(i) => {
+        let Some(array_key) = self.resolve_key_path(&parent) else {
+          return false;
+        };
+        match self.get_value(array_key.as_str()) {
+          Some(Value::Array(ref values)) if values.len() == i => {
+            debug!("Appending to array: {array_key}");
+            let mut values = values.to_vec();
+            values.push(val);
+            self.set_value(array_key, Value::Array(Rc::new(values)))
+          },
+          _ => false,
+        }
+      },
+    }
+  }
+
+  /// Adds an empty table with the header `[key]`, or `[[key]]` if `key` ends in an index one past the end of an array
+  /// of tables. Returns false if the table already has a header, `key` isn't a valid key path, or it's a value.
+  pub fn insert_table<S>(self: &mut Parser<'a>, key: S) -> bool where S: Into<String> {
+    let table_key = match KeyPath::parse(&key.into()) {
+      Ok(ref p) if !p.segments().is_empty() => self.resolve_new_key_path(p),
+      _ => return false,
+    };
+    let tables = self.expression_tables();
+    let has_header = self.root.borrow().exprs.iter().zip(&tables)
+      .any(|(nl_expr, table)| nl_expr.expr.table.is_some() && *table == table_key);
+    if has_header {
+      return false;
+    }
+    let Some(index) = self.insert_table_header(&table_key, &tables) else {
+      return false;
+    };
+    let next_is_header = self.root.borrow().exprs.get(index).is_some_and(|nl_expr| nl_expr.expr.table.is_some());
+    if next_is_header {
+      self.insert_expression(index, Expression::new(WSSep::new_str("", ""), None, None, None));
+    }
+    self.rebuild_map();
+    true
+  }
+
+  /// Removes `key`, whether it's a key/value pair, an element of an array, a table along with all of its sub-tables,
+  /// an array of tables, or one table of an array of tables. Comments directly above the next table are kept. Returns
+  /// false if the key doesn't exist or is the root table.
+  pub fn remove_key<S>(self: &mut Parser<'a>, key: S) -> bool where S: Into<String> {
+    let k = match self.resolve_key(&key.into()) {
+      Some(ref k) if !k.is_empty() => k.clone(),
+      _ => return false,
+    };
+    let is_table = match self.map.get(&k) {
+      Some(&HashValue{value: Some(ref v), ..}) => *v.borrow() == TOMLValue::Table,
+      Some(&HashValue{value: None, ..}) => true,
+      None => return false,
+    };
+    if is_table {
+      return self.remove_table(&k);
+    }
+    let (segment, index) = {
+      let segments = split_key(&k);
+      let last = &segments[segments.len() - 1];
+      match split_index(last) {
+        (_, Some(i)) => (last.clone(), i),
+        _ => return self.remove_keyval(&k),
+      }
+    };
+    let array_key = &k[..k.len() - (segment.len() - segment.rfind('[').unwrap_or(0))];
+    match self.get_value(array_key) {
+      Some(Value::Array(ref values)) => {
+        debug!("Removing array element: {k}");
+        let mut values = values.to_vec();
+        values.remove(index);
+        self.set_value(array_key, Value::Array(Rc::new(values)))
+      },
+      _ => false,
+    }
+  }
+
+  fn remove_table(self: &mut Parser<'a>, key: &str) -> bool {
+    let tables = self.expression_tables();
+    let (sub_prefix, element_prefix) = (format!("{key}."), format!("{key}["));
+    let mut remove: Vec<bool> = tables.iter()
+      .map(|t| t == key || t.starts_with(&sub_prefix) || t.starts_with(&element_prefix)).collect();
+    if !remove.iter().any(|&r| r) {
+      return false;
+    }
+    debug!("Removing table: {key}");
+    let mut root = self.root.borrow_mut();
+    // Comments at the end of a removed table usually describe the table after it, so they stay
+    for end in 1..remove.len() {
+      if remove[end - 1] && !remove[end] {
+        let is_comment = |e: &Expression| e.comment.is_some() && e.keyval.is_none() && e.table.is_none();
+        let comments: Vec<usize> = (0..end).rev().take_while(|&i| remove[i] && is_comment(&root.exprs[i].expr))
+          .collect();
+        for i in comments {
+          remove[i] = false;
+        }
+      }
+    }
+    // The blank line after the document's final newline belongs to the last table, but ends the document
+    if let Some(last) = root.exprs.last() {
+      let e = &last.expr;
+      if e.keyval.is_none() && e.table.is_none() && e.comment.is_none() && e.invalid.is_none() {
+        remove[root.exprs.len() - 1] = false;
+      }
+    }
+    for i in (0..remove.len()).rev().filter(|&i| remove[i]) {
+      root.exprs.remove(i);
+    }
+    if let Some(first) = root.exprs.first_mut() {
+      first.nl = "".into();
+    }
+    drop(root);
+    self.rebuild_map();
+    true
+  }
+
+  /// Renames the last segment of `key` to `new_name`, which is quoted if it isn't a valid bare key. Renaming a table
+  /// renames it in the headers of all of its sub-tables too. Returns false if the key doesn't exist, is an array
+  /// element or the root table, `new_name` can't be used as a key, or its table already has a key named `new_name`.
+  pub fn rename_key<S, T>(self: &mut Parser<'a>, key: S, new_name: T) -> bool where S: Into<String>, T: Into<String> {
+    let k = match self.resolve_key(&key.into()) {
+      Some(ref k) if !k.is_empty() && !k.ends_with(']') => k.clone(),
+      _ => return false,
+    };
+    let Some(new_name) = format_key(&new_name.into()) else {
+      return false;
+    };
+    let mut segments = split_key(&k);
+    let last = segments.pop().unwrap_or_default();
+    let parent = segments.join(".");
+    let taken = match self.get_children(map_key(&parent)) {
+      Some(Children::Keys(keys)) => keys.borrow().iter().any(|c| key_name(c) == key_name(&new_name)),
+      _ => false,
+    };
+    if taken {
+      return false;
+    }
+    debug!("Renaming key: {k} to {new_name}");
+    let tables = self.expression_tables();
+    let value = self.map.get(&k).and_then(|hv| hv.value.clone());
+    match value {
+      Some(ref v) if *v.borrow() != TOMLValue::Table => {
+        let mut root = self.root.borrow_mut();
+        let keyval = root.exprs.iter_mut().zip(&tables).find_map(|(nl_expr, table)| {
+          match nl_expr.expr.keyval {
+            Some(ref mut keyval) if join_key(table, &keyval.key) == k => Some(keyval),
+            _ => None,
+          }
+        });
+        if let Some(keyval) = keyval {
+          keyval.key = new_name.into();
+        } else {
+          drop(root);
+          if !self.rename_inline_keyval(&parent, &last, new_name) {
+            return false;
+          }
+        }
+      },
+      _ => {
+        let depth = segments.len();
+        let (sub_prefix, element_prefix) = (format!("{k}."), format!("{k}["));
+        let mut root = self.root.borrow_mut();
+        for (nl_expr, table) in root.exprs.iter_mut().zip(&tables) {
+          if *table != k && !table.starts_with(&sub_prefix) && !table.starts_with(&element_prefix) {
+            continue;
+          }
+          let renamed = match nl_expr.expr.table {
+            Some(ref tt) => match **tt {
+              TableType::Standard(ref t) => TableType::Standard(rename_segment(t, depth, &new_name)),
+              TableType::Array(ref t) => TableType::Array(rename_segment(t, depth, &new_name)),
+            },
+            None => continue,
+          };
+          nl_expr.expr.table = Some(Rc::new(renamed));
+        }
+      },
+    }
+    self.rebuild_map();
+    true
+  }
+
+  /// Sets the comment at the end of the line that defines `key`, a key/value pair or a table header, to `# text`,
+  /// replacing any comment already there. An empty `text` removes the comment. Returns false if the key doesn't
+  /// exist, isn't defined on a line of its own, or `text` has more than one line.
+  pub fn set_comment<S, T>(self: &mut Parser<'a>, key: S, text: T) -> bool where S: Into<String>, T: Into<String> {
+    let k = match self.resolve_key(&key.into()) {
+      Some(ref k) if !k.is_empty() => k.clone(),
+      _ => return false,
+    };
+    let text = text.into();
+    if text.contains(['\n', '\r']) {
+      return false;
+    }
+    let tables = self.expression_tables();
+    let mut root = self.root.borrow_mut();
+    let expr = root.exprs.iter_mut().zip(&tables).find_map(|(nl_expr, table)| {
+      let defines_key = match nl_expr.expr.keyval {
+        Some(ref keyval) => join_key(table, &keyval.key) == k,
+        None => nl_expr.expr.table.is_some() && *table == k,
+      };
+      if defines_key { Some(&mut nl_expr.expr) } else { None }
+    });
+    let Some(expr) = expr else {
+      return false;
+    };
+    debug!("Setting comment of {k} to: {text}");
+    if text.is_empty() {
+      expr.comment = None;
+      expr.ws.ws2 = "".into();
+    } else {
+      if expr.ws.ws2.is_empty() {
+        expr.ws.ws2 = " ".into();
+      }
+      expr.comment = Some(Comment::new_string(format!(" {text}")));
+    }
+    true
+  }
+
+  fn rename_inline_keyval(self: &mut Parser<'a>, table_key: &str, key: &str, new_name: String) -> bool {
+    let inline_table = match self.map.get(&map_key(table_key)) {
+      Some(&HashValue{value: Some(ref v), ..}) => match *v.borrow() {
+        TOMLValue::InlineTable(ref it) => it.clone(),
+        _ => return false,
+      },
+      _ => return false,
+    };
+    let mut it = inline_table.borrow_mut();
+    match it.keyvals.iter_mut().find(|tkv| tkv.keyval.key == key) {
+      Some(tkv) => {
+        tkv.keyval.key = new_name.into();
+        true
+      },
+      None => false,
+    }
+  }
+
+  fn remove_inline_keyval(self: &mut Parser<'a>, key: &str) -> bool {
+    let mut segments = split_key(key);
+    let last = match segments.pop() {
+      Some(ref s) if !segments.is_empty() => s.clone(),
+      _ => return false,
+    };
+    let inline_table = match self.map.get(&segments.join(".")) {
+      Some(&HashValue{value: Some(ref v), ..}) => match *v.borrow() {
+        TOMLValue::InlineTable(ref it) => it.clone(),
+        _ => return false,
+      },
+      _ => return false,
+    };
+    let mut it = inline_table.borrow_mut();
+    let Some(pos) = it.keyvals.iter().position(|tkv| tkv.keyval.key == last) else {
+      return false;
+    };
+    debug!("Removing inline table keyval: {key}");
+    let removed = it.keyvals.remove(pos);
+    if pos == it.keyvals.len() {
+      if let Some(new_last) = it.keyvals.last_mut() {
+        new_last.kv_sep = None;
+        new_last.comment_nls = removed.comment_nls;
+      }
+    }
+    true
+  }
+
+  fn insert_table_keyval(self: &mut Parser<'a>, table_key: &str, key: String, val: Rc<RefCell<TOMLValue<'a>>>)
+    -> bool {
+    let mut tables = self.expression_tables();
+    let header = self.root.borrow().exprs.iter().zip(&tables)
+      .position(|(nl_expr, table)| nl_expr.expr.table.is_some() && table == table_key);
+    let (index, ws, blank_after) = match header {
+      Some(h) => {
+        let root = self.root.borrow();
+        let end = (h + 1..root.exprs.len()).find(|&i| root.exprs[i].expr.table.is_some()).unwrap_or(root.exprs.len());
+        match (h + 1..end).rev().find(|&i| root.exprs[i].expr.keyval.is_some()) {
+          Some(i) => (i + 1, root.exprs[i].expr.ws.ws1.clone(), false),
+          None => (h + 1, "".into(), false),
+        }
+      },
+      None if table_key.is_empty() => {
+        let root = self.root.borrow();
+        let end = root.exprs.iter().position(|nl_expr| nl_expr.expr.table.is_some()).unwrap_or(root.exprs.len());
+        match (0..end).rev().find(|&i| root.exprs[i].expr.keyval.is_some()) {
+          Some(i) => (i + 1, root.exprs[i].expr.ws.ws1.clone(), false),
+          None => ((0..end).rev().find(|&i| !is_blank(&root.exprs[i].expr)).map_or(0, |i| i + 1), "".into(), true),
+        }
+      },
+      None => match self.insert_table_header(table_key, &tables) {
+        Some(i) => (i, "".into(), true),
+        None => return false,
+      },
+    };
+    let keyval = KeyVal::new_string(key, WSSep::new_str(" ", " "), val);
+    self.insert_expression(index, Expression::new(WSSep{ws1: ws, ws2: "".into()}, Some(keyval), None, None));
+    tables = self.expression_tables();
+    let next_is_header = match self.root.borrow().exprs.get(index + 1) {
+      Some(nl_expr) => nl_expr.expr.table.is_some() && tables[index + 1] != table_key,
+      None => false,
+    };
+    if blank_after && next_is_header {
+      self.insert_expression(index + 1, Expression::new(WSSep::new_str("", ""), None, None, None));
+    }
+    true
+  }
+
+  /// Adds a header for the table `table_key`, which must not have one yet. The header goes at the end of the array of
+  /// tables element it belongs to, or at the end of the document. Returns the index of the expression after the
+  /// header, or `None` if a header for `table_key` can't be added.
+  fn insert_table_header(self: &mut Parser<'a>, table_key: &str, tables: &[String]) -> Option<usize> {
+    let segments = split_key(table_key);
+    let mut keys = vec![];
+    let mut prefix = String::new();
+    let mut element = None;
+    let mut is_array = false;
+    for (i, segment) in segments.iter().enumerate() {
+      let (name, index) = split_index(segment);
+      let name_key = join_key(&prefix, name);
+      let is_last = i == segments.len() - 1;
+      match (index, self.map.get(&name_key)) {
+        (Some(n), Some(&HashValue{value: None, subkeys: Children::Count(ref c)})) if n < c.get() && !is_last => {
+          element = Some(join_key(&prefix, segment));
+        },
+        (Some(n), Some(&HashValue{value: None, subkeys: Children::Count(ref c)})) if n == c.get() && is_last => {
+          is_array = true;
+        },
+        (Some(0), None) if is_last => is_array = true,
+        (Some(_), _) => return None,
+        (None, Some(&HashValue{value: Some(ref v), ..})) if *v.borrow() != TOMLValue::Table => return None,
+        (None, Some(&HashValue{value: None, subkeys: Children::Count(_)})) => return None,
+        (None, _) => (),
+      }
+      keys.push(WSKeySep::new_string(WSSep::new_str("", ""), name.to_string()));
+      prefix = join_key(&prefix, segment);
+    }
+    let table = Table{keys};
+    let table_type = if is_array { TableType::Array(table) } else { TableType::Standard(table) };
+    let mut index = {
+      let root = self.root.borrow();
+      let last_non_blank = |from: usize, to: usize| {
+        (from..to).rev().find(|&i| !is_blank(&root.exprs[i].expr)).map_or(from, |i| i + 1)
+      };
+      match element {
+        Some(ref element) => {
+          let h = root.exprs.iter().zip(tables)
+            .position(|(nl_expr, table)| nl_expr.expr.table.is_some() && table == element)?;
+          let sub_prefix = format!("{element}.");
+          let end = (h + 1..root.exprs.len()).find(|&i| tables[i] != *element && !tables[i].starts_with(&sub_prefix))
+            .unwrap_or(root.exprs.len());
+          last_non_blank(h, end)
+        },
+        None => last_non_blank(0, root.exprs.len()),
+      }
+    };
+    debug!("Inserting table header: {table_type}");
+    if index > 0 && !is_blank(&self.root.borrow().exprs[index - 1].expr) {
+      self.insert_expression(index, Expression::new(WSSep::new_str("", ""), None, None, None));
+      index += 1;
+    }
+    self.insert_expression(index, Expression::new(WSSep::new_str("", ""), None, Some(Rc::new(table_type)), None));
+    Some(index + 1)
+  }
+
+  /// Inserts `expr` so that it becomes the expression at `index`, using the same newlines as the rest of the document.
+  fn insert_expression(self: &mut Parser<'a>, index: usize, expr: Expression<'a>) {
+    let mut root = self.root.borrow_mut();
+    let nl: Cow<'a, str> = root.exprs.iter().map(|nl_expr| nl_expr.nl.clone()).find(|nl| !nl.is_empty())
+      .unwrap_or_else(|| "\n".into());
+    if root.exprs.is_empty() {
+      root.exprs.push(NLExpression::new_str("", expr));
+      root.exprs.push(NLExpression{nl, expr: Expression::new(WSSep::new_str("", ""), None, None, None)});
+    } else if index == 0 {
+      root.exprs[0].nl = nl;
+      root.exprs.insert(0, NLExpression::new_str("", expr));
+    } else {
+      root.exprs.insert(index, NLExpression{nl, expr});
+    }
+  }
+
+  /// Returns the full key of the table each expression of the document belongs to, `""` being the root table. A
+  /// table header belongs to the table it defines.
+  fn expression_tables(self: &Parser<'a>) -> Vec<String> {
+    let mut tracker = TableTracker::new();
+    let mut current = String::new();
+    self.root.borrow().exprs.iter().map(|nl_expr| {
+      if let Some(ref table) = nl_expr.expr.table {
+        current = tracker.resolve(table).key;
+      }
+      current.clone()
+    }).collect()
+  }
+}
+
+/// Returns a copy of the header `t` with the key segment at `depth` renamed to `new_name`.
+fn rename_segment<'a>(t: &Table<'a>, depth: usize, new_name: &str) -> Table<'a> {
+  Table{keys: t.keys.iter().enumerate().map(|(i, k)| {
+    let key = if i == depth { new_name.to_string().into() } else { k.key.clone() };
+    WSKeySep{ws: WSSep{ws1: k.ws.ws1.clone(), ws2: k.ws.ws2.clone()}, key}
+  }).collect()}
+}
+
+fn is_blank(expr: &Expression) -> bool {
+  expr.keyval.is_none() && expr.table.is_none() && expr.comment.is_none() && expr.invalid.is_none()
+}
+
+/// Splits a key segment like `fruit[1]` into its name and array index.
+fn split_index(segment: &str) -> (&str, Option<usize>) {
+  if segment.ends_with(']') {
+    if let Some(start) = segment.rfind('[') {
+      if let Ok(index) = segment[start + 1..segment.len() - 1].parse() {
+        return (&segment[..start], Some(index));
+      }
+    }
+  }
+  (segment, None)
+}
+
+#[cfg(test)]
+mod test {
+  use std::cell::Cell;
+  use std::rc::Rc;
+  use crate::internals::parser::Parser;
+  use crate::types::{Value, Children};
+
+  fn parse_insert(doc: &str, table_key: &str, key: &str, val: Value) -> String {
+    let (mut p, _) = Parser::new().parse(doc);
+    assert!(p.insert_keyval(table_key, key, &val), "insert {} into {}", key, table_key);
+    format!("{p}")
+  }
+
+  #[test]
+  fn test_insert_keyval() {
+    assert_eq!("a = 1\nb = 2\n\n[t]\nc = 3\n",
+      parse_insert("a = 1\n\n[t]\nc = 3\n", "", "b", Value::int(2)));
+    assert_eq!("[t]\n  c = 3\n  d = \"x y\"\n[u]\n",
+      parse_insert("[t]\n  c = 3\n[u]\n", "t", "d", Value::basic_string("x y").unwrap()));
+    assert_eq!("# top\nb = 2\n\n[t]\n", parse_insert("# top\n[t]\n", "", "b", Value::int(2)));
+    assert_eq!("t = { a = 1, b = 2 }\n", parse_insert("t = { a = 1 }\n", "t", "b", Value::int(2)));
+    assert_eq!("a = 1\n\n[x.y]\n\"z z\" = true\n",
+      parse_insert("a = 1\n", "x.y", "z z", Value::bool(true)));
+    assert_eq!("[[f]]\nn = 1\n\n[f.g]\nk = 2\n\n[[f]]\nn = 3\n",
+      parse_insert("[[f]]\nn = 1\n\n[[f]]\nn = 3\n", "f[0].g", "k", Value::int(2)));
+    assert_eq!("[[f]]\nn = 1\n\n[[f]]\nn = 2\n", parse_insert("[[f]]\nn = 1\n", "f[1]", "n", Value::int(2)));
+    assert_eq!("k = 1\n", parse_insert("", "", "k", Value::int(1)));
+    let (mut p, _) = Parser::new().parse("a = 1\n[[f]]\n");
+    assert!(p.get_value("a").is_some());
+    assert!(!p.insert_keyval("", "a", &Value::int(2)));
+    assert!(!p.insert_keyval("a", "b", &Value::int(2)));
+    assert!(!p.insert_keyval("f", "b", &Value::int(2)));
+    assert!(!p.insert_keyval("f[2]", "b", &Value::int(2)));
+    assert!(p.insert_keyval("f[0]", "b", &Value::int(2)));
+    assert_eq!(Some(Value::int(2)), p.get_value("f[0].b"));
+  }
+
+  #[test]
+  fn test_remove_keyval() {
+    let (mut p, _) = Parser::new().parse("a = 1\nb = { c = 2, d = 3 }\n[t]\ne = 4 # gone\nf = 5\n");
+    assert!(p.remove_keyval("a"));
+    assert!(p.remove_keyval("b.d"));
+    assert!(p.remove_keyval("t.e"));
+    assert!(!p.remove_keyval("t.e"));
+    assert!(!p.remove_keyval("t"));
+    assert_eq!("b = { c = 2 }\n[t]\nf = 5\n", format!("{p}"));
+    assert_eq!(None, p.get_value("t.e"));
+    assert_eq!(Some(Value::int(5)), p.get_value("t.f"));
+  }
+
+  #[test]
+  fn test_insert_value() {
+    let (mut p, _) = Parser::new().parse("a = [1]\nt = { b = 1 }\n\n[[f]]\nn = 1\n");
+    assert!(p.insert_value("t.'c d'", Value::int(2)));
+    assert!(p.insert_value("a[1]", Value::int(2)));
+    assert!(p.insert_value("f[0].g.h", Value::bool(true)));
+    assert!(!p.insert_value("a[3]", Value::int(4)));
+    assert!(!p.insert_value("a.b", Value::int(4)));
+    assert!(!p.insert_value("f[0].n", Value::int(4)));
+    assert!(!p.insert_value("'x", Value::int(4)));
+    assert_eq!("a = [1, 2]\nt = { b = 1, \"c d\" = 2 }\n\n[[f]]\nn = 1\n\n[f.g]\nh = true\n", format!("{p}"));
+    assert_eq!(Some(Value::bool(true)), p.get_value("f[0].g.h"));
+  }
+
+  #[test]
+  fn test_insert_table() {
+    let (mut p, _) = Parser::new().parse("[a.b]\nc = 1\n[[f]]\n[x]\n");
+    assert!(p.insert_table("a"));
+    assert!(p.insert_table("f[1]"));
+    assert!(!p.insert_table("a.b"));
+    assert!(!p.insert_table("a.b.c"));
+    assert!(!p.insert_table("f[3]"));
+    assert!(!p.insert_table(""));
+    assert_eq!("[a.b]\nc = 1\n[[f]]\n[x]\n\n[a]\n\n[[f]]\n", format!("{p}"));
+    assert_eq!(Some(&Children::Count(Cell::new(2))), p.get_children("f"));
+  }
+
+  #[test]
+  fn test_remove_key() {
+    let (mut p, _) = Parser::new().parse("[a.c]\nd = { e = [1, 2] }\n\n[a.z]\nb = 1\n\n# x\n[x]\n[[f]]\ng = 1\n[[f]]\ng = 2\n");
+    assert!(p.remove_key("a.c.d.e[0]"));
+    assert!(p.remove_key("f[0]"));
+    assert_eq!(Some(Value::int(2)), p.get_value("f[0].g"));
+    assert_eq!(Some(Value::Array(Rc::new(vec![Value::int(2)]))), p.get_value("a.c.d.e"));
+    assert!(p.remove_key("a"));
+    assert!(!p.remove_key("a.z.b"));
+    assert!(!p.remove_key(""));
+    assert_eq!("# x\n[x]\n[[f]]\ng = 2\n", format!("{p}"));
+    assert!(p.remove_key("f"));
+    assert_eq!("# x\n[x]\n", format!("{p}"));
+    let (mut p, _) = Parser::new().parse("a = 1\n[t]\nb = 2\n");
+    assert!(p.remove_key("t"));
+    assert_eq!("a = 1\n", format!("{p}"));
+  }
+
+  #[test]
+  fn test_rename_key() {
+    let (mut p, _) = Parser::new().parse("a = { b = 1, c = 2 }\n[[f]]\n[f.g]\nh = 1\n[[f]]\n[i.j]\n");
+    assert!(p.rename_key("a.b", "\"B\""));
+    assert!(!p.rename_key("a.B", "c"));
+    assert!(p.rename_key("f", "fruit"));
+    assert!(p.rename_key("fruit[0].g", "k"));
+    assert!(p.rename_key("i", "l"));
+    assert!(!p.rename_key("fruit[1]", "x"));
+    assert!(!p.rename_key("l.j", ""));
+    assert!(!p.rename_key("missing", "x"));
+    assert_eq!("a = { \"B\" = 1, c = 2 }\n[[fruit]]\n[fruit.k]\nh = 1\n[[fruit]]\n[l.j]\n", format!("{p}"));
+    assert_eq!(Some(Value::int(1)), p.get_value("fruit[0].k.h"));
+    assert_eq!(Some(Value::int(1)), p.get_value("a.B"));
+  }
+
+  #[test]
+  fn test_set_comment() {
+    let (mut p, _) = Parser::new().parse("a = 1\nb = 2 # old\n[t]\nc = { d = 1 }\n[[f]]\n");
+    assert!(p.set_comment("a", "first"));
+    assert!(p.set_comment("b", "new"));
+    assert!(p.set_comment("t", "table"));
+    assert!(p.set_comment("f[0]", "element"));
+    assert!(p.set_comment("t.c", ""));
+    assert!(!p.set_comment("t.c.d", "inline"));
+    assert!(!p.set_comment("f", "array"));
+    assert!(!p.set_comment("a", "two\nlines"));
+    assert_eq!("a = 1 # first\nb = 2 # new\n[t] # table\nc = { d = 1 }\n[[f]] # element\n", format!("{p}"));
+    assert!(p.set_comment("b", ""));
+    assert_eq!(Some(Value::int(2)), p.get_value("b"));
+    assert!(format!("{p}").starts_with("a = 1 # first\nb = 2\n"));
+  }
+}
@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+use crate::internals::ast::structs::{TOMLValue, Expression, NLExpression, WSSep, Table, TableType, WSKeySep, Array,
+                                     InlineTable, Comment, CommentNewLines, CommentOrNewLines};
+use crate::internals::parser::Parser;
+use crate::types::FormatOptions;
+
+impl<'a> Parser<'a> {
+  /// Rewrites the whitespace of the document the way `opts` describes: the indentation of key/value pairs and
+  /// comments, the spacing around `=`, inside table headers, arrays and inline tables, the blank lines between tables
+  /// and trailing whitespace. Keys, values and comments are kept as written, as are the spaces before a comment at
+  /// the end of a line, so that aligned comments stay aligned. The document ends with a newline.
+  pub fn format(self: &mut Parser<'a>, opts: &FormatOptions) {
+    let mut root = self.root.borrow_mut();
+    let newline = root.exprs.iter().map(|e| e.nl.clone()).find(|nl| !nl.is_empty()).unwrap_or_else(|| "\n".into());
+    let exprs = mem::take(&mut root.exprs);
+    let indents = expression_indents(&exprs, opts);
+    let header_blocks = header_blocks(&exprs);
+    let mut formatted: Vec<NLExpression<'a>> = vec![];
+    let mut blank_lines = 0;
+    for (i, mut nl_expr) in exprs.into_iter().enumerate() {
+      if is_blank(&nl_expr.expr) {
+        blank_lines += 1;
+        continue;
+      }
+      let blank_lines_before = if formatted.is_empty() {
+        0
+      } else if header_blocks[i] {
+        opts.blank_lines_between_tables
+      } else {
+        blank_lines.min(opts.max_blank_lines)
+      };
+      blank_lines = 0;
+      for _ in 0..blank_lines_before {
+        formatted.push(NLExpression::new_string(newline.to_string(), Expression::new(WSSep::new_str("", ""), None, None,
+          None)));
+      }
+      format_expression(&mut nl_expr.expr, &indents[i], &newline, opts);
+      nl_expr.nl = if formatted.is_empty() {
+        "".into()
+      } else if nl_expr.nl.is_empty() {
+        newline.clone()
+      } else {
+        nl_expr.nl
+      };
+      formatted.push(nl_expr);
+    }
+    if !formatted.is_empty() {
+      formatted.push(NLExpression::new_string(newline.to_string(), Expression::new(WSSep::new_str("", ""), None, None,
+        None)));
+    }
+    debug!("Formatted {} lines", formatted.len());
+    root.exprs = formatted;
+    drop(root);
+    self.rebuild_map();
+  }
+}
+
+/// Returns the indentation of each expression: key/value pairs after a table header are indented, and comments on
+/// their own line are indented like the key/value pair or table header that follows them
+fn expression_indents(exprs: &[NLExpression], opts: &FormatOptions) -> Vec<String> {
+  let mut indents = vec![String::new(); exprs.len()];
+  let mut in_table = false;
+  for (i, nl_expr) in exprs.iter().enumerate() {
+    in_table |= nl_expr.expr.table.is_some();
+    if nl_expr.expr.keyval.is_some() && in_table {
+      indents[i] = " ".repeat(opts.indent);
+    }
+  }
+  let mut next_indent = String::new();
+  for (i, nl_expr) in exprs.iter().enumerate().rev() {
+    if is_comment(&nl_expr.expr) {
+      indents[i].clone_from(&next_indent);
+    } else if !is_blank(&nl_expr.expr) {
+      next_indent.clone_from(&indents[i]);
+    }
+  }
+  indents
+}
+
+/// Returns whether each expression starts a table header along with the comments right above it, which is where the
+/// blank lines between tables go
+fn header_blocks(exprs: &[NLExpression]) -> Vec<bool> {
+  let mut starts = vec![false; exprs.len()];
+  for (i, nl_expr) in exprs.iter().enumerate() {
+    if nl_expr.expr.table.is_some() {
+      let mut start = i;
+      while start > 0 && is_comment(&exprs[start - 1].expr) {
+        start -= 1;
+      }
+      starts[start] = true;
+    }
+  }
+  starts
+}
+
+fn format_expression(expr: &mut Expression<'_>, indent: &str, newline: &str, opts: &FormatOptions) {
+  if expr.invalid.is_some() {
+    return;
+  }
+  expr.ws.ws1 = indent.to_string().into();
+  if let Some(ref mut keyval) = expr.keyval {
+    keyval.keyval_sep = eq_sep(opts);
+    format_value(&keyval.val, indent, newline, opts);
+  }
+  let header = match expr.table {
+    Some(ref tt) => match **tt {
+      TableType::Standard(ref t) => Some(TableType::Standard(strip_header(t))),
+      TableType::Array(ref t) => Some(TableType::Array(strip_header(t))),
+    },
+    None => None,
+  };
+  if let Some(header) = header {
+    expr.table = Some(Rc::new(header));
+  }
+  match expr.comment {
+    Some(ref mut comment) => {
+      comment.text = comment.text.trim_end().to_string().into();
+      if expr.ws.ws2.is_empty() {
+        expr.ws.ws2 = " ".into();
+      }
+    },
+    None => expr.ws.ws2 = "".into(),
+  }
+}
+
+/// Returns a copy of the header `t` without whitespace around its key segments.
+fn strip_header<'a>(t: &Table<'a>) -> Table<'a> {
+  Table{keys: t.keys.iter().map(|k| WSKeySep{ws: WSSep::new_str("", ""), key: k.key.clone()}).collect()}
+}
+
+fn eq_sep<'a>(opts: &FormatOptions) -> WSSep<'a> {
+  if opts.space_around_eq {
+    WSSep::new_str(" ", " ")
+  } else {
+    WSSep::new_str("", "")
+  }
+}
+
+/// Formats the arrays and inline tables in a value that's on a line indented by indent
+fn format_value(val: &RefCell<TOMLValue>, indent: &str, newline: &str, opts: &FormatOptions) {
+  match *val.borrow() {
+    TOMLValue::Array(ref arr) => format_array(&mut arr.borrow_mut(), indent, newline, opts),
+    TOMLValue::InlineTable(ref it) => format_inline_table(&mut it.borrow_mut(), indent, newline, opts),
+    _ => (),
+  }
+}
+
+/// An array stays on one line unless it has comments or already spans several lines, in which case every element goes
+/// on its own line followed by a comma
+fn format_array(arr: &mut Array<'_>, indent: &str, newline: &str, opts: &FormatOptions) {
+  let multi_line = arr.comment_nls1.iter().chain(arr.comment_nls2.iter())
+    .chain(arr.values.iter().flat_map(|v| v.comment_nls.iter()))
+    .any(|c| match *c {
+      CommentOrNewLines::Comment(_) => true,
+      CommentOrNewLines::NewLines(ref n) => n.contains('\n'),
+    });
+  if !multi_line {
+    let pad = if opts.array_padding && !arr.values.is_empty() { " " } else { "" };
+    arr.comment_nls1 = vec![CommentOrNewLines::NewLines(pad.into())];
+    arr.comment_nls2 = vec![CommentOrNewLines::NewLines(pad.into())];
+    let last = arr.values.len().saturating_sub(1);
+    for (i, value) in arr.values.iter_mut().enumerate() {
+      value.array_sep = if i < last { Some(WSSep::new_str("", " ")) } else { None };
+      value.comment_nls = vec![];
+      format_value(&value.val, indent, newline, opts);
+    }
+    return;
+  }
+  let inner = format!("{}{}", indent, " ".repeat(opts.array_indent));
+  let mut comment_nls2 = mem::take(&mut arr.comment_nls2);
+  if arr.values.is_empty() {
+    let mut gap = mem::take(&mut arr.comment_nls1);
+    gap.append(&mut comment_nls2);
+    arr.comment_nls1 = format_gap(gap, &inner, indent, newline);
+    return;
+  }
+  arr.comment_nls1 = format_gap(mem::take(&mut arr.comment_nls1), &inner, &inner, newline);
+  let last = arr.values.len() - 1;
+  for (i, value) in arr.values.iter_mut().enumerate() {
+    value.array_sep = Some(WSSep::new_str("", ""));
+    let mut gap = mem::take(&mut value.comment_nls);
+    if i == last {
+      gap.append(&mut comment_nls2);
+    }
+    value.comment_nls = format_gap(gap, &inner, if i == last { indent } else { &inner }, newline);
+    format_value(&value.val, &inner, newline, opts);
+  }
+}
+
+/// Lays out the comments between two elements of a multi-line array: a comment that was on the same line as the
+/// element before it stays there, every other comment goes on its own line indented by `comment_indent`, and the gap
+/// ends with a new line indented by `end_indent`
+fn format_gap<'a>(gap: Vec<CommentOrNewLines<'a>>, comment_indent: &str, end_indent: &str, newline: &str)
+  -> Vec<CommentOrNewLines<'a>> {
+  let mut formatted = vec![];
+  let mut on_own_line = false;
+  for comment_nl in gap {
+    match comment_nl {
+      CommentOrNewLines::NewLines(ref n) => on_own_line |= n.contains('\n'),
+      CommentOrNewLines::Comment(c) => {
+        on_own_line |= c.pre_ws_nl.contains('\n');
+        let pre_ws_nl = if on_own_line { format!("{newline}{comment_indent}") } else { " ".to_string() };
+        formatted.push(CommentOrNewLines::Comment(CommentNewLines::new_string(pre_ws_nl,
+          Comment::new_string(c.comment.text.trim_end().to_string()), String::new())));
+        on_own_line = true;
+      },
+    }
+  }
+  formatted.push(CommentOrNewLines::NewLines(format!("{newline}{end_indent}").into()));
+  formatted
+}
+
+/// Inline tables are always on one line; one with comments is left alone
+fn format_inline_table(it: &mut InlineTable<'_>, indent: &str, newline: &str, opts: &FormatOptions) {
+  let has_comments = it.keyvals.iter().flat_map(|kv| kv.comment_nls.iter())
+    .any(|c| matches!(*c, CommentOrNewLines::Comment(_)));
+  if has_comments {
+    return;
+  }
+  let pad: Cow<str> = if opts.inline_table_padding && !it.keyvals.is_empty() { " ".into() } else { "".into() };
+  it.ws = WSSep{ws1: pad.clone(), ws2: pad};
+  let last = it.keyvals.len().saturating_sub(1);
+  for (i, kv) in it.keyvals.iter_mut().enumerate() {
+    kv.keyval.keyval_sep = eq_sep(opts);
+    kv.kv_sep = if i < last { Some(WSSep::new_str("", " ")) } else { None };
+    kv.comment_nls = vec![];
+    format_value(&kv.keyval.val, indent, newline, opts);
+  }
+}
+
+fn is_blank(expr: &Expression) -> bool {
+  expr.keyval.is_none() && expr.table.is_none() && expr.comment.is_none() && expr.invalid.is_none()
+}
+
+fn is_comment(expr: &Expression) -> bool {
+  expr.keyval.is_none() && expr.table.is_none() && expr.comment.is_some()
+}
+
+#[cfg(test)]
+mod test {
+  use crate::internals::parser::Parser;
+  use crate::types::{FormatOptions, Value};
+
+  fn format(doc: &str, opts: &FormatOptions) -> String {
+    let (mut p, _) = Parser::new().parse(doc);
+    p.format(opts);
+    format!("{p}")
+  }
+
+  #[test]
+  fn test_format() {
+    let opts = FormatOptions::default();
+    assert_eq!("a = 1    # one\nb = [1, 2]\nc = { x = 1, y = [] }\n\n# tables\n[t.u]\nd = 'x'\n\n[[v]]\n",
+      format("\n\na=1    # one   \nb = [ 1 ,2, ]  \nc={x=1,y=[  ]}\n\n\n# tables\n[ t . u ]\n  d   = 'x'\n[[ v ]]\n\n\n",
+        &opts));
+    assert_eq!("a = 1\n\nb = 2\n", format("a = 1\n\n\n\nb = 2", &opts));
+    assert_eq!("", format("\n\n", &opts));
+    assert_eq!("a = 1\r\n\r\n[t]\r\nb = 2\r\n", format("a = 1\r\n[t]\r\nb = 2\r\n", &opts));
+  }
+
+  #[test]
+  fn test_format_multi_line_array() {
+    let opts = FormatOptions{indent: 2, ..FormatOptions::default()};
+    assert_eq!("[t]\n  # ports\n  a = [\n    1, # one\n    # two\n    2,\n    [\n      3,\n    ],\n  ]\n",
+      format("[t]\n# ports\na = [ 1,   # one   \n# two\n2,[\n3]\n]\n", &opts));
+    assert_eq!("b = [ # empty\n  # really\n]\n", format("b = [ # empty\n\n   # really\n   ]\n", &opts));
+  }
+
+  #[test]
+  fn test_format_options() {
+    let opts = FormatOptions{indent: 4, space_around_eq: false, array_padding: true, inline_table_padding: false,
+      blank_lines_between_tables: 2, max_blank_lines: 0, ..FormatOptions::default()};
+    assert_eq!("a=[ 1, 2 ]\nb={x=1}\n\n\n[t]\n    c=[]\n    d=2\n",
+      format("a = [1,2]\n\nb = { x = 1 }\n[t]\nc = []\n\n\nd = 2\n", &opts));
+  }
+
+  #[test]
+  fn test_format_keeps_values() {
+    let doc = "[a]\nb = \"\"\"\n  x  \n\"\"\"\nc = 1979-05-27T07:32:00Z\n[[d]]\ne = { f = [1, { g = 'h' }] }\n";
+    let (mut p, _) = Parser::new().parse(doc);
+    p.format(&FormatOptions{indent: 2, ..FormatOptions::default()});
+    assert_eq!("[a]\n  b = \"\"\"\n  x  \n\"\"\"\n  c = 1979-05-27T07:32:00Z\n\n[[d]]\n  e = { f = [1, { g = 'h' }] }\n",
+      format!("{p}"));
+    assert_eq!(Some(Value::int(1)), p.get_value("d[0].e.f[0]"));
+    let formatted = format!("{p}");
+    assert_eq!(formatted, format(&formatted, &FormatOptions{indent: 2, ..FormatOptions::default()}));
+  }
+}
@@ -0,0 +1,229 @@
+use crate::internals::parser::Parser;
+use crate::internals::compare::unescape;
+use crate::internals::primitives::is_keychar;
+use crate::internals::walk::{join_key, map_key};
+use crate::types::{Children, KeyPath, KeySegment, TOMLError};
+
+/// Parses a key path like `a."b.c".'d'[1]` into its segments, removing quotes and replacing escape sequences. An
+/// empty path is the root table, and a path can start with indices, like `[0].a`.
+pub fn parse_key_path(path: &str) -> Result<Vec<KeySegment>, TOMLError> {
+  let mut segments = vec![];
+  let mut rest = path.trim_start_matches(is_ws);
+  if rest.is_empty() {
+    return Ok(segments);
+  }
+  let fail = |rest: &str, msg: &str| key_path_error(path, rest, msg);
+  // A path can start with indices, which is how a path built with a leading index is displayed
+  rest = parse_indices(path, rest, &mut segments)?;
+  if !segments.is_empty() {
+    if rest.is_empty() {
+      return Ok(segments);
+    }
+    if !rest.starts_with('.') {
+      return Err(fail(rest, "expected \".\" or \"[\""));
+    }
+    rest = rest[1..].trim_start_matches(is_ws);
+  }
+  loop {
+    let (name, len) = if rest.starts_with('"') {
+      let Some(end) = basic_key_end(rest) else {
+        return Err(fail(rest, "unterminated basic-quoted key"));
+      };
+      match unescape(&rest[1..end]) {
+        Some(name) => (name, end + 1),
+        None => return Err(fail(rest, "invalid escape sequence in basic-quoted key")),
+      }
+    } else if rest.starts_with('\'') {
+      let inner = &rest[1..];
+      match inner.find(['\'', '\n']) {
+        Some(end) if inner[end..].starts_with('\'') => (inner[..end].to_string(), end + 2),
+        _ => return Err(fail(rest, "unterminated literal-quoted key")),
+      }
+    } else {
+      let len = rest.find(|c| !is_keychar(c)).unwrap_or(rest.len());
+      if len == 0 {
+        return Err(fail(rest, "expected a key"));
+      }
+      (rest[..len].to_string(), len)
+    };
+    if name.is_empty() {
+      return Err(fail(rest, "empty key"));
+    }
+    segments.push(KeySegment::Key(name));
+    rest = parse_indices(path, rest[len..].trim_start_matches(is_ws), &mut segments)?;
+    if rest.is_empty() {
+      return Ok(segments);
+    }
+    if !rest.starts_with('.') {
+      return Err(fail(rest, "expected \".\" or \"[\""));
+    }
+    rest = rest[1..].trim_start_matches(is_ws);
+  }
+}
+
+/// Parses any `[n]` array indices at the start of `rest`, a suffix of `path`, into `segments` and returns the rest.
+fn parse_indices<'p>(path: &str, mut rest: &'p str, segments: &mut Vec<KeySegment>) -> Result<&'p str, TOMLError> {
+  while rest.starts_with('[') {
+    let index = match rest.find(']') {
+      Some(end) => match rest[1..end].trim_matches(is_ws).parse() {
+        Ok(i) => (i, end + 1),
+        Err(_) => return Err(key_path_error(path, rest, "invalid array index")),
+      },
+      None => return Err(key_path_error(path, rest, "unclosed \"[\"")),
+    };
+    segments.push(KeySegment::Index(index.0));
+    rest = rest[index.1..].trim_start_matches(is_ws);
+  }
+  Ok(rest)
+}
+
+fn key_path_error(path: &str, rest: &str, msg: &str) -> TOMLError {
+  TOMLError::new(format!("Invalid key path \"{}\": {} at position {}", path, msg, path.len() - rest.len()))
+}
+
+fn is_ws(c: char) -> bool {
+  c == ' ' || c == '\t'
+}
+
+/// Returns the index of the closing quote of the basic-quoted key at the start of `s`.
+fn basic_key_end(s: &str) -> Option<usize> {
+  let mut escaped = false;
+  for (i, c) in s.char_indices().skip(1) {
+    match c {
+      '"' if !escaped => return Some(i),
+      '\n' => return None,
+      _ => (),
+    }
+    escaped = c == '\\' && !escaped;
+  }
+  None
+}
+
+/// Returns the name of a key as it's written in a document, with any quotes removed and escape sequences replaced.
+pub fn key_name(key: &str) -> String {
+  if key.len() >= 2 && key.starts_with('"') && key.ends_with('"') {
+    let inner = &key[1..key.len() - 1];
+    unescape(inner).unwrap_or_else(|| inner.to_string())
+  } else {
+    key.to_string()
+  }
+}
+
+impl<'a> Parser<'a> {
+  /// Returns the key in the map for `path`, spelled the way the document spells each segment, or `None` if it
+  /// doesn't exist.
+  pub fn resolve_key_path(self: &Parser<'a>, path: &KeyPath) -> Option<String> {
+    let mut key = String::new();
+    for segment in path.segments() {
+      let hv = self.map.get(&map_key(&key))?;
+      match (segment, &hv.subkeys) {
+        (KeySegment::Key(name), Children::Keys(children)) => {
+          let child = children.borrow().iter().find(|c| key_name(c) == *name)?.clone();
+          key = join_key(&key, &child);
+        },
+        (&KeySegment::Index(i), Children::Count(c)) if i < c.get() => {
+          key = Children::combine_keys_index(key.as_str(), i);
+        },
+        _ => return None,
+      }
+    }
+    Some(key)
+  }
+
+  /// Returns the key in the map for `key`: `key` itself if the map has it, otherwise `key` parsed as a `KeyPath` and
+  /// resolved, so that any valid way of writing the key finds it.
+  pub fn resolve_key(self: &Parser<'a>, key: &str) -> Option<String> {
+    if key.is_empty() || self.map.contains_key(key) {
+      return Some(key.to_string());
+    }
+    let path = KeyPath::parse(key).ok()?;
+    self.resolve_key_path(&path)
+  }
+
+  /// Like `resolve_key`, but for a key that may not exist yet: the longest part of `path` that exists is spelled the
+  /// way the document spells it, and the rest the way `KeyPath` writes it.
+  pub fn resolve_new_key_path(self: &Parser<'a>, path: &KeyPath) -> String {
+    let segments = path.segments();
+    for n in (1..=segments.len()).rev() {
+      if let Some(key) = self.resolve_key_path(&KeyPath::from(segments[..n].to_vec())) {
+        let rest = KeyPath::from(segments[n..].to_vec()).to_string();
+        return match rest.chars().next() {
+          None => key,
+          Some('[') => format!("{key}{rest}"),
+          Some(_) => join_key(&key, &rest),
+        };
+      }
+    }
+    path.to_string()
+  }
+
+  /// Like `resolve_key`, but returns an error describing what's wrong with `key` if it isn't a valid key path.
+  pub fn try_resolve_key(self: &Parser<'a>, key: &str) -> Result<Option<String>, TOMLError> {
+    if key.is_empty() || self.map.contains_key(key) {
+      return Ok(Some(key.to_string()));
+    }
+    let path = KeyPath::parse(key)?;
+    Ok(self.resolve_key_path(&path))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::internals::parser::Parser;
+  use crate::internals::keypath::parse_key_path;
+  use crate::types::{KeyPath, KeySegment};
+
+  #[test]
+  fn test_parse_key_path() {
+    let key = |k: &str| KeySegment::Key(k.to_string());
+    assert_eq!(vec![key("a"), key("b.c"), key("d e"), KeySegment::Index(1), KeySegment::Index(0)],
+      parse_key_path("a.\"b.c\" . 'd e'[1] [ 0 ]").unwrap());
+    assert_eq!(vec![key("tab\t\"quote\""), key("C:\\dir")], parse_key_path(r#""tab\t\"quote\"".'C:\dir'"#).unwrap());
+    assert_eq!(Vec::<KeySegment>::new(), parse_key_path("").unwrap());
+    for bad in &["a.", ".a", "a..b", "a b", "\"a", "'a", "\"\\q\"", "a[", "a[x]", "a[-1]", "\"\"", "a.[0]", "[0]a",
+      "[0].", "[0"] {
+      assert!(parse_key_path(bad).is_err(), "key path {}", bad);
+    }
+    assert_eq!("Invalid key path \"a.b c\": expected \".\" or \"[\" at position 4",
+      format!("{}", parse_key_path("a.b c").unwrap_err()));
+  }
+
+  #[test]
+  fn test_resolve_key() {
+    let (p, _) = Parser::new().parse(r#""A Key" = 1
+[a."b.c"]
+d = [1, 2]
+[[e]]
+f = { "g h" = true }
+"#);
+    assert_eq!(Some("\"A Key\"".to_string()), p.resolve_key("'A Key'"));
+    assert_eq!(Some("\"A Key\"".to_string()), p.resolve_key("\"A Key\""));
+    assert_eq!(Some("a.\"b.c\".d[1]".to_string()), p.resolve_key("a.'b.c'.d[1]"));
+    assert_eq!(Some("e[0].f.\"g h\"".to_string()), p.resolve_key("\"e\" [0].f.'g h'"));
+    assert_eq!(None, p.resolve_key("a.'b.c'.d[2]"));
+    assert_eq!(None, p.resolve_key("A Key"));
+    assert!(p.try_resolve_key("A Key").is_err());
+    assert_eq!(None, p.try_resolve_key("missing").unwrap());
+    let new_key = |k: &str| p.resolve_new_key_path(&KeyPath::parse(k).unwrap());
+    assert_eq!("a.\"b.c\".\"x y\".z", new_key("a.'b.c'.'x y'.z"));
+    assert_eq!("e[1]", new_key("e[1]"));
+    assert_eq!("\"A Key\"", new_key("'A Key'"));
+    assert_eq!("new.key", new_key("new.key"));
+  }
+
+  #[test]
+  fn test_key_path_display() {
+    for path in &["a", "a.\"b.c\"[1][0].d", "\"A Key\"", "\"tab\\t\\\"quote\\\"\"", "\"\\u0001\"", "", "[0]",
+      "[1][2].a"] {
+      assert_eq!(*path, KeyPath::parse(path).unwrap().to_string());
+    }
+    assert_eq!("a.\"b c\".d[2]", KeyPath::parse("'a' . 'b c'.\"d\" [2]").unwrap().to_string());
+    let path: KeyPath = "x.'y'[3]".parse().unwrap();
+    assert_eq!(KeyPath::new().key("x").key("y").index(3), path);
+    assert_eq!(path, KeyPath::from(vec![KeySegment::Key("x".to_string()), KeySegment::Key("y".to_string()),
+      KeySegment::Index(3)]));
+    assert!("x.".parse::<KeyPath>().is_err());
+    let path = KeyPath::new().index(0).key("a");
+    assert_eq!(path, path.to_string().parse().unwrap());
+  }
+}
@@ -3,7 +3,7 @@ macro_rules! to_val(
     match $tval {
       &TOMLValue::Integer(ref v) => Value::Integer(v.clone()),
       &TOMLValue::Float(ref v) => Value::Float(v.clone()),
-      &TOMLValue::Boolean(v) => Value::Boolean(v),
+      &TOMLValue::Boolean(v, _) => Value::Boolean(v),
       &TOMLValue::DateTime(ref v) => Value::DateTime(v.clone()),
       &TOMLValue::Array(ref arr) => Parser::sanitize_array(arr.clone()),
       &TOMLValue::String(ref s, t) => Value::String(s.clone(), t.clone()),
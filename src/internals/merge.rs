@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use crate::internals::parser::Parser;
+use crate::internals::build::split_key;
+use crate::internals::compare::{normalize_key, values_eq};
+use crate::types::{Value, Change, Conflict, DocEntry};
+
+impl<'a> Parser<'a> {
+  /// Three-way merge: applies every change between `base` and `theirs` to this document, unless this document changed
+  /// the same key differently, in which case the key is kept as it is and returned as a `Conflict`.
+  pub fn merge(self: &mut Parser<'a>, base: &Parser<'a>, theirs: &Parser<'a>) -> Vec<Conflict<'a>> {
+    let ours: HashMap<String, (String, Value<'a>)> = self.leaves().into_iter()
+      .map(|(norm_key, key, val)| (norm_key, (key, val))).collect();
+    let mut conflicts = vec![];
+    for change in base.diff(theirs) {
+      let (key, base_val, their_val) = match change {
+        Change::Added(key, val) => (key, None, Some(val)),
+        Change::Removed(key, val) => (key, Some(val), None),
+        Change::Changed(key, old, new) => (key, Some(old), Some(new)),
+      };
+      let our_entry = ours.get(&normalize_key(&key));
+      let our_val = our_entry.map(|(_, val)| val.clone());
+      if opt_values_eq(our_val.as_ref(), their_val.as_ref()) {
+        continue;
+      }
+      if opt_values_eq(our_val.as_ref(), base_val.as_ref()) {
+        let applied = match (our_entry, &their_val) {
+          (Some((our_key, _)), Some(val)) => self.set_value(our_key.clone(), val.clone()),
+          (Some((our_key, _)), &None) => self.remove_keyval(our_key),
+          (None, Some(val)) => self.insert_merged_keyval(&key, val),
+          (None, &None) => true,
+        };
+        if applied {
+          debug!("Merged key {key}");
+          continue;
+        }
+      }
+      debug!("Conflict on key {key}");
+      conflicts.push(Conflict{key, base: base_val, ours: our_val, theirs: their_val});
+    }
+    conflicts
+  }
+
+  /// Adds the key `key` from another document, finding its table by meaning so it's added to the table as it's
+  /// written in this document.
+  fn insert_merged_keyval(self: &mut Parser<'a>, key: &str, val: &Value<'a>) -> bool {
+    let mut segments = split_key(key);
+    let Some(last) = segments.pop() else {
+      return false;
+    };
+    let mut table_key = segments.join(".");
+    if !table_key.is_empty() {
+      let norm_table_key = normalize_key(&table_key);
+      let found = self.entries().into_iter().find(|(k, entry)| {
+        let is_table = match *entry {
+          DocEntry::Table | DocEntry::ArrayTable | DocEntry::Value(Value::InlineTable(_)) => true,
+          DocEntry::ArrayOfTables | DocEntry::Value(_) => false,
+        };
+        is_table && normalize_key(k) == norm_table_key
+      });
+      if let Some((k, _)) = found {
+        table_key = k;
+      }
+    }
+    self.insert_keyval(&table_key, &last, val)
+  }
+}
+
+fn opt_values_eq(left: Option<&Value>, right: Option<&Value>) -> bool {
+  match (left, right) {
+    (Some(l), Some(r)) => values_eq(l, r),
+    (None, None) => true,
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::internals::parser::Parser;
+  use crate::types::{Value, Conflict};
+
+  #[test]
+  fn test_merge() {
+    let (base, _) = Parser::new().parse(r#"name = "app"
+port = 80
+debug = false
+[db]
+host = "localhost"
+pool = { min = 1, max = 5 }
+"#);
+    let (mut ours, _) = Parser::new().parse(r#"# customized
+name = "my app"
+port = 80 # keep this
+debug = false
+
+[db]
+host = "db.local"
+pool = { min = 1, max = 5 }
+"#);
+    let (theirs, _) = Parser::new().parse(r#"name = "app"
+port = 8080
+[db]
+host = "127.0.0.1"
+pool = { min = 1, max = 10, idle = 2 }
+[cache]
+size = 64
+"#);
+    let conflicts = ours.merge(&base, &theirs);
+    assert_eq!(vec![Conflict{key: "db.host".to_string(), base: Some(Value::basic_string("localhost").unwrap()),
+      ours: Some(Value::basic_string("db.local").unwrap()), theirs: Some(Value::basic_string("127.0.0.1").unwrap())}],
+      conflicts);
+    assert_eq!(r#"# customized
+name = "my app"
+port = 8080 # keep this
+
+[db]
+host = "db.local"
+pool = { min = 1, max = 10, idle = 2 }
+
+[cache]
+size = 64
+"#, format!("{ours}"));
+  }
+
+  #[test]
+  fn test_merge_array_of_tables() {
+    let (base, _) = Parser::new().parse("[[server]]\nname = \"a\"\n");
+    let (mut ours, _) = Parser::new().parse("[[server]]\nname = \"a\"\nweight = 2\n");
+    let (theirs, _) = Parser::new().parse("[[server]]\nname = \"a\"\n[[server]]\nname = \"b\"\n");
+    assert!(ours.merge(&base, &theirs).is_empty());
+    assert_eq!("[[server]]\nname = \"a\"\nweight = 2\n\n[[server]]\nname = \"b\"\n", format!("{ours}"));
+  }
+
+  #[test]
+  fn test_merge_conflicting_structure() {
+    let (base, _) = Parser::new().parse("a = 1\n");
+    let (mut ours, _) = Parser::new().parse("");
+    let (theirs, _) = Parser::new().parse("a = 2\n");
+    let conflicts = ours.merge(&base, &theirs);
+    assert_eq!(vec![Conflict{key: "a".to_string(), base: Some(Value::int(1)), ours: None,
+      theirs: Some(Value::int(2))}], conflicts);
+    let (base, _) = Parser::new().parse("a = 1\n");
+    let (mut ours, _) = Parser::new().parse("a = 2\n");
+    let (theirs, _) = Parser::new().parse("[a]\nb = 1\n");
+    let keys: Vec<String> = ours.merge(&base, &theirs).into_iter().map(|c| c.key).collect();
+    assert_eq!(vec!["a", "a.b"], keys);
+    assert_eq!("a = 2\n", format!("{ours}"));
+  }
+}
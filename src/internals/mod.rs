@@ -1,8 +1,16 @@
 #[macro_use]
 mod macros;
-mod ast;
+pub mod ast;
 mod toml;
 mod util;
 mod objects;
 pub mod parser;
-mod primitives;
+pub mod primitives;
+pub mod walk;
+pub mod build;
+pub mod compare;
+pub mod edit;
+pub mod merge;
+pub mod query;
+pub mod keypath;
+pub mod format;
@@ -14,7 +14,7 @@ fn map_val_to_array_type(val: &TOMLValue) -> ArrayType {
   match *val {
     TOMLValue::Integer(_)        => ArrayType::Integer,
     TOMLValue::Float(_)          => ArrayType::Float,
-    TOMLValue::Boolean(_)        => ArrayType::Boolean,
+    TOMLValue::Boolean(..)       => ArrayType::Boolean,
     TOMLValue::DateTime(_)       => ArrayType::DateTime,
     TOMLValue::Array(_)          => ArrayType::Array,
     TOMLValue::String(_,_)       => ArrayType::String,
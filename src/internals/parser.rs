@@ -7,7 +7,7 @@ use std::rc::Rc;
 use std::borrow::Cow;
 use crate::internals::ast::structs::{HashValue, ArrayType, TOMLValue, Toml, TableType, Array, InlineTable, ArrayValue, WSSep,
                               TableKeyVal};
-use crate::types::{ParseError, ParseResult, Value, Children};
+use crate::types::{ParseError, ParseResult, ParseMode, Value, Children, ValueKind, ValueRef};
 use crate::internals::primitives::Key;
 use nom::IResult;
 
@@ -16,6 +16,7 @@ pub struct Parser<'a> {
   pub map: HashMap<String, HashValue<'a>>,
   pub errors: Rc<RefCell<Vec<ParseError<'a>>>>,
   pub leftover: &'a str,
+  pub document: &'a str,
   pub line_count: Cell<usize>,
   pub last_array_tables: RefCell<Vec<Rc<TableType<'a>>>>,
   pub last_array_tables_index: RefCell<Vec<usize>>,
@@ -25,6 +26,7 @@ pub struct Parser<'a> {
   pub array_error: Cell<bool>,
   pub mixed_array: Cell<bool>,
   pub failure: Cell<bool>,
+  pub mode: ParseMode,
 }
 
 // TODO change this to return a parser result
@@ -33,13 +35,22 @@ impl<'a> Parser<'a> {
     let mut map = HashMap::new();
     map.insert("$Root$".to_string(), HashValue::none_keys());
     Parser{ root: RefCell::new(Toml{ exprs: vec![] }), map,
-            errors: Rc::new(RefCell::new(vec![])), leftover: "",
+            errors: Rc::new(RefCell::new(vec![])), leftover: "", document: "",
             line_count: Cell::new(1), last_array_tables: RefCell::new(vec![]),
             last_array_tables_index: RefCell::new(vec![]),
             last_table: None, last_array_type: RefCell::new(vec![]),
             keychain: RefCell::new(vec![]),
             array_error: Cell::new(false), mixed_array: Cell::new(false),
-            failure: Cell::new(false)}
+            failure: Cell::new(false), mode: ParseMode::Recovering}
+  }
+
+  pub fn with_mode(mode: ParseMode) -> Parser<'a> {
+    Parser{mode, ..Parser::new()}
+  }
+
+  /// Returns false once a strict parser has found an error, so that parsing stops there.
+  pub fn recovering(self: &Parser<'a>) -> bool {
+    self.mode == ParseMode::Recovering || self.errors.borrow().is_empty()
   }
 
   pub fn parse(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, ParseResult<'a>) {
@@ -55,6 +66,16 @@ impl<'a> Parser<'a> {
       },
       _ => return (self, ParseResult::Failure(line_count, 0)),
     };
+    if self.mode == ParseMode::Strict {
+      let first_error = self.errors.borrow().first().map(ParseError::position);
+      if let Some((line, col)) = first_error {
+        debug!("Strict parse failed at line {line}");
+        return (self, ParseResult::Failure(line, col));
+      }
+      if !self.leftover.is_empty() {
+        return (self, ParseResult::Failure(line_count, 0));
+      }
+    }
     let len = self.errors.borrow().len();
     if !self.leftover.is_empty() {
       if len > 0 {
@@ -92,7 +113,7 @@ impl<'a> Parser<'a> {
   }
 
   pub fn get_value<S>(self: &Parser<'a>, key: S) -> Option<Value<'a>> where S: Into<String> {
-    let s_key = key.into();
+    let s_key = self.resolve_key(&key.into())?;
     if self.map.contains_key(&s_key) {
       let hashval = self.map.get(&s_key).unwrap();
       let clone = hashval.clone();
@@ -102,8 +123,36 @@ impl<'a> Parser<'a> {
     }
   }
 
+  pub fn get_value_ref<S>(self: &Parser<'a>, key: S) -> Option<ValueRef<'a>> where S: Into<String> {
+    let s_key = self.resolve_key(&key.into())?;
+    match self.map.get(&s_key)?.value {
+      Some(ref val) if *val.borrow() != TOMLValue::Table => Some(ValueRef::new(val.clone())),
+      _ => None,
+    }
+  }
+
+  pub fn get_kind<S>(self: &Parser<'a>, key: S) -> Option<ValueKind> where S: Into<String> {
+    let s_key = self.resolve_key(&key.into())?;
+    let hashval = if s_key.is_empty() { self.map.get("$Root$")? } else { self.map.get(&s_key)? };
+    Some(match hashval.value {
+      Some(ref val) => ValueRef::new(val.clone()).kind(),
+      None => match hashval.subkeys {
+        Children::Count(_) => ValueKind::ArrayOfTables,
+        Children::Keys(_) => ValueKind::Table,
+      },
+    })
+  }
+
+  pub fn get_line<S>(self: &Parser<'a>, key: S) -> Option<usize> where S: Into<String> {
+    let s_key = self.resolve_key(&key.into())?;
+    if s_key.is_empty() {
+      return None;
+    }
+    self.key_lines().get(&s_key).copied()
+  }
+
   pub fn get_children<S>(self: &Parser<'a>, key: S) -> Option<&Children> where S: Into<String> {
-    let s_key = key.into();
+    let s_key = self.resolve_key(&key.into())?;
     let k;
     if s_key.is_empty() {
       k = "$Root$".to_string();
@@ -119,7 +168,9 @@ impl<'a> Parser<'a> {
   }
 
   pub fn set_value<S>(self: &mut Parser<'a>, key: S, val: Value<'a>) -> bool where S: Into<String> {
-    let s_key = key.into();
+    let Some(s_key) = self.resolve_key(&key.into()) else {
+      return false;
+    };
     {
       let tvalue = match self.map.entry(s_key.clone()) {
         Entry::Occupied(entry) => entry.into_mut(),
@@ -146,15 +197,7 @@ impl<'a> Parser<'a> {
       return false;
     }
     let new_value = new_value_opt.unwrap();
-    let new_value_clone = match new_value {
-      TOMLValue::Array(ref rc_rc) => {
-        TOMLValue::Array(rc_rc.clone())
-      },
-      TOMLValue::InlineTable(ref rc_rc) => {
-        TOMLValue::InlineTable(rc_rc.clone())
-      },
-      unknown => panic!("In Parser.set_value, new_value should only be an Array or InlineTable. Instead it's a {:?}", unknown),
-    };
+    let new_value_clone = new_value.clone();
     if self.map.contains_key(&s_key) {
       let existing_value = match self.map.entry(s_key.clone()) {
         Entry::Occupied(entry) => entry.into_mut(),
@@ -172,7 +215,7 @@ impl<'a> Parser<'a> {
     true
   }
 
-  fn convert_vector(tval: &Value<'a>) -> Option<TOMLValue<'a>> {
+  pub fn convert_vector(tval: &Value<'a>) -> Option<TOMLValue<'a>> {
     if !tval.validate() {
       return None;
     }
@@ -226,7 +269,7 @@ impl<'a> Parser<'a> {
         }
         None
       },
-      Value::Boolean(b) => return Some(TOMLValue::Boolean(b)),
+      Value::Boolean(b) => Some(TOMLValue::Boolean(b, None)),
       Value::DateTime(ref dt) => {
         if tval.validate() {
           return Some(TOMLValue::DateTime(dt.clone()))
@@ -321,7 +364,8 @@ impl<'a> Parser<'a> {
         }
       },
       _ => {
-        self.map.entry(key).or_insert_with(|| HashValue::new_count(val.clone()));
+        // An Array or InlineTable replaced with a scalar keeps its entry, but loses its children
+        self.map.entry(key).or_insert_with(|| HashValue::new_count(val.clone())).subkeys = Children::Count(Cell::new(0));
       },
     }
   }
@@ -375,7 +419,7 @@ impl<'a> Parser<'a> {
       },
       (_, &Value::Integer(ref v))   => TOMLValue::Integer(v.clone()),
       (_, &Value::Float(ref v))     => TOMLValue::Float(v.clone()),
-      (_, &Value::Boolean(v))       => TOMLValue::Boolean(v),
+      (_, &Value::Boolean(v))       => TOMLValue::Boolean(v, None),
       (_, &Value::DateTime(ref v))  => TOMLValue::DateTime(v.clone()),
       (_, &Value::String(ref s, t)) => TOMLValue::String(s.clone(), t),
       (v, tv)                             => panic!("Check for the same structure should have eliminated the possibility of replacing {} with {}", v, tv),
@@ -413,7 +457,7 @@ mod test {
   use std::cell::{Cell, RefCell};
   use std::rc::Rc;
   use crate::internals::parser::Parser;
-  use crate::types::{Value, Children, StrType, Date, Time, DateTime};
+  use crate::types::{Value, Children, StrType, Date, Time, DateTime, ValueKind, ParseMode, ParseResult, ParseError};
   struct TT;
   impl TT {
     fn get<'a>() -> &'a str {
@@ -595,6 +639,145 @@ properties = { color = "red", "plate number" = "ABC 345",
       Some(&Children::Keys(RefCell::new(vec!["Name".to_string(), "Age".to_string()]))));
   }
 
+  #[test]
+  fn test_strict_mode() {
+    let doc = "a = 1\n[t]\nb = [1, 'two']\nc = 3\n";
+    let (p, result) = Parser::new().parse(doc);
+    assert!(matches!(result, ParseResult::FullError(_)));
+    assert_eq!(Some(Value::int(3)), p.get_value("t.c"));
+    let (p, result) = Parser::with_mode(ParseMode::Strict).parse(doc);
+    assert_eq!(ParseResult::Failure(3, 0), result);
+    assert_eq!(None, p.get_value("t.c"));
+    let (_, result) = Parser::with_mode(ParseMode::Strict).parse("a = 1\nb = \n");
+    assert_eq!(ParseResult::Failure(2, 1), result);
+    let (_, result) = Parser::with_mode(ParseMode::Strict).parse("a = 1\n[t]\n");
+    assert_eq!(ParseResult::Full, result);
+  }
+
+  #[test]
+  fn test_syntax_error_recovery() {
+    let doc = "a = 1\nb = \n[t]\nc = [1,\n  2, oops\nd = \"ok\" junk\r\n[[u]]\ne = true\n";
+    let (p, result) = Parser::new().parse(doc);
+    let errors = if let ParseResult::FullError(ref errors) = result {
+      errors.clone()
+    } else {
+      panic!("Expected the syntax errors to be reported with FullError, got {:?}", result);
+    };
+    assert_eq!(vec![
+      ParseError::InvalidSyntax(2, 1, "b = ".into()),
+      ParseError::InvalidSyntax(4, 1, "c = [1,".into()),
+      ParseError::InvalidSyntax(5, 3, "2, oops".into()),
+      ParseError::InvalidSyntax(6, 10, "junk".into()),
+    ], *errors.borrow());
+    assert_eq!(Some(Value::int(1)), p.get_value("a"));
+    assert_eq!(None, p.get_value("b"));
+    assert_eq!(None, p.get_value("t.c"));
+    assert_eq!(Some(Value::basic_string("ok").unwrap()), p.get_value("t.d"));
+    assert_eq!(Some(Value::Boolean(true)), p.get_value("u[0].e"));
+    assert_eq!(doc, format!("{p}"));
+  }
+
+  #[test]
+  fn test_value_errors() {
+    let doc = r#"big = 9_223_372_036_854_775_808
+small = -9223372036854775809
+zeros = 0123
+huge = 1e400
+pi = 3.14159265358979323846
+dot = 1.
+flag = True
+path = "C:\qemu"
+esc = "\uZZZZ"
+surrogate = "\uD800"
+far = "\U00110000"
+[t]
+ok = [1, 2.5, 1e-5, 0.1, "\u00e9", """a \
+  b""", false]
+"#;
+    let (p, result) = Parser::new().parse(doc);
+    let errors = if let ParseResult::FullError(ref errors) = result {
+      errors.clone()
+    } else {
+      panic!("Expected the value errors to be reported with FullError, got {:?}", result);
+    };
+    assert_eq!(vec![
+      ParseError::IntegerOverflow("big".into(), 1, 7, "9_223_372_036_854_775_808".into()),
+      ParseError::IntegerUnderflow("small".into(), 2, 9, "-9223372036854775809".into()),
+      ParseError::InvalidInteger("zeros".into(), 3, 9, "0123".into()),
+      ParseError::Infinity("huge".into(), 4, 8, "1e400".into()),
+      ParseError::LossOfPrecision("pi".into(), 5, 6, "3.14159265358979323846".into()),
+      ParseError::InvalidFloat("dot".into(), 6, 7, "1.".into()),
+      ParseError::InvalidBoolean("flag".into(), 7, 8, "True".into()),
+      ParseError::InvalidString("path".into(), 8, 8, "\"C:\\qemu\"".into(), StrType::Basic),
+      ParseError::InvalidString("esc".into(), 9, 7, "\"\\uZZZZ\"".into(), StrType::Basic),
+      ParseError::InvalidString("surrogate".into(), 10, 13, "\"\\uD800\"".into(), StrType::Basic),
+      ParseError::InvalidString("far".into(), 11, 7, "\"\\U00110000\"".into(), StrType::Basic),
+    ], *errors.borrow());
+    assert_eq!(Some(Value::Integer("9_223_372_036_854_775_808".into())), p.get_value("big"));
+    assert_eq!(Some(Value::Boolean(true)), p.get_value("flag"));
+    assert_eq!(Some(Value::String("C:\\qemu".into(), StrType::Basic)), p.get_value("path"));
+    assert_eq!(Some(Value::String("\\u00e9".into(), StrType::Basic)), p.get_value("t.ok[4]"));
+    assert_eq!(doc, format!("{p}"));
+  }
+
+  #[test]
+  fn test_get_line() {
+    let p = Parser::new();
+    let (p, _) = p.parse(TT::get());
+    assert_eq!(p.get_line(""), None);
+    assert_eq!(p.get_line("animal"), Some(1));
+    assert_eq!(p.get_line("car"), Some(14));
+    assert_eq!(p.get_line("car.owners"), Some(3));
+    assert_eq!(p.get_line("car.owners[1]"), Some(6));
+    assert_eq!(p.get_line("car.owners[0].Name"), Some(4));
+    assert_eq!(p.get_line("car.interior"), Some(10));
+    assert_eq!(p.get_line("car.drivers[4].banned"), Some(19));
+    assert_eq!(p.get_line("car.properties.accident_dates[1]"), Some(21));
+    assert_eq!(p.get_line("car.nope"), None);
+  }
+
+  #[test]
+  fn test_get_kind() {
+    let p = Parser::new();
+    let (p, _) = p.parse(TT::get());
+    assert_eq!(p.get_kind(""), Some(ValueKind::Table));
+    assert_eq!(p.get_kind("car"), Some(ValueKind::Table));
+    assert_eq!(p.get_kind("car.interior"), Some(ValueKind::Table));
+    assert_eq!(p.get_kind("car.owners"), Some(ValueKind::ArrayOfTables));
+    assert_eq!(p.get_kind("car.owners[1]"), Some(ValueKind::Table));
+    assert_eq!(p.get_kind("car.owners[0].Name"), Some(ValueKind::String(StrType::MLBasic)));
+    assert_eq!(p.get_kind("car.drivers"), Some(ValueKind::Array));
+    assert_eq!(p.get_kind("car.drivers[4]"), Some(ValueKind::InlineTable));
+    assert_eq!(p.get_kind("car.'ωλèèℓƨ'"), Some(ValueKind::Integer));
+    assert_eq!(p.get_kind("car.\"ƭôƥ ƨƥèèδ\""), Some(ValueKind::Float));
+    assert_eq!(p.get_kind("car.properties.accident_dates[2]"), Some(ValueKind::DateTime));
+    assert_eq!(p.get_kind("car.owners[2]"), None);
+  }
+
+  #[test]
+  fn test_get_value_ref() {
+    let p = Parser::new();
+    let (mut p, _) = p.parse(TT::get());
+    let drivers = p.get_value_ref("car.drivers").unwrap();
+    assert_eq!(ValueKind::Array, drivers.kind());
+    assert_eq!(p.get_value("car.drivers").unwrap(), drivers.to_value());
+    assert_eq!("\"Bob\"", drivers.get(0).unwrap().to_string());
+    let values: Vec<Value> = drivers.iter().map(|v| v.to_value()).collect();
+    assert_eq!(5, values.len());
+    assert_eq!(values[0], p.get_value("car.drivers[0]").unwrap());
+    let last = drivers.get(4).unwrap();
+    assert_eq!(ValueKind::InlineTable, last.kind());
+    for (key, val) in last.entries() {
+      assert_eq!(p.get_value(format!("car.drivers[4].{key}")).unwrap(), val.to_value());
+    }
+    assert!(drivers.get(5).is_none());
+    assert!(drivers.get_key("x").is_none());
+    assert!(p.get_value_ref("car").is_none());
+    assert!(p.get_value_ref("car.owners[0]").is_none());
+    assert!(p.set_value("car.drivers[0]", Value::int(7)));
+    assert_eq!(Value::int(7), drivers.get(0).unwrap().to_value());
+  }
+
   #[test]
   fn test_set_bare_key() {
     let p = Parser::new();
@@ -771,4 +954,14 @@ properties = { color = "red", "plate number" = "ABC 345",
     assert_eq!(p.get_value("database.servers.failover2.something"), None);
     assert_eq!(p.get_value("database.servers.failover2.nothing"), None);
   }
+
+  #[test]
+  fn test_replace_array_with_scalar() {
+    let p = Parser::new();
+    let (mut p, _) = p.parse(TT::get());
+    assert!(p.set_value("car.drivers", Value::int(3)));
+    assert_eq!(p.get_value("car.drivers"), Some(Value::int(3)));
+    assert_eq!(p.get_value("car.drivers[0]"), None);
+    assert_eq!(p.get_children("car.drivers"), Some(&Children::Count(Cell::new(0))));
+  }
 }
\ No newline at end of file
@@ -3,9 +3,12 @@ use std::rc::Rc;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
 use std::borrow::Cow;
+use std::num::IntErrorKind;
+use std::convert::TryFrom;
 use crate::internals::ast::structs::{KeyVal, WSSep, TOMLValue, ErrorCode, HashValue, TableType, Table, get_last_keys};
 use crate::types::{Date, Time, DateTime, TimeOffset, TimeOffsetAmount, ParseError, StrType, Children, Value};
 use crate::internals::parser::Parser;
+use crate::internals::compare::valid_string;
 use nom::{IResult, InputLength};
 
 pub enum Key<'a> {
@@ -21,7 +24,7 @@ impl<'a> Key<'a> {
   }
 }
 
-fn is_keychar(chr: char) -> bool {
+pub fn is_keychar(chr: char) -> bool {
   let ucharacter = chr as u32;
   (0x41..=0x5A).contains(&ucharacter) || // A-Z
   (0x61..=0x7A).contains(&ucharacter) || // a-z
@@ -40,6 +43,30 @@ pub fn count_lines(s: &str) -> usize {
   }
 }
 
+// Builds the error for a value from its key, line, column and text
+type ValueError<'a> = fn(String, usize, usize, Cow<'a, str>) -> ParseError<'a>;
+
+// Returns the significant digits of a decimal number like `-12.50e3` without leading or trailing zeros, along with the
+// exponent of the last digit, so that numbers written in different ways can be compared
+fn decimal_digits(s: &str) -> (String, i64) {
+  let s = s.trim_start_matches(['+', '-']);
+  let (mantissa, exp) = match s.find(['e', 'E']) {
+    Some(i) => (&s[..i], s[i + 1..].parse::<i64>().unwrap_or(0)),
+    None => (s, 0),
+  };
+  let (int, frac) = match mantissa.find('.') {
+    Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+    None => (mantissa, ""),
+  };
+  let digits = format!("{int}{frac}");
+  let trimmed = digits.trim_end_matches('0');
+  if trimmed.trim_start_matches('0').is_empty() {
+    return (String::new(), 0);
+  }
+  let exp = exp - i64::try_from(frac.len()).unwrap_or(0) + i64::try_from(digits.len() - trimmed.len()).unwrap_or(0);
+  (trimmed.trim_start_matches('0').to_string(), exp)
+}
+
 impl<'a> Parser<'a> {
   pub fn get_key_parent(tables: &RefCell<Vec<Rc<TableType<'a>>>>,
     tables_index: &RefCell<Vec<usize>>) -> String {
@@ -420,13 +447,87 @@ impl<'a> Parser<'a> {
   method!(pub float<Parser<'a>, &'a str, &'a str>, self,
          re_find!(r#"^(\+|-)?([1-9](\d|(_\d))+|\d)((\.\d(\d|(_\d))*)((e|E)(\+|-)?([1-9](\d|(_\d))+|\d))|(\.\d(\d|(_\d))*)|((e|E)(\+|-)?([1-9](\d|(_\d))+|\d)))"#));
 
+  // Anything that looks like an integer or a float, so that badly written ones can be reported instead of failing
+  method!(loose_integer<Parser<'a>, &'a str, &'a str>, self, re_find!(r"^(\+|-)?[0-9][0-9_]*"));
+  method!(loose_float<Parser<'a>, &'a str, &'a str>, self,
+         re_find!(r"^(\+|-)?[0-9][0-9_]*(\.[0-9_]*([eE](\+|-)?[0-9_]*)?|[eE](\+|-)?[0-9_]*)"));
+
+  /// Parses an integer, reporting one that isn't written correctly or doesn't fit in an `i64` as an error.
+  fn integer_value(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, IResult<&'a str, &'a str>) {
+    let (tmp, loose) = self.loose_integer(input);
+    self = tmp;
+    let (i, int) = match loose {
+      IResult::Done(i, o) => (i, o),
+      IResult::Error(e) => return (self, IResult::Error(e)),
+      IResult::Incomplete(n) => return (self, IResult::Incomplete(n)),
+    };
+    let (tmp, strict) = self.integer(int);
+    self = tmp;
+    let error: Option<ValueError<'a>> = match strict {
+      IResult::Done("", _) => match int.replace('_', "").parse::<i64>() {
+        Ok(_) => None,
+        Err(ref e) if *e.kind() == IntErrorKind::PosOverflow => Some(ParseError::IntegerOverflow),
+        Err(ref e) if *e.kind() == IntErrorKind::NegOverflow => Some(ParseError::IntegerUnderflow),
+        Err(_) => Some(ParseError::InvalidInteger),
+      },
+      _ => Some(ParseError::InvalidInteger),
+    };
+    if let Some(error) = error {
+      self.push_value_error(error, int);
+    }
+    (self, IResult::Done(i, int))
+  }
+
+  /// Parses a float, reporting one that isn't written correctly or can't be represented exactly by an `f64` as an
+  /// error.
+  fn float_value(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, IResult<&'a str, &'a str>) {
+    let (tmp, loose) = self.loose_float(input);
+    self = tmp;
+    let (i, flt) = match loose {
+      IResult::Done(i, o) => (i, o),
+      IResult::Error(e) => return (self, IResult::Error(e)),
+      IResult::Incomplete(n) => return (self, IResult::Incomplete(n)),
+    };
+    let (tmp, strict) = self.float(flt);
+    self = tmp;
+    let error: Option<ValueError<'a>> = match strict {
+      IResult::Done("", _) => match flt.replace('_', "").parse::<f64>() {
+        Ok(f) if f.is_infinite() && f > 0.0 => Some(ParseError::Infinity),
+        Ok(f) if f.is_infinite() => Some(ParseError::NegativeInfinity),
+        Ok(f) if decimal_digits(&flt.replace('_', "")) != decimal_digits(&format!("{f:e}")) =>
+          Some(ParseError::LossOfPrecision),
+        Ok(_) => None,
+        Err(_) => Some(ParseError::InvalidFloat),
+      },
+      _ => Some(ParseError::InvalidFloat),
+    };
+    if let Some(error) = error {
+      self.push_value_error(error, flt);
+    }
+    (self, IResult::Done(i, flt))
+  }
+
+  /// Reports an error in the value `text` of the key being parsed.
+  fn push_value_error(&mut self, error: ValueError<'a>, text: &'a str) {
+    let key = self.value_key();
+    let err = error(key, self.line_count.get(), self.column(text), text.into());
+    debug!("Value error: {err:?}");
+    self.errors.borrow_mut().push(err);
+  }
+
+  /// Returns the full key of the value being parsed.
+  fn value_key(&mut self) -> String {
+    Parser::get_full_key(&RefCell::new(&mut self.map), &self.last_array_tables, &self.last_array_tables_index,
+      &self.keychain).1
+  }
+
   // Basic String
   named!(pub quoteless_basic_string<&'a str, &'a str>,
-    re_find!(r#"^( |!|[#-\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\\t)|(\\u[0-9A-Z]{4})|(\\U[0-9A-Z]{8}))*"#));
+    re_find!(r#"^( |!|[#-\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\\t)|(\\u[0-9A-Fa-f]{4})|(\\U[0-9A-Fa-f]{8}))*"#));
   // Multiline Basic String
   // TODO: Convert this to take_while_s using a function that increments self.linecount
   named!(pub quoteless_ml_basic_string<&'a str, &'a str>,
-    re_find!(r#"^([ -\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\t)|(\\u[0-9A-Z]{4})|(\\U[0-9A-Z]{8})|\n|(\r\n)|(\\(\n|(\r\n))))*"#));
+    re_find!(r#"^([ -\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\t)|(\\u[0-9A-Fa-f]{4})|(\\U[0-9A-Fa-f]{8})|\n|(\r\n)|(\\(\n|(\r\n))))*"#));
   // Literal String
   named!(pub quoteless_literal_string<&'a str, &'a str>, re_find!(r#"^( |[ -&]|[\(-￿])*"#));
   // Multiline Literal String
@@ -435,12 +536,20 @@ impl<'a> Parser<'a> {
 
   // Basic String
   method!(raw_basic_string<Parser<'a>, &'a str, &'a str>, self,
-    re_find!(r#"^"( |!|[#-\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\\t)|(\\u[0-9A-Z]{4})|(\\U[0-9A-Z]{8}))*?""#));
+    re_find!(r#"^"( |!|[#-\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\\t)|(\\u[0-9A-Fa-f]{4})|(\\U[0-9A-Fa-f]{8}))*?""#));
   // Multiline Basic String
   // TODO: Convert this to take_while_s using a function that increments self.linecount
   method!(raw_ml_basic_string<Parser<'a>, &'a str, &'a str>, self,
     chain!(
-   string: re_find!(r#"^"""([ -\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\t)|(\\u[0-9A-Z]{4})|(\\U[0-9A-Z]{8})|\n|(\r\n)|(\\(\n|(\r\n))))*?""""#),
+   string: re_find!(r#"^"""([ -\[]|[\]-￿]|(\\")|(\\\\)|(\\/)|(\\b)|(\\f)|(\\n)|(\\r)|(\t)|(\\u[0-9A-Fa-f]{4})|(\\U[0-9A-Fa-f]{8})|\n|(\r\n)|(\\(\n|(\r\n))))*?""""#),
+      ||{self.line_count.set(self.line_count.get() + count_lines(string)); string}
+    )
+  );
+  // Basic strings with any escape sequences or control characters in them, so that invalid ones can be reported
+  method!(raw_loose_basic_string<Parser<'a>, &'a str, &'a str>, self, re_find!(r#"^"([^"\\\n]|\\[^\n])*""#));
+  method!(raw_loose_ml_basic_string<Parser<'a>, &'a str, &'a str>, self,
+    chain!(
+   string: re_find!(r#"^"""([^\\]|\\(.|\n))*?""""#),
       ||{self.line_count.set(self.line_count.get() + count_lines(string)); string}
     )
   );
@@ -456,8 +565,14 @@ impl<'a> Parser<'a> {
   );
 
   fn ml_basic_string(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, nom::IResult<&'a str, &'a str>) {
-    let (tmp, raw) = self.raw_ml_basic_string(input);
+    let (tmp, mut raw) = self.raw_ml_basic_string(input);
     self = tmp;
+    if let IResult::Error(_) = raw {
+      let (tmp, loose) = self.raw_loose_ml_basic_string(input);
+      self = tmp;
+      raw = loose;
+    }
+    raw = self.check_string(raw, "\"\"\"", StrType::MLBasic);
     let r = match raw {
       IResult::Done(i, o) => IResult::Done(i, &o["\"\"\"".input_len()..o.input_len()-"\"\"\"".input_len()]),
       IResult::Error(_) => IResult::Error(nom::Err::Code(nom::ErrorKind::Custom(ErrorCode::MLLiteralString as u32))),
@@ -467,8 +582,14 @@ impl<'a> Parser<'a> {
   }
 
   fn basic_string(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, nom::IResult<&'a str, &'a str>) {
-    let (tmp, raw) = self.raw_basic_string(input);
+    let (tmp, mut raw) = self.raw_basic_string(input);
     self = tmp;
+    if let IResult::Error(_) = raw {
+      let (tmp, loose) = self.raw_loose_basic_string(input);
+      self = tmp;
+      raw = loose;
+    }
+    raw = self.check_string(raw, "\"", StrType::Basic);
     let r = match raw {
       IResult::Done(i, o) => IResult::Done(i, &o["\"".input_len()..o.input_len()-"\"".input_len()]),
       IResult::Error(_) => IResult::Error(nom::Err::Code(nom::ErrorKind::Custom(ErrorCode::MLLiteralString as u32))),
@@ -477,6 +598,21 @@ impl<'a> Parser<'a> {
     (self, r)
   }
 
+  // Reports a string with an invalid escape sequence, control character or code point as an error
+  fn check_string(&mut self, raw: IResult<&'a str, &'a str>, delimiter: &str, st: StrType)
+    -> IResult<&'a str, &'a str> {
+    if let IResult::Done(_, o) = raw {
+      let text = &o[delimiter.len()..o.len() - delimiter.len()];
+      if !valid_string(text, st) {
+        let err = ParseError::InvalidString(self.value_key(), self.line_count.get() - count_lines(text),
+          self.column(o), o.into(), st);
+        debug!("Value error: {err:?}");
+        self.errors.borrow_mut().push(err);
+      }
+    }
+    raw
+  }
+
   fn ml_literal_string(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, nom::IResult<&'a str, &'a str>) {
     let (tmp, raw) = self.raw_ml_literal_string(input);
     self = tmp;
@@ -508,10 +644,27 @@ impl<'a> Parser<'a> {
     )
   );
 
-  // TODO: Allow alternate casing, but report it as an error
   // Boolean
   method!(boolean<Parser<'a>, &'a str, bool>, self, alt!(complete!(tag_s!("false")) => {|_| false} |
                                                          complete!(tag_s!("true"))  => {|_| true}));
+  method!(loose_boolean<Parser<'a>, &'a str, &'a str>, self, re_find!("^(?i)(true|false)"));
+
+  /// Parses a boolean, accepting any casing of `true` and `false` but reporting anything but lowercase as an error. The
+  /// original text is kept so that the document still prints back exactly as it was written.
+  fn boolean_value(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, IResult<&'a str, &'a str>) {
+    let (tmp, loose) = self.loose_boolean(input);
+    self = tmp;
+    match loose {
+      IResult::Done(i, b) => {
+        if b != "true" && b != "false" {
+          self.push_value_error(ParseError::InvalidBoolean, b);
+        }
+        (self, IResult::Done(i, b))
+      },
+      IResult::Error(e) => (self, IResult::Error(e)),
+      IResult::Incomplete(n) => (self, IResult::Incomplete(n)),
+    }
+  }
 
 
   // Datetime
@@ -580,11 +733,7 @@ impl<'a> Parser<'a> {
       IResult::Done(i, o) => {
         if !o.validate() {
           let parsed_len = before_len - i.len();
-          self.errors.borrow_mut().push(ParseError::InvalidDateTime(
-            Parser::get_full_key(&RefCell::new(& mut self.map), &self.last_array_tables,
-              &self.last_array_tables_index, &self.keychain
-            ).1, self.line_count.get(), 0, input[0..parsed_len].into()
-          ));
+          self.push_value_error(ParseError::InvalidDateTime, &input[0..parsed_len]);
         }
         (self, IResult::Done(i, o))
       },
@@ -631,9 +780,9 @@ impl<'a> Parser<'a> {
       complete!(call_m!(self.array))        => {|arr|           Rc::new(RefCell::new(TOMLValue::Array(arr)))}             |
       complete!(call_m!(self.inline_table)) => {|it|            Rc::new(RefCell::new(TOMLValue::InlineTable(it)))}        |
       complete!(call_m!(self.date_time))    => {|dt|            Rc::new(RefCell::new(TOMLValue::DateTime(dt)))}           |
-      complete!(call_m!(self.float))        => {|flt: &'a str|  Rc::new(RefCell::new(TOMLValue::Float(flt.into())))}   |
-      complete!(call_m!(self.integer))      => {|int: &'a str|  Rc::new(RefCell::new(TOMLValue::Integer(int.into())))} |
-      complete!(call_m!(self.boolean))      => {|b|             Rc::new(RefCell::new(TOMLValue::Boolean(b)))}             |
+      complete!(call_m!(self.float_value))   => {|flt: &'a str| Rc::new(RefCell::new(TOMLValue::Float(flt.into())))}   |
+      complete!(call_m!(self.integer_value)) => {|int: &'a str| Rc::new(RefCell::new(TOMLValue::Integer(int.into())))} |
+      complete!(call_m!(self.boolean_value)) => {|b: &'a str|   Rc::new(RefCell::new(TOMLValue::boolean(b)))}             |
       complete!(call_m!(self.string))       => {|s|             Rc::new(RefCell::new(s))}
     )
   );
@@ -876,9 +1025,16 @@ NÃ›MÃŸÃ‰R-THRÃ‰Ã‰
     p = Parser::new();
     assert_eq!(p.val("8932838").1, Done("", Rc::new(RefCell::new(TOMLValue::Integer("8932838".into())))));
     p = Parser::new();
-    assert_eq!(p.val("false").1, Done("", Rc::new(RefCell::new(TOMLValue::Boolean(false)))));
+    assert_eq!(p.val("false").1, Done("", Rc::new(RefCell::new(TOMLValue::Boolean(false, None)))));
     p = Parser::new();
-    assert_eq!(p.val("true").1, Done("", Rc::new(RefCell::new(TOMLValue::Boolean(true)))));
+    assert_eq!(p.val("true").1, Done("", Rc::new(RefCell::new(TOMLValue::Boolean(true, None)))));
+    p = Parser::new();
+    if let Done("", val) = p.val("True").1 {
+      assert_eq!(TOMLValue::Boolean(true, Some("True".into())), *val.borrow());
+      assert_eq!("True", val.borrow().to_string());
+    } else {
+      panic!("Expected True to be read as a boolean");
+    }
     p = Parser::new();
     assert_eq!(p.val("'Â§Ã´â‚¥Ã¨ Â§Æ­Å™Ã¯Ã±Ï±'").1, Done("", Rc::new(RefCell::new(TOMLValue::String(
       "Â§Ã´â‚¥Ã¨ Â§Æ­Å™Ã¯Ã±Ï±".into(), StrType::Literal
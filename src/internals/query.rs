@@ -0,0 +1,392 @@
+use std::collections::HashSet;
+use nom::IResult;
+use crate::internals::ast::structs::TOMLValue;
+use crate::internals::parser::Parser;
+use crate::internals::build::split_key;
+use crate::internals::compare::{normalize_key, values_eq};
+use crate::internals::primitives::is_keychar;
+use crate::internals::walk::{join_key, map_key};
+use crate::types::{Value, Children, DocEntry, TOMLError};
+
+/// One step of a query, matched against the children of every key matched by the previous step.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Step {
+  /// A child key, bare or quoted: `name`.
+  Child(String),
+  /// Every child key of a table or inline table: `*`.
+  AnyChild,
+  /// An element of an array or array of tables: `[1]`.
+  Index(usize),
+  /// Every element of an array or array of tables: `[*]`.
+  AnyIndex,
+  /// Every child whose value at the key compares to the value literal: `[?key == value]` or `[?key != value]`.
+  /// Contains the key, true for `==`, and the value as written.
+  Filter(String, bool, String),
+  /// The step matched against the key itself and all of its descendants: `..step`.
+  Descendant(Box<Step>),
+}
+
+/// Parses `query` into the steps it's made of.
+pub fn parse_query(query: &str) -> Result<Vec<Step>, TOMLError> {
+  let mut steps = vec![];
+  let mut rest = query.trim();
+  let fail = |msg: &str| TOMLError::new(format!("Invalid query \"{query}\": {msg}"));
+  while !rest.is_empty() {
+    let descendant = rest.starts_with("..");
+    if descendant {
+      rest = &rest[2..];
+    } else if rest.starts_with('.') {
+      if steps.is_empty() {
+        return Err(fail("a query can't start with a single \".\""));
+      }
+      rest = &rest[1..];
+    } else if !steps.is_empty() && !rest.starts_with('[') {
+      return Err(fail("keys must be separated by \".\""));
+    }
+    let (step, len) = if rest.starts_with('[') {
+      let Some(end) = bracket_end(rest) else {
+        return Err(fail("unclosed \"[\""));
+      };
+      let inner = rest[1..end].trim();
+      let step = if inner == "*" {
+        Step::AnyIndex
+      } else if let Ok(i) = inner.parse() {
+        Step::Index(i)
+      } else if inner.starts_with('?') {
+        parse_filter(&inner[1..]).ok_or_else(|| fail("invalid filter"))?
+      } else {
+        return Err(fail("expected an index, \"*\" or a filter between \"[\" and \"]\""));
+      };
+      (step, end + 1)
+    } else if rest.starts_with('*') {
+      (Step::AnyChild, 1)
+    } else {
+      let len = key_len(rest);
+      if len == 0 {
+        return Err(fail("expected a key"));
+      }
+      (Step::Child(rest[..len].to_string()), len)
+    };
+    steps.push(if descendant { Step::Descendant(Box::new(step)) } else { step });
+    rest = &rest[len..];
+  }
+  if steps.is_empty() {
+    return Err(fail("the query is empty"));
+  }
+  Ok(steps)
+}
+
+/// Returns the length of the bare or quoted key at the start of `s`, or 0 if there isn't one.
+fn key_len(s: &str) -> usize {
+  if s.starts_with('"') {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+      match c {
+        '"' if !escaped => return i + 1,
+        '\n' => return 0,
+        _ => (),
+      }
+      escaped = c == '\\' && !escaped;
+    }
+    0
+  } else {
+    s.find(|c| !is_keychar(c)).unwrap_or(s.len())
+  }
+}
+
+/// Returns the index of the `]` that closes the `[` at the start of `s`, skipping over quoted strings.
+fn bracket_end(s: &str) -> Option<usize> {
+  let mut quote = None;
+  let mut escaped = false;
+  for (i, c) in s.char_indices().skip(1) {
+    match (quote, c) {
+      (None, ']') => return Some(i),
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(q), _) if q == c && (q == '\'' || !escaped) => quote = None,
+      _ => (),
+    }
+    escaped = c == '\\' && !escaped;
+  }
+  None
+}
+
+/// Parses the inside of a filter after the `?`, e.g. `name == "tomlkit"`.
+fn parse_filter(filter: &str) -> Option<Step> {
+  let (pos, is_eq) = match (filter.find("=="), filter.find("!=")) {
+    (Some(i), Some(j)) => if i < j { (i, true) } else { (j, false) },
+    (Some(i), None) => (i, true),
+    (None, Some(j)) => (j, false),
+    (None, None) => return None,
+  };
+  let key = filter[..pos].trim();
+  let val = filter[pos + 2..].trim();
+  let mut len = 0;
+  while len < key.len() {
+    let key_len = key_len(&key[len..]);
+    if key_len == 0 {
+      return None;
+    }
+    len += key_len;
+    if key[len..].starts_with('.') {
+      len += 1;
+    }
+  }
+  if key.is_empty() || key.ends_with('.') || len != key.len() || parse_value(val).is_none() {
+    return None;
+  }
+  Some(Step::Filter(key.to_string(), is_eq, val.to_string()))
+}
+
+/// Parses a TOML value literal, e.g. `"tomlkit"`, `42` or `true`.
+fn parse_value(val: &str) -> Option<Value<'_>> {
+  match Parser::new().val(val) {
+    (_, IResult::Done("", tval)) => Some(to_val!(&*tval.borrow())),
+    _ => None,
+  }
+}
+
+impl<'a> Parser<'a> {
+  /// Returns every table, array of tables, array of tables element and key/value pair matching `query`, in document
+  /// order.
+  pub fn query(self: &Parser<'a>, query: &str) -> Result<Vec<(String, DocEntry<'a>)>, TOMLError> {
+    let steps = parse_query(query)?;
+    let mut keys = vec![String::new()];
+    for step in &steps {
+      let mut matched = vec![];
+      let mut seen = HashSet::new();
+      for key in &keys {
+        for m in self.match_step(key, step) {
+          if seen.insert(m.clone()) {
+            matched.push(m);
+          }
+        }
+      }
+      keys = matched;
+    }
+    debug!("Query {} matched {} keys", query, keys.len());
+    Ok(keys.into_iter().filter_map(|key| self.doc_entry(&key).map(|entry| (key, entry))).collect())
+  }
+
+  /// Replaces every value matching `query` with the value `f` returns for it, using `set_value`, and returns how many
+  /// values changed. Matches without a value are skipped, as are values `f` returns unchanged. A match inside another
+  /// match that changes, like `a[0]` when `a` changes, is skipped too, since setting `a` replaces it. Nothing is
+  /// changed if `f` returns an invalid value.
+  pub fn set_query_values<F>(self: &mut Parser<'a>, query: &str, mut f: F) -> Result<usize, TOMLError>
+    where F: FnMut(&Value<'a>) -> Value<'a> {
+    let mut changes = vec![];
+    for (key, entry) in self.query(query)? {
+      if let DocEntry::Value(old) = entry {
+        let new = f(&old);
+        if new != old {
+          if Parser::convert_vector(&new).is_none() {
+            return Err(TOMLError::new(format!("Invalid value for key {key}: {new}")));
+          }
+          changes.push((key, new));
+        }
+      }
+    }
+    let ancestors: Vec<String> = changes.iter().map(|(key, _)| key.clone()).collect();
+    changes.retain(|(key, _)| !ancestors.iter().any(|a| is_descendant(key, a)));
+    let mut count = 0;
+    for (key, val) in changes {
+      debug!("Setting {key} = {val}");
+      if self.set_value(key, val) {
+        count += 1;
+      }
+    }
+    Ok(count)
+  }
+
+  fn match_step(self: &Parser<'a>, key: &str, step: &Step) -> Vec<String> {
+    match *step {
+      Step::Child(ref name) => self.child_key(key, name).into_iter().collect(),
+      Step::AnyChild => match self.map.get(&map_key(key)) {
+        Some(hv) => match hv.subkeys {
+          Children::Keys(ref children) => children.borrow().iter().map(|c| join_key(key, c)).collect(),
+          Children::Count(_) => vec![],
+        },
+        None => vec![],
+      },
+      Step::Index(i) => match self.map.get(&map_key(key)) {
+        Some(hv) => match hv.subkeys {
+          Children::Count(ref c) if i < c.get() => vec![Children::combine_keys_index(key, i)],
+          _ => vec![],
+        },
+        None => vec![],
+      },
+      Step::AnyIndex => match self.map.get(&map_key(key)) {
+        Some(hv) => match hv.subkeys {
+          Children::Count(ref c) => (0..c.get()).map(|i| Children::combine_keys_index(key, i)).collect(),
+          Children::Keys(_) => vec![],
+        },
+        None => vec![],
+      },
+      Step::Filter(ref filter_key, is_eq, ref filter_val) => {
+        let Some(filter_val) = parse_value(filter_val) else {
+          return vec![];
+        };
+        let mut children = self.match_step(key, &Step::AnyChild);
+        children.extend(self.match_step(key, &Step::AnyIndex));
+        children.into_iter().filter(|child| {
+          match self.find_key(child, filter_key).and_then(|k| self.get_value(k)) {
+            Some(ref val) => values_eq(val, &filter_val) == is_eq,
+            None => false,
+          }
+        }).collect()
+      },
+      Step::Descendant(ref inner) => {
+        let mut keys = vec![];
+        self.push_descendants(key, &mut keys);
+        keys.iter().flat_map(|k| self.match_step(k, inner)).collect()
+      },
+    }
+  }
+
+  /// Returns the key of the child `name` of `key` as it's written in the document, matching quoted and bare keys by
+  /// meaning.
+  fn child_key(self: &Parser<'a>, key: &str, name: &str) -> Option<String> {
+    let hv = self.map.get(&map_key(key))?;
+    match hv.subkeys {
+      Children::Keys(ref children) => {
+        let norm_name = normalize_key(name);
+        children.borrow().iter().find(|c| normalize_key(c) == norm_name).map(|c| join_key(key, c))
+      },
+      Children::Count(_) => None,
+    }
+  }
+
+  /// Follows the dotted `path` down from `key`.
+  fn find_key(self: &Parser<'a>, key: &str, path: &str) -> Option<String> {
+    let mut found = key.to_string();
+    for segment in split_key(path) {
+      found = self.child_key(&found, &segment)?;
+    }
+    Some(found)
+  }
+
+  fn push_descendants(self: &Parser<'a>, key: &str, keys: &mut Vec<String>) {
+    keys.push(key.to_string());
+    let mut children = self.match_step(key, &Step::AnyChild);
+    children.extend(self.match_step(key, &Step::AnyIndex));
+    for child in children {
+      self.push_descendants(&child, keys);
+    }
+  }
+
+  fn doc_entry(self: &Parser<'a>, key: &str) -> Option<DocEntry<'a>> {
+    let hv = self.map.get(&map_key(key))?;
+    Some(match (&hv.value, &hv.subkeys) {
+      (Some(val), _) if *val.borrow() != TOMLValue::Table => DocEntry::Value(to_val!(&*val.borrow())),
+      (&None, &Children::Count(_)) => DocEntry::ArrayOfTables,
+      _ if key.ends_with(']') => DocEntry::ArrayTable,
+      _ => DocEntry::Table,
+    })
+  }
+}
+
+/// Returns whether `key` is a key inside `ancestor`, e.g. `a.b` or `a[0]` inside `a`.
+fn is_descendant(key: &str, ancestor: &str) -> bool {
+  key.len() > ancestor.len() && key.starts_with(ancestor) && key[ancestor.len()..].starts_with(['.', '['])
+}
+
+#[cfg(test)]
+mod test {
+  use std::rc::Rc;
+  use crate::internals::parser::Parser;
+  use crate::internals::query::{Step, parse_query};
+  use crate::types::{Value, DocEntry, StrType};
+
+  #[test]
+  fn test_parse_query() {
+    assert_eq!(vec![Step::Child("a".to_string()), Step::AnyChild, Step::Child("\"b.c\"".to_string())],
+      parse_query("a.*.\"b.c\"").unwrap());
+    assert_eq!(vec![Step::Child("bin".to_string()), Step::AnyIndex, Step::Index(2)],
+      parse_query("bin[*][2]").unwrap());
+    assert_eq!(vec![Step::Descendant(Box::new(Step::Child("version".to_string())))],
+      parse_query("..version").unwrap());
+    assert_eq!(vec![Step::Child("bin".to_string()),
+      Step::Filter("name".to_string(), true, "\"tom]kit\"".to_string())],
+      parse_query("bin[?name == \"tom]kit\"]").unwrap());
+    assert_eq!(vec![Step::Child("a".to_string()), Step::Filter("b.c".to_string(), false, "1".to_string())],
+      parse_query("a[? b.c != 1 ]").unwrap());
+    for bad in &["", ".a", "a b", "a[", "a[x]", "a[?b]", "a[?b == nope]", "a..", "a.\"b"] {
+      assert!(parse_query(bad).is_err(), "query {}", bad);
+    }
+  }
+
+  #[test]
+  fn test_query() {
+    let (p, _) = Parser::new().parse(r#"[dependencies]
+nom = { version = "1.2" }
+regex = { version = "0.1", optional = true }
+
+[[bin]]
+name = "tomlkit"
+path = "src/bin/tomlkit.rs"
+
+[[bin]]
+name = "other"
+
+[package]
+version = "0.1.2"
+authors = ["a", "b"]
+"#);
+    let keys = |q: &str| -> Vec<String> { p.query(q).unwrap().into_iter().map(|(k, _)| k).collect() };
+    assert_eq!(vec!["dependencies.nom.version", "dependencies.regex.version"], keys("dependencies.*.version"));
+    assert_eq!(vec!["bin[0].name", "bin[1].name"], keys("bin[*].name"));
+    assert_eq!(vec!["dependencies.nom.version", "dependencies.regex.version", "package.version"], keys("..version"));
+    assert_eq!(vec!["bin[0]"], keys("bin[?name == \"tomlkit\"]"));
+    assert_eq!(vec!["bin[1].name"], keys("bin[?name != 'tomlkit'].name"));
+    assert_eq!(vec!["dependencies.regex"], keys("dependencies[?optional == true]"));
+    assert_eq!(vec!["package.authors[1]"], keys("\"package\".authors[1]"));
+    assert!(keys("package.missing").is_empty());
+    assert_eq!(vec![("package.version".to_string(), DocEntry::Value(Value::basic_string("0.1.2").unwrap()))],
+      p.query("package.version").unwrap());
+    assert_eq!(vec![("bin".to_string(), DocEntry::ArrayOfTables), ("bin[1]".to_string(), DocEntry::ArrayTable),
+      ("package".to_string(), DocEntry::Table)], p.query("bin").unwrap().into_iter()
+      .chain(p.query("bin[1]").unwrap()).chain(p.query("package").unwrap()).collect::<Vec<_>>());
+    assert!(p.query("bin[").is_err());
+  }
+
+  #[test]
+  fn test_set_query_values() {
+    let (mut p, _) = Parser::new().parse(r#"[dependencies]
+nom = { version = "0.1.2", features = ["regexp"] }
+regex = { version = "0.1.2" } # pinned
+log = "0.1.2"
+
+[dev-dependencies]
+csv = {version='0.1.2'}
+"#);
+    let old = Value::basic_string("0.1.2").unwrap();
+    let count = p.set_query_values("..version", |v| {
+      if v.semantic_eq(&old) { Value::basic_string("0.1.3").unwrap() } else { v.clone() }
+    }).unwrap();
+    assert_eq!(3, count);
+    assert_eq!(r#"[dependencies]
+nom = { version = "0.1.3", features = ["regexp"] }
+regex = { version = "0.1.3" } # pinned
+log = "0.1.2"
+
+[dev-dependencies]
+csv = {version="0.1.3"}
+"#, format!("{p}"));
+    assert_eq!(0, p.set_query_values("..version", Value::clone).unwrap());
+    assert_eq!(1, p.set_query_values("dependencies.log", |_| Value::int(1)).unwrap());
+    assert!(p.set_query_values("dependencies.log", |_| Value::String("a\nb".into(), StrType::Basic)).is_err());
+    assert_eq!(Some(Value::int(1)), p.get_value("dependencies.log"));
+    assert!(p.set_query_values("[", Value::clone).is_err());
+  }
+
+  #[test]
+  fn test_set_query_values_nested() {
+    let (mut p, _) = Parser::new().parse("a = [1, 2]\nb = { c = 3 }\n");
+    let count = p.set_query_values("..*", |v| match *v {
+      Value::Integer(_) => Value::int(0),
+      Value::InlineTable(_) => Value::InlineTable(Rc::new(vec![("c".into(), Value::int(4))])),
+      _ => Value::Array(Rc::new(vec![Value::int(5)])),
+    }).unwrap();
+    assert_eq!(2, count);
+    assert_eq!("a = [5]\nb = { c = 4 }\n", p.to_string());
+  }
+}
@@ -1,23 +1,64 @@
 use crate::internals::ast::structs::{Toml, NLExpression, Expression, WSSep};
 use crate::internals::parser::Parser;
+use crate::types::ParseError;
+use nom::IResult;
 
 impl<'a> Parser<'a> {
-  method!(pub toml<Parser<'a>, &'a str, Toml>, mut self,
-    chain!(
-      expr: call_m!(self.expression)    ~
-  nl_exprs: call_m!(self.nl_expressions),
-      ||{
-        let mut tmp = vec![NLExpression::new_str("", expr)];
-        tmp.extend(nl_exprs); Toml{ exprs: tmp}
+  /// Parses the document one line at a time. Anything left on a line that can't be parsed is recorded as a
+  /// `ParseError::InvalidSyntax`, and parsing resumes on the next line. A strict parser stops after the first error.
+  pub fn toml(mut self: Parser<'a>, input: &'a str) -> (Parser<'a>, IResult<&'a str, Toml<'a>>) {
+    let first_line = self.line_count.get();
+    self.document = input;
+    let (tmp, res) = self.expression(input);
+    self = tmp;
+    let (mut rest, expr) = match res {
+      IResult::Done(i, o) => (i, o),
+      _ => (input, Expression::new(WSSep::new_str("", ""), None, None, None)),
+    };
+    let mut exprs = vec![NLExpression::new_str("", expr)];
+    loop {
+      let end = match rest.find('\n') {
+        Some(i) if rest[..i].ends_with('\r') => i - 1,
+        Some(i) => i,
+        None => rest.len(),
+      };
+      if end > 0 {
+        let line = first_line + input[..input.len() - rest.len()].matches('\n').count();
+        let col = self.column(rest);
+        debug!("Invalid syntax at line {}, column {}: \"{}\"", line, col, &rest[..end]);
+        // A line that failed part way through can leave the key and array type stacks of its keys behind
+        self.keychain.borrow_mut().clear();
+        self.last_array_type.borrow_mut().clear();
+        self.errors.borrow_mut().push(ParseError::InvalidSyntax(line, col, rest[..end].into()));
+        if let Some(nl_expr) = exprs.last_mut() {
+          nl_expr.expr.invalid = Some(rest[..end].into());
+        }
+        rest = &rest[end..];
+        self.line_count.set(line);
       }
-    )
-  );
+      if rest.is_empty() || !self.recovering() {
+        break;
+      }
+      let (tmp, res) = self.nl_expressions(rest);
+      self = tmp;
+      match res {
+        IResult::Done(i, ref o) if o.is_empty() && i == rest => break,
+        IResult::Done(i, o) => {
+          rest = i;
+          exprs.extend(o);
+        },
+        _ => break,
+      }
+    }
+    self.line_count.set(first_line + input[..input.len() - rest.len()].matches('\n').count());
+    (self, IResult::Done(rest, Toml{ exprs }))
+  }
 
   method!(nl_expressions<Parser<'a>, &'a str, Vec<NLExpression> >, mut self, many0!(call_m!(self.nl_expression)));
 
   method!(nl_expression<Parser<'a>, &'a str, NLExpression>, mut self,
     chain!(
-       nl: call_m!(self.newline)    ~
+       nl: cond_reduce!(self.recovering(), call_m!(self.newline)) ~
      expr: call_m!(self.expression) ,
       ||{
         NLExpression::new_str(nl, expr)
@@ -210,7 +251,7 @@ enabled = true"#).1, Done("",
         NLExpression::new_str(
           "\n", Expression::new(
             WSSep::new_str("", ""), Some(KeyVal::new_str(
-              "enabled", WSSep::new_str(" ", " "), Rc::new(RefCell::new(TOMLValue::Boolean(true)))
+              "enabled", WSSep::new_str(" ", " "), Rc::new(RefCell::new(TOMLValue::Boolean(true, None)))
             )),
             None, None
           )
@@ -357,7 +398,7 @@ enabled = true"#).1, Done("",
     assert_eq!(p.keyval_comment(" \"Tôƭáℓℓ¥\" = true\t#λèřè ïƨ ₥¥ çô₥₥èñƭ\n").1,
       Done("\n",
         Expression::new(WSSep::new_str(" ", "\t"), Some(KeyVal::new_str(
-            "\"Tôƭáℓℓ¥\"", WSSep::new_str(" ", " "), Rc::new(RefCell::new(TOMLValue::Boolean(true)))
+            "\"Tôƭáℓℓ¥\"", WSSep::new_str(" ", " "), Rc::new(RefCell::new(TOMLValue::Boolean(true, None)))
           )),
           None, Some(Comment::new_str("λèřè ïƨ ₥¥ çô₥₥èñƭ"))
         )
@@ -28,6 +28,18 @@ impl<'a> Parser<'a> {
       }
     )
   );
+
+  /// Returns the column, starting at 1, where `text` starts in the document being parsed, or 0 if `text` isn't part of
+  /// the document.
+  pub fn column(self: &Parser<'a>, text: &str) -> usize {
+    let start = self.document.as_ptr() as usize;
+    let pos = text.as_ptr() as usize;
+    if pos < start || pos > start + self.document.len() {
+      return 0;
+    }
+    let before = &self.document[..pos - start];
+    before[before.rfind('\n').map_or(0, |i| i + 1)..].chars().count() + 1
+  }
 }
 
 #[cfg(test)]
@@ -44,6 +56,15 @@ mod test {
     assert_eq!(p.newline("\n").1, Done("", "\n"));
   }
 
+  #[test]
+  fn test_column() {
+    let mut p = Parser::new();
+    p.document = "a = 1\nƀ = 2\n";
+    assert_eq!(1, p.column(&p.document[0..]));
+    assert_eq!(5, p.column(&p.document[11..]));
+    assert_eq!(0, p.column(&String::from("b = 2")));
+  }
+
   #[test]
   fn test_ws() {
     let p = Parser::new();
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use crate::internals::ast::structs::{TableType, TOMLValue, NLExpression};
+use crate::internals::parser::Parser;
+use crate::types::{Value, Children, DocEntry};
+
+/// The full key of a table header along with the keys it creates implicitly.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ResolvedTable {
+  /// The full key of the table, e.g. `a[1].b` or `fruit[0]`.
+  pub key: String,
+  /// The full key of the parent of the table, `""` for the root table.
+  pub parent: String,
+  /// The last key segment of the table header, as written.
+  pub segment: String,
+  /// For an array of tables, the full key of the array without the index of the new element.
+  pub array_key: Option<String>,
+  /// Tables that didn't exist before this header and are implied by it. Each tuple is parent key, key segment and
+  /// full key.
+  pub implicit: Vec<(String, String, String)>,
+}
+
+/// Resolves table headers encountered in document order to their full keys, keeping track of how many elements each
+/// array of tables has so far.
+pub struct TableTracker {
+  array_counts: HashMap<String, usize>,
+  known: HashSet<String>,
+}
+
+impl TableTracker {
+  pub fn new() -> TableTracker {
+    TableTracker{array_counts: HashMap::new(), known: HashSet::new()}
+  }
+
+  pub fn resolve(&mut self, table: &TableType) -> ResolvedTable {
+    let (t, is_array) = match *table {
+      TableType::Standard(ref t) => (t, false),
+      TableType::Array(ref t) => (t, true),
+    };
+    let mut key = String::new();
+    let mut implicit = vec![];
+    let len = t.keys.len();
+    for i in 0..len - 1 {
+      let parent = key.clone();
+      key = join_key(&key, &t.keys[i].key);
+      if let Some(count) = self.array_counts.get(&key) {
+        key = Children::combine_keys_index(key, count - 1);
+      } else if self.known.insert(key.clone()) {
+        implicit.push((parent, t.keys[i].key.clone().into_owned(), key.clone()));
+      }
+    }
+    let parent = key.clone();
+    let segment = t.keys[len - 1].key.clone().into_owned();
+    key = join_key(&key, &segment);
+    let mut array_key = None;
+    if is_array {
+      let count = self.array_counts.entry(key.clone()).or_insert(0);
+      *count += 1;
+      array_key = Some(key.clone());
+      key = Children::combine_keys_index(key, *count - 1);
+    } else {
+      self.known.insert(key.clone());
+    }
+    ResolvedTable{key, parent, segment, array_key, implicit}
+  }
+}
+
+/// Joins a parent key and a child key segment, treating an empty parent as the root table.
+pub fn join_key(parent: &str, child: &str) -> String {
+  if parent.is_empty() {
+    child.to_string()
+  } else {
+    format!("{parent}.{child}")
+  }
+}
+
+/// Converts a full key to the key used in the map, which stores the root table under `$Root$`.
+pub fn map_key(key: &str) -> String {
+  if key.is_empty() {
+    "$Root$".to_string()
+  } else {
+    key.to_string()
+  }
+}
+
+/// Returns the line number each expression in `exprs` starts on.
+pub fn expression_lines(exprs: &[NLExpression]) -> Vec<usize> {
+  let mut lines = Vec::with_capacity(exprs.len());
+  let mut line = 1;
+  for nl_expr in exprs {
+    if !nl_expr.nl.is_empty() {
+      line += 1;
+    }
+    lines.push(line);
+    line += newlines(&nl_expr.expr);
+  }
+  lines
+}
+
+fn newlines<T: Display>(t: &T) -> usize {
+  format!("{t}").matches('\n').count()
+}
+
+/// Adds the line of the value `val` of `key`, which starts on `line`, and of every value nested in it.
+fn push_value_lines(key: &str, val: &TOMLValue, line: usize, lines: &mut HashMap<String, usize>) {
+  lines.entry(key.to_string()).or_insert(line);
+  match *val {
+    TOMLValue::Array(ref arr) => {
+      let arr = arr.borrow();
+      let mut line = line + arr.comment_nls1.iter().map(newlines).sum::<usize>();
+      for (i, av) in arr.values.iter().enumerate() {
+        push_value_lines(&Children::combine_keys_index(key, i), &av.val.borrow(), line, lines);
+        line += newlines(av);
+      }
+    },
+    TOMLValue::InlineTable(ref it) => {
+      let it = it.borrow();
+      let mut line = line + newlines(&it.ws.ws1);
+      for kv in &it.keyvals {
+        push_value_lines(&join_key(key, &kv.keyval.key), &kv.keyval.val.borrow(), line, lines);
+        line += newlines(kv);
+      }
+    },
+    _ => (),
+  }
+}
+
+impl<'a> Parser<'a> {
+  /// Returns the line each key of the document is defined on, starting at 1. Tables that are only implied by the
+  /// header of a sub-table get the line of the first header that implies them, and an array of tables gets the line of
+  /// its first element.
+  pub fn key_lines(self: &Parser<'a>) -> HashMap<String, usize> {
+    let root = self.root.borrow();
+    let mut lines = HashMap::new();
+    let mut tracker = TableTracker::new();
+    let mut table_key = String::new();
+    for (nl_expr, line) in root.exprs.iter().zip(expression_lines(&root.exprs)) {
+      if let Some(ref table) = nl_expr.expr.table {
+        let resolved = tracker.resolve(table);
+        for (_, _, key) in resolved.implicit {
+          lines.entry(key).or_insert(line);
+        }
+        if let Some(array_key) = resolved.array_key {
+          lines.entry(array_key).or_insert(line);
+        }
+        lines.insert(resolved.key.clone(), line);
+        table_key = resolved.key;
+      } else if let Some(ref keyval) = nl_expr.expr.keyval {
+        push_value_lines(&join_key(&table_key, &keyval.key), &keyval.val.borrow(), line, &mut lines);
+      }
+    }
+    lines
+  }
+
+  /// Returns every table, array of tables element and key/value pair in the document depth-first, with children in
+  /// the order they first appear in the document.
+  pub fn entries(self: &Parser<'a>) -> Vec<(String, DocEntry<'a>)> {
+    let mut entries = vec![];
+    self.push_child_entries("", &mut entries);
+    entries
+  }
+
+  fn push_child_entries(self: &Parser<'a>, key: &str, entries: &mut Vec<(String, DocEntry<'a>)>) {
+    let children = match self.map.get(&map_key(key)) {
+      Some(hv) => match hv.subkeys {
+        Children::Keys(ref keys) => keys.borrow().clone(),
+        Children::Count(_) => return,
+      },
+      None => return,
+    };
+    for child in children {
+      let full_key = join_key(key, &child);
+      let Some(hv) = self.map.get(&full_key) else {
+        continue;
+      };
+      match (&hv.value, &hv.subkeys) {
+        (Some(val), _) if *val.borrow() != TOMLValue::Table => {
+          entries.push((full_key, DocEntry::Value(to_val!(&*val.borrow()))));
+        },
+        (&None, Children::Count(count)) => {
+          for i in 0..count.get() {
+            let element_key = Children::combine_keys_index(full_key.as_str(), i);
+            entries.push((element_key.clone(), DocEntry::ArrayTable));
+            self.push_child_entries(&element_key, entries);
+          }
+        },
+        _ => {
+          entries.push((full_key.clone(), DocEntry::Table));
+          self.push_child_entries(&full_key, entries);
+        },
+      }
+    }
+  }
+
+  /// Returns the tables of the document in the order their headers appear, starting with the root table under the key
+  /// `""`, each with its key/value pairs in document order.
+  pub fn table_entries(self: &Parser<'a>) -> Vec<(String, Vec<(String, Value<'a>)>)> {
+    let mut tables = vec![(String::new(), vec![])];
+    let mut tracker = TableTracker::new();
+    for nl_expr in &self.root.borrow().exprs {
+      if let Some(ref table) = nl_expr.expr.table {
+        tables.push((tracker.resolve(table).key, vec![]));
+      } else if let Some(ref keyval) = nl_expr.expr.keyval {
+        if let Some(&mut (ref table_key, ref mut values)) = tables.last_mut() {
+          values.push((join_key(table_key, &keyval.key), to_val!(&*keyval.val.borrow())));
+        }
+      }
+    }
+    tables
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::internals::parser::Parser;
+  use crate::internals::walk::{TableTracker, ResolvedTable};
+  use crate::types::{Value, DocEntry};
+
+  #[test]
+  fn test_resolve_nested_array_tables() {
+    let p = Parser::new();
+    let (p, _) = p.parse("[[a]]\n[[a.b]]\n[[a.b]]\n[[a]]\n[[a.b]]\n[a.b.c]\n");
+    let mut tracker = TableTracker::new();
+    let keys: Vec<String> = p.root.borrow().exprs.iter().filter_map(|e| e.expr.table.as_ref())
+      .map(|t| tracker.resolve(t).key).collect();
+    assert_eq!(vec!["a[0]", "a[0].b[0]", "a[0].b[1]", "a[1]", "a[1].b[0]", "a[1].b[0].c"], keys);
+  }
+
+  #[test]
+  fn test_resolve_implicit_tables() {
+    let p = Parser::new();
+    let (p, _) = p.parse("[x.\"y\".z]\n");
+    let mut tracker = TableTracker::new();
+    let resolved = tracker.resolve(p.root.borrow().exprs[0].expr.table.as_ref().unwrap());
+    assert_eq!(ResolvedTable{key: "x.\"y\".z".to_string(), parent: "x.\"y\"".to_string(), segment: "z".to_string(),
+      array_key: None, implicit: vec![
+        (String::new(), "x".to_string(), "x".to_string()),
+        ("x".to_string(), "\"y\"".to_string(), "x.\"y\"".to_string()),
+      ]}, resolved);
+  }
+
+  #[test]
+  fn test_entries() {
+    let p = Parser::new();
+    let (p, _) = p.parse("a = 1\n[[b.c]]\nd = [1]\n[[b.c]]\n[b.e]\nf = {g = true}\n[b]\nh = 2.0\n");
+    assert_eq!(vec![
+      ("a".to_string(), DocEntry::Value(Value::int(1))),
+      ("b".to_string(), DocEntry::Table),
+      ("b.c[0]".to_string(), DocEntry::ArrayTable),
+      ("b.c[0].d".to_string(), DocEntry::Value(Value::Array(vec![Value::int(1)].into()))),
+      ("b.c[1]".to_string(), DocEntry::ArrayTable),
+      ("b.e".to_string(), DocEntry::Table),
+      ("b.e.f".to_string(), DocEntry::Value(Value::InlineTable(vec![("g".into(), Value::bool(true))].into()))),
+      ("b.h".to_string(), DocEntry::Value(Value::Float("2.0".into()))),
+    ], p.entries());
+  }
+
+  #[test]
+  fn test_key_lines() {
+    let p = Parser::new();
+    let (p, _) = p.parse(r#"# Comment
+a = 1
+
+[b.c]
+d = [
+  1,
+  # Two
+  [2, 3],
+]
+e = {f = """
+x"""}
+[[g]]
+[[g]]
+h = true
+"#);
+    let lines = p.key_lines();
+    let expected = [("a", 2), ("b", 4), ("b.c", 4), ("b.c.d", 5), ("b.c.d[0]", 6), ("b.c.d[1]", 8),
+      ("b.c.d[1][0]", 8), ("b.c.d[1][1]", 8), ("b.c.e", 10), ("b.c.e.f", 10), ("g", 12), ("g[0]", 12), ("g[1]", 13),
+      ("g[1].h", 14)];
+    for &(key, line) in &expected {
+      assert_eq!(Some(&line), lines.get(key), "key {key}");
+    }
+    assert_eq!(expected.len(), lines.len());
+  }
+
+  #[test]
+  fn test_table_entries() {
+    let p = Parser::new();
+    let (p, _) = p.parse("a = 1\n[[b.c]]\nd = 2\n[[b.c]]\n[b]\ne = 3\nf = 4\n");
+    assert_eq!(vec![
+      (String::new(), vec![("a".to_string(), Value::int(1))]),
+      ("b.c[0]".to_string(), vec![("b.c[0].d".to_string(), Value::int(2))]),
+      ("b.c[1]".to_string(), vec![]),
+      ("b".to_string(), vec![("b.e".to_string(), Value::int(3)), ("b.f".to_string(), Value::int(4))]),
+    ], p.table_entries());
+  }
+}
@@ -93,10 +93,17 @@ extern crate regex;
 extern crate log;
 mod internals;
 pub mod types;
+pub mod builder;
+pub mod schema;
+pub mod lint;
+pub mod ast;
 
 use std::fmt;
 use std::fmt::Display;
-use crate::types::{ParseResult, Value, Children};
+use crate::types::{ParseResult, ParseMode, Value, Children, DocIter, TableIter, Change, Conflict, DocEntry, TOMLError,
+                   KeyPath, ValueKind, ValueRef, FormatOptions};
+use crate::ast::{Visitor, VisitorMut};
+use crate::schema::{Schema, SchemaError};
 use crate::internals::parser::Parser;
 
 /// A parser, manipulator, and outputter of TOML documents.
@@ -118,6 +125,46 @@ impl<'a> TOMLParser<'a> {
     TOMLParser{parser: Parser::new()}
   }
 
+  /// Constructs a new `TOMLParser` that handles errors in documents according to `mode`. A `TOMLParser` constructed
+  /// with `new` uses `ParseMode::Recovering`, which keeps parsing after an error, skipping to the next line after a
+  /// syntax error, and reports every error with `ParseResult::FullError`. With `ParseMode::Strict` parsing stops at the
+  /// first error and the result is a `ParseResult::Failure` with the line where the error was found.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{ParseMode, ParseResult};
+  ///
+  /// let toml_doc = "a = 1\na = 2\nb = 3\n";
+  /// let (parser, result) = TOMLParser::new().parse(toml_doc);
+  /// assert!(matches!(result, ParseResult::FullError(_)));
+  /// assert_eq!(toml_doc, parser.to_string());
+  ///
+  /// let (_, result) = TOMLParser::with_mode(ParseMode::Strict).parse(toml_doc);
+  /// assert_eq!(ParseResult::Failure(2, 0), result);
+  /// let (_, result) = TOMLParser::with_mode(ParseMode::Strict).parse("a = [1, 2\n");
+  /// assert!(matches!(result, ParseResult::Failure(_, _)));
+  /// ```
+  pub fn with_mode(mode: ParseMode) -> TOMLParser<'a> {
+    TOMLParser{parser: Parser::with_mode(mode)}
+  }
+
+  /// Returns the `ParseMode` of the parser.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::ParseMode;
+  ///
+  /// assert_eq!(ParseMode::Recovering, TOMLParser::new().mode());
+  /// assert_eq!(ParseMode::Strict, TOMLParser::with_mode(ParseMode::Strict).mode());
+  /// ```
+  pub fn mode(self: &TOMLParser<'a>) -> ParseMode {
+    self.parser.mode
+  }
+
   /// Parses the string slice `input` as a TOML document. The method takes ownership of the parser and then returns it,
   /// along with the `ParseResult`, in a tuple.
   ///
@@ -136,7 +183,7 @@ impl<'a> TOMLParser<'a> {
   }
 
   /// Given a string type `key`, returns the associated `Value` or `None` if the key doesn't exist in the parsed
-  /// document.
+  /// document. Quoted key segments can be written with any quoting, e.g. `"A Key"` or `'A Key'`; see `KeyPath::parse`.
   ///
   /// # Examples
   ///
@@ -163,6 +210,32 @@ impl<'a> TOMLParser<'a> {
     self.parser.get_value(key)
   }
 
+  /// Given a string type `key`, returns a `ValueRef` view of the associated value, or `None` if the key doesn't exist in
+  /// the parsed document or is a table. Unlike `get_value` this doesn't copy the value, so it's cheaper for large
+  /// `Array`s and `InlineTable`s. The view can be indexed, iterated and copied into a `Value` with `ValueRef::to_value`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse(r#"
+  /// [server]
+  /// ports = [8080, 8081, 8082]
+  /// limits = { cpu = 2, memory = "4G" }
+  /// "#);
+  /// let ports = parser.get_value_ref("server.ports").unwrap();
+  /// assert_eq!(3, ports.len());
+  /// assert_eq!(Value::int(8081), ports.get(1).unwrap().to_value());
+  /// let memory = parser.get_value_ref("server.limits").unwrap().get_key("memory").unwrap();
+  /// assert_eq!(Value::basic_string("4G").unwrap(), memory.to_value());
+  /// assert!(parser.get_value_ref("server").is_none());
+  /// ```
+  pub fn get_value_ref<S>(self: &TOMLParser<'a>, key: S) -> Option<ValueRef<'a>> where S: Into<String> {
+    self.parser.get_value_ref(key)
+  }
+
   /// Given a string type `key` and a `Value` `val`, sets `Value` at `key` to `val` and returns true if `key` exists in
   /// the parsed document. If `key` doesn't exist in the parsed document returns false. Setting a value does not alter
   /// the document's format, including whitespace and comments, unless an `Array` or `InlineTable`'s structure is changed
@@ -194,6 +267,195 @@ impl<'a> TOMLParser<'a> {
     self.parser.set_value(key, val)
   }
 
+  /// Given a string type `key` that doesn't exist yet, adds it with the value `val` after the last key/value pair of
+  /// its table. Tables on the way to `key` that don't exist yet are added at the end of the document, or at the end of
+  /// the array of tables element they belong to. If `key` ends in an index one past the end of an array, `val` is
+  /// appended to the array. Returns false if `key` already exists or isn't a valid key path, `val` is invalid, or the
+  /// table it would belong to is a value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("title = \"x\"\nports = [80]\n");
+  /// assert!(parser.insert_value("owner.'full name'", Value::basic_string("Tom").unwrap()));
+  /// assert!(parser.insert_value("ports[1]", Value::int(443)));
+  /// assert!(!parser.insert_value("title", Value::int(1)));
+  /// assert_eq!("title = \"x\"\nports = [80, 443]\n\n[owner]\n\"full name\" = \"Tom\"\n", format!("{}", parser));
+  /// ```
+  pub fn insert_value<S>(self: &mut TOMLParser<'a>, key: S, val: Value<'a>) -> bool where S: Into<String> {
+    self.parser.insert_value(key, val)
+  }
+
+  /// Given a string type `key`, adds an empty table with the header `[key]`, or a new array of tables element with
+  /// the header `[[key]]` if `key` ends in an index one past the end of an array of tables. Returns false if the table
+  /// already has a header, `key` isn't a valid key path, or it's a value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("[[bin]]\nname = \"a\"\n");
+  /// assert!(parser.insert_table("bin[1]"));
+  /// assert!(parser.insert_table("dependencies"));
+  /// assert!(!parser.insert_table("dependencies"));
+  /// assert_eq!("[[bin]]\nname = \"a\"\n\n[[bin]]\n\n[dependencies]\n", format!("{}", parser));
+  /// ```
+  pub fn insert_table<S>(self: &mut TOMLParser<'a>, key: S) -> bool where S: Into<String> {
+    self.parser.insert_table(key)
+  }
+
+  /// Given a string type `key`, removes it from the document, whether it's a key/value pair, an element of an array,
+  /// a table along with all of its sub-tables, an array of tables, or one table of an array of tables. Comments
+  /// directly above the next table are kept. Returns false if the key doesn't exist or is the root table.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("a = [1, 2, 3]\n\n[t]\nb = 1\n\n# Servers\n[[s]]\n[[s]]\nc = 2\n");
+  /// assert!(parser.remove_key("a[1]"));
+  /// assert!(parser.remove_key("t"));
+  /// assert!(parser.remove_key("s[0]"));
+  /// assert!(!parser.remove_key("t.b"));
+  /// assert_eq!("a = [1, 3]\n\n# Servers\n[[s]]\nc = 2\n", format!("{}", parser));
+  /// ```
+  pub fn remove_key<S>(self: &mut TOMLParser<'a>, key: S) -> bool where S: Into<String> {
+    self.parser.remove_key(key)
+  }
+
+  /// Given a string type `key`, renames its last segment to `new_name`, which is quoted if it isn't a valid bare key.
+  /// Renaming a table renames it in the headers of all of its sub-tables too. Returns false if the key doesn't exist,
+  /// is an array element or the root table, `new_name` can't be used as a key, or the key's table already has a key
+  /// named `new_name`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("[server]\nip = \"10.0.0.1\" # primary\n[server.tls]\n");
+  /// assert!(parser.rename_key("server.ip", "address"));
+  /// assert!(parser.rename_key("server", "main server"));
+  /// assert!(!parser.rename_key("'main server'.address", "tls"));
+  /// assert_eq!("[\"main server\"]\naddress = \"10.0.0.1\" # primary\n[\"main server\".tls]\n", format!("{}", parser));
+  /// ```
+  pub fn rename_key<S, T>(self: &mut TOMLParser<'a>, key: S, new_name: T) -> bool
+    where S: Into<String>, T: Into<String> {
+    self.parser.rename_key(key, new_name)
+  }
+
+  /// Given a string type `key`, sets the comment at the end of the line that defines it, a key/value pair or a table
+  /// header, to `# text`, replacing any comment already there. An empty `text` removes the comment. Returns false if
+  /// the key doesn't exist, isn't defined on a line of its own (a key of an inline table, or a table implied by a
+  /// sub-table's header), or `text` has more than one line.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("[server]\nport = 80 # default\n");
+  /// assert!(parser.set_comment("server.port", "changed for the proxy"));
+  /// assert!(parser.set_comment("server", "public"));
+  /// assert_eq!("[server] # public\nport = 80 # changed for the proxy\n", format!("{}", parser));
+  /// ```
+  pub fn set_comment<S, T>(self: &mut TOMLParser<'a>, key: S, text: T) -> bool where S: Into<String>, T: Into<String> {
+    self.parser.set_comment(key, text)
+  }
+
+  /// Rewrites the whitespace of the parsed document the way `opts` describes: the indentation of key/value pairs, the
+  /// spacing around `=` and inside table headers, arrays and inline tables, the blank lines between tables and
+  /// trailing whitespace. Keys, values and comments are kept as written. See `FormatOptions` for the choices.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::FormatOptions;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("title='x'  \n[ server ]\nports=[ 80,443 ] # web\n");
+  /// parser.format(&FormatOptions{indent: 2, ..FormatOptions::default()});
+  /// assert_eq!("title = 'x'\n\n[server]\n  ports = [80, 443] # web\n", format!("{}", parser));
+  /// ```
+  pub fn format(self: &mut TOMLParser<'a>, opts: &FormatOptions) {
+    self.parser.format(opts);
+  }
+
+  /// Checks the parsed document against `schema` and returns an error for every key that doesn't match it. Returns an
+  /// empty `Vec` if the whole document matches. See `Schema` for how to describe a document.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table)
+  ///   .required_key("package", Schema::new(SchemaType::Table).required_key("name", Schema::new(SchemaType::String)));
+  /// let (parser, _) = TOMLParser::new().parse("[package]\nversion = \"0.1.0\"\n");
+  /// let errors = parser.validate(&schema);
+  /// assert_eq!(1, errors.len());
+  /// assert_eq!("package.name", errors[0].key);
+  /// assert_eq!(Some(1), errors[0].line);
+  /// ```
+  pub fn validate(self: &TOMLParser<'a>, schema: &Schema) -> Vec<SchemaError> {
+    schema.validate(self)
+  }
+
+  /// Given a string type `key`, returns what kind of table or value it refers to, or `None` if the key doesn't exist
+  /// in the parsed document. Unlike `get_value` this doesn't copy the value, and it also works for tables and arrays of
+  /// tables. The root table has the key `""`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{ValueKind, StrType};
+  ///
+  /// let (parser, _) = TOMLParser::new().parse(r#"
+  /// [[products]]
+  /// name = 'Hammer'
+  /// sizes = [1, 2, 3]
+  /// [products.stock]
+  /// count = 5
+  /// "#);
+  /// assert_eq!(Some(ValueKind::ArrayOfTables), parser.get_kind("products"));
+  /// assert_eq!(Some(ValueKind::Table), parser.get_kind("products[0]"));
+  /// assert_eq!(Some(ValueKind::Table), parser.get_kind("products[0].stock"));
+  /// assert_eq!(Some(ValueKind::String(StrType::Literal)), parser.get_kind("products[0].name"));
+  /// assert_eq!(Some(ValueKind::Array), parser.get_kind("products[0].sizes"));
+  /// assert_eq!(Some(ValueKind::Integer), parser.get_kind("products[0].sizes[2]"));
+  /// assert_eq!(None, parser.get_kind("products[1]"));
+  /// ```
+  pub fn get_kind<S>(self: &TOMLParser<'a>, key: S) -> Option<ValueKind> where S: Into<String> {
+    self.parser.get_kind(key)
+  }
+
+  /// Given a string type `key` returns the line, starting at 1, that the key is defined on in the parsed document, or
+  /// `None` if the key doesn't exist. A table that's only implied by the header of a sub-table gets the line of that
+  /// header, and an array of tables gets the line of its first element.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("title = 'Tools'\n\n[[products]]\nname = 'Hammer'\nsizes = [\n  1,\n  2,\n]\n");
+  /// assert_eq!(Some(1), parser.get_line("title"));
+  /// assert_eq!(Some(3), parser.get_line("products"));
+  /// assert_eq!(Some(4), parser.get_line("products[0].name"));
+  /// assert_eq!(Some(7), parser.get_line("products[0].sizes[1]"));
+  /// assert_eq!(None, parser.get_line("products[0].price"));
+  /// ```
+  pub fn get_line<S>(self: &TOMLParser<'a>, key: S) -> Option<usize> where S: Into<String> {
+    self.parser.get_line(key)
+  }
+
   /// Given a string type `key` returns all the child keys of the `key` if it exists in the parsed document, otherwise
   /// returns `None`.
   ///
@@ -225,6 +487,438 @@ impl<'a> TOMLParser<'a> {
   pub fn get_children<S>(self: &TOMLParser<'a>, key: S) -> Option<&Children> where S: Into<String> {
     self.parser.get_children(key)
   }
+
+  /// Like `get_value`, but returns a `TOMLError` describing the problem if `key` isn't a valid key path, instead of
+  /// `None`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("[servers]\n\"main server\" = { ip = \"10.0.0.1\" }\n");
+  /// let ip = parser.try_get_value("servers.'main server'.ip").unwrap();
+  /// assert_eq!(Some(Value::basic_string("10.0.0.1").unwrap()), ip);
+  /// assert_eq!(None, parser.try_get_value("servers.backup").unwrap());
+  /// let err = parser.try_get_value("servers.main server.ip").unwrap_err();
+  /// assert_eq!("Invalid key path \"servers.main server.ip\": expected \".\" or \"[\" at position 13",
+  ///   format!("{}", err));
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `key` isn't a valid key path.
+  pub fn try_get_value(self: &TOMLParser<'a>, key: &str) -> Result<Option<Value<'a>>, TOMLError> {
+    match self.parser.try_resolve_key(key)? {
+      Some(k) => Ok(self.parser.get_value(k)),
+      None => Ok(None),
+    }
+  }
+
+  /// Like `set_value`, but returns a `TOMLError` describing why the value couldn't be set instead of false.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("\"A Key\" = 1\n");
+  /// assert!(parser.try_set_value("'A Key'", Value::int(2)).is_ok());
+  /// assert_eq!("\"A Key\" = 2\n", format!("{}", parser));
+  /// assert!(parser.try_set_value("'A Key", Value::int(3)).is_err());
+  /// assert!(parser.try_set_value("missing", Value::int(3)).is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `key` isn't a valid key path, the key doesn't exist or doesn't have a value, or `val`
+  /// is invalid.
+  pub fn try_set_value(self: &mut TOMLParser<'a>, key: &str, val: Value<'a>) -> Result<(), TOMLError> {
+    let Some(k) = self.parser.try_resolve_key(key)? else {
+      return Err(TOMLError::new(format!("Key not found: {key}")));
+    };
+    if self.parser.get_value(k.as_str()).is_none() {
+      return Err(TOMLError::new(format!("Key doesn't have a value: {key}")));
+    }
+    let s_val = val.to_string();
+    if self.parser.set_value(k, val) {
+      Ok(())
+    } else {
+      Err(TOMLError::new(format!("Invalid value for key {key}: {s_val}")))
+    }
+  }
+
+  /// Like `get_children`, but returns a `TOMLError` describing the problem if `key` isn't a valid key path, instead
+  /// of `None`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Children;
+  /// use std::cell::Cell;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("\"my list\" = [1, 2]\n");
+  /// assert_eq!(Some(&Children::Count(Cell::new(2))), parser.try_get_children("'my list'").unwrap());
+  /// assert!(parser.try_get_children("'my list'[").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `key` isn't a valid key path.
+  pub fn try_get_children(self: &TOMLParser<'a>, key: &str) -> Result<Option<&Children>, TOMLError> {
+    match self.parser.try_resolve_key(key)? {
+      Some(k) => Ok(self.parser.get_children(k)),
+      None => Ok(None),
+    }
+  }
+
+  /// Like `get_value`, but takes a `KeyPath`, so keys never need quoting or escaping.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Value, KeyPath};
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("[[hosts]]\n\"name.with.dots\" = \"alpha\"\n");
+  /// let path = KeyPath::new().key("hosts").index(0).key("name.with.dots");
+  /// assert_eq!(Some(Value::basic_string("alpha").unwrap()), parser.get(&path));
+  /// assert_eq!(None, parser.get(&KeyPath::new().key("hosts").index(1)));
+  /// ```
+  pub fn get(self: &TOMLParser<'a>, path: &KeyPath) -> Option<Value<'a>> {
+    self.parser.resolve_key_path(path).and_then(|k| self.parser.get_value(k))
+  }
+
+  /// Like `set_value`, but takes a `KeyPath`, so keys never need quoting or escaping.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Value, KeyPath};
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("\"tab\\tkey\" = 1 # count\n");
+  /// assert!(parser.set(&KeyPath::new().key("tab\tkey"), Value::int(2)));
+  /// assert_eq!("\"tab\\tkey\" = 2 # count\n", format!("{}", parser));
+  /// ```
+  pub fn set(self: &mut TOMLParser<'a>, path: &KeyPath, val: Value<'a>) -> bool {
+    match self.parser.resolve_key_path(path) {
+      Some(k) => self.parser.set_value(k, val),
+      None => false,
+    }
+  }
+
+  /// Like `get_children`, but takes a `KeyPath`, so keys never need quoting or escaping. An empty `KeyPath` returns
+  /// the children of the root table.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Children, KeyPath};
+  /// use std::cell::RefCell;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("title = \"x\"\n[\"my table\"]\n");
+  /// assert_eq!(Some(&Children::Keys(RefCell::new(vec!["title".to_string(), "\"my table\"".to_string()]))),
+  ///   parser.children(&KeyPath::new()));
+  /// ```
+  pub fn children(self: &TOMLParser<'a>, path: &KeyPath) -> Option<&Children> {
+    self.parser.resolve_key_path(path).and_then(|k| self.parser.get_children(k))
+  }
+
+  /// Returns an iterator over every entry of the parsed document as (full key, `DocEntry`) pairs. The document is
+  /// walked depth-first: each table is followed by its key/value pairs and sub-tables, in the order they first appear in
+  /// the document, and each array of tables is yielded as its tables, in order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Value, DocEntry};
+  ///
+  /// let parser = TOMLParser::new();
+  /// let toml_doc = r#"
+  /// title = "Fruit"
+  /// [[fruit]]
+  /// name = "apple"
+  /// [fruit.physical]
+  /// color = "red"
+  /// "#;
+  /// let (parser, result) = parser.parse(toml_doc);
+  /// let entries: Vec<(String, DocEntry)> = parser.iter().collect();
+  /// assert_eq!(entries, vec![
+  ///   ("title".to_string(), DocEntry::Value(Value::basic_string("Fruit").unwrap())),
+  ///   ("fruit[0]".to_string(), DocEntry::ArrayTable),
+  ///   ("fruit[0].name".to_string(), DocEntry::Value(Value::basic_string("apple").unwrap())),
+  ///   ("fruit[0].physical".to_string(), DocEntry::Table),
+  ///   ("fruit[0].physical.color".to_string(), DocEntry::Value(Value::basic_string("red").unwrap())),
+  /// ]);
+  /// ```
+  pub fn iter(self: &TOMLParser<'a>) -> DocIter<'a> {
+    DocIter{entries: self.parser.entries().into_iter()}
+  }
+
+  /// Returns an iterator over the tables of the parsed document in the order their headers appear. Each item is the
+  /// full key of a table and its key/value pairs, as (full key, `Value`) pairs, in document order. The first item is
+  /// always the root table, which has the key `""`. Tables that are only implied by the headers of their sub-tables
+  /// aren't included.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let parser = TOMLParser::new();
+  /// let toml_doc = r#"
+  /// title = "Servers"
+  /// [servers.alpha]
+  /// ip = "10.0.0.1"
+  /// [[servers.alpha.ports]]
+  /// number = 8080
+  /// "#;
+  /// let (parser, result) = parser.parse(toml_doc);
+  /// let tables: Vec<(String, Vec<(String, Value)>)> = parser.tables().collect();
+  /// assert_eq!(tables, vec![
+  ///   ("".to_string(), vec![("title".to_string(), Value::basic_string("Servers").unwrap())]),
+  ///   ("servers.alpha".to_string(), vec![("servers.alpha.ip".to_string(), Value::basic_string("10.0.0.1").unwrap())]),
+  ///   ("servers.alpha.ports[0]".to_string(), vec![("servers.alpha.ports[0].number".to_string(), Value::int(8080))]),
+  /// ]);
+  /// ```
+  pub fn tables(self: &TOMLParser<'a>) -> TableIter<'a> {
+    TableIter{tables: self.parser.table_entries().into_iter()}
+  }
+
+  /// Returns the key and `DocEntry` of everything in the parsed document that matches `query`, or a `TOMLError` if
+  /// `query` isn't valid. A query is a key where any segment can be replaced with a pattern:
+  ///
+  /// * `*` matches every child key of a table or inline table, e.g. `dependencies.*.version`.
+  /// * `[*]` matches every element of an array or array of tables, e.g. `bin[*].name`.
+  /// * `..` before a segment or pattern matches it at any depth, e.g. `..version`.
+  /// * `[?key == value]` and `[?key != value]` match every child whose value at `key` compares to the TOML value
+  ///   `value`, e.g. `bin[?name == "tomlkit"]`. Values are compared by meaning, so `1_000` matches `1000`.
+  ///
+  /// Keys are returned as they're written in the document, and quoted and bare keys in the query match by meaning.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Value, DocEntry};
+  ///
+  /// let (parser, _) = TOMLParser::new().parse(r#"
+  /// [[bin]]
+  /// name = "tomlkit"
+  /// path = "src/bin/tomlkit.rs"
+  /// [[bin]]
+  /// name = "other"
+  /// "#);
+  /// assert_eq!(vec![
+  ///   ("bin[0].name".to_string(), DocEntry::Value(Value::basic_string("tomlkit").unwrap())),
+  ///   ("bin[1].name".to_string(), DocEntry::Value(Value::basic_string("other").unwrap())),
+  /// ], parser.query("bin[*].name").unwrap());
+  /// assert_eq!(vec![
+  ///   ("bin[0].path".to_string(), DocEntry::Value(Value::basic_string("src/bin/tomlkit.rs").unwrap())),
+  /// ], parser.query("bin[?name == \"tomlkit\"].path").unwrap());
+  /// assert!(parser.query("bin[").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `query` isn't a valid query.
+  pub fn query(self: &TOMLParser<'a>, query: &str) -> Result<Vec<(String, DocEntry<'a>)>, TOMLError> {
+    self.parser.query(query)
+  }
+
+  /// Sets every value matching `query` to `val` and returns how many values changed. See `query` for the query syntax
+  /// and `set_value` for how each value is set; formatting and comments around the values are kept. Tables and
+  /// values that already equal `val` aren't changed or counted, and neither are matches inside another match that
+  /// changes, like `a[0]` when `a` changes.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse("[servers.alpha]\nport = 80\n[servers.beta]\nport = 8080\n");
+  /// assert_eq!(1, parser.set_values("servers.*.port", Value::int(8080)).unwrap());
+  /// assert_eq!("[servers.alpha]\nport = 8080\n[servers.beta]\nport = 8080\n", format!("{}", parser));
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `query` isn't a valid query or `val` is invalid, in which case nothing is changed.
+  pub fn set_values(self: &mut TOMLParser<'a>, query: &str, val: Value<'a>) -> Result<usize, TOMLError> {
+    self.parser.set_query_values(query, |_| val.clone())
+  }
+
+  /// Sets every value matching `query` to the value `f` returns when given its current value, and returns how many
+  /// values changed. See `query` for the query syntax and `set_value` for how each value is set; formatting and
+  /// comments around the values are kept. Tables aren't passed to `f`, and values `f` returns unchanged aren't
+  /// counted. A match inside another match that changes, like `a[0]` when `a` changes, is skipped, since setting the
+  /// outer value replaces it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (mut parser, _) = TOMLParser::new().parse(r#"[dependencies]
+  /// nom = { version = "0.1.2" }
+  /// regex = "0.1.2" # pinned
+  /// log = "0.3"
+  /// "#);
+  /// let old = Value::basic_string("0.1.2").unwrap();
+  /// let count = parser.set_values_with("dependencies..version", |v| {
+  ///   if v.semantic_eq(&old) { Value::basic_string("0.1.3").unwrap() } else { v.clone() }
+  /// });
+  /// assert_eq!(1, count.unwrap());
+  /// let count = parser.set_values_with("dependencies.*", |v| {
+  ///   if v.semantic_eq(&old) { Value::basic_string("0.1.3").unwrap() } else { v.clone() }
+  /// });
+  /// assert_eq!(1, count.unwrap());
+  /// assert_eq!(r#"[dependencies]
+  /// nom = { version = "0.1.3" }
+  /// regex = "0.1.3" # pinned
+  /// log = "0.3"
+  /// "#, format!("{}", parser));
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `query` isn't a valid query or `f` returns an invalid value, in which case nothing is
+  /// changed.
+  pub fn set_values_with<F>(self: &mut TOMLParser<'a>, query: &str, f: F) -> Result<usize, TOMLError>
+    where F: FnMut(&Value<'a>) -> Value<'a> {
+    self.parser.set_query_values(query, f)
+  }
+
+  /// Compares the parsed document with `other` by meaning and returns every difference, as a `Change` with the full
+  /// key and the old and/or new `Value`. Key order, whitespace, comments and how keys and values are written don't
+  /// matter, so `1_000` equals `1000` and `'a'` equals `"a"`. `InlineTable`s are compared key by key, as if their keys
+  /// were defined in a table, while `Array`s are compared as a whole. Tables without any values aren't compared. Keys
+  /// are given as they're written in the document the value is from.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{Value, Change};
+  ///
+  /// let (old, _) = TOMLParser::new().parse("count = 1_000\nname = 'tom'\n[owner]\nage = 30\n");
+  /// let (new, _) = TOMLParser::new().parse("name = \"tom\"\ncount = 1000\nowner = {age = 31, city = \"Oslo\"}\n");
+  /// assert_eq!(vec![
+  ///   Change::Changed("owner.age".to_string(), Value::int(30), Value::int(31)),
+  ///   Change::Added("owner.city".to_string(), Value::basic_string("Oslo").unwrap()),
+  /// ], old.diff(&new));
+  /// ```
+  pub fn diff(self: &TOMLParser<'a>, other: &TOMLParser<'a>) -> Vec<Change<'a>> {
+    self.parser.diff(&other.parser)
+  }
+
+  /// Returns true if the parsed document means the same thing as `other`, i.e. `diff` finds no differences.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (first, _) = TOMLParser::new().parse("[a]\nb = 1e2\nc = [1, 2]\n");
+  /// let (second, _) = TOMLParser::new().parse("\"a\" = { c = [ 1, 2 ], b = 100.0 }");
+  /// assert!(first.semantic_eq(&second));
+  /// ```
+  pub fn semantic_eq(self: &TOMLParser<'a>, other: &TOMLParser<'a>) -> bool {
+    self.diff(other).is_empty()
+  }
+
+  /// Three-way merge of the parsed document ("ours") with `theirs`, where `base` is the document both were derived
+  /// from. Every key that was added, changed or removed between `base` and `theirs` gets the same change in this
+  /// document, unless this document already changed it differently. Changed values keep their key/value pair's
+  /// formatting and comments, added keys go at the end of their table, and new tables go at the end of the document.
+  /// Keys are compared by meaning, like `diff`. Returns a `Conflict` with the full key and the three values for every
+  /// change that couldn't be applied; these keys keep the value from this document.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (base, _) = TOMLParser::new().parse("port = 80\nhost = \"localhost\"\n");
+  /// let (mut ours, _) = TOMLParser::new().parse("port = 80 # web\nhost = \"example.com\"\n");
+  /// let (theirs, _) = TOMLParser::new().parse("port = 8080\nhost = \"127.0.0.1\"\ntls = true\n");
+  /// let conflicts = ours.merge(&base, &theirs);
+  /// assert_eq!("port = 8080 # web\nhost = \"example.com\"\ntls = true\n", format!("{}", ours));
+  /// assert_eq!(1, conflicts.len());
+  /// assert_eq!("host", conflicts[0].key);
+  /// assert_eq!(Some(Value::basic_string("127.0.0.1").unwrap()), conflicts[0].theirs);
+  /// ```
+  pub fn merge(self: &mut TOMLParser<'a>, base: &TOMLParser<'a>, theirs: &TOMLParser<'a>) -> Vec<Conflict<'a>> {
+    self.parser.merge(&base.parser, &theirs.parser)
+  }
+
+  /// Walks the syntax tree of the parsed document with `visitor`, giving it read-only access to every part of the
+  /// document, including whitespace, comments and table headers. See the `ast` module for details.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::ast::{Visitor, TableType};
+  ///
+  /// struct TableCounter(usize);
+  ///
+  /// impl<'a> Visitor<'a> for TableCounter {
+  ///   fn visit_table(&mut self, _table: &TableType<'a>) {
+  ///     self.0 += 1;
+  ///   }
+  /// }
+  ///
+  /// let parser = TOMLParser::new();
+  /// let (parser, result) = parser.parse("[a]\nkey = 1\n[[b]]\n[[b]]\n");
+  /// let mut counter = TableCounter(0);
+  /// parser.visit(&mut counter);
+  /// assert_eq!(3, counter.0);
+  /// ```
+  pub fn visit<V>(self: &TOMLParser<'a>, visitor: &mut V) where V: Visitor<'a> {
+    visitor.visit_toml(&self.parser.root.borrow());
+  }
+
+  /// Walks the syntax tree of the parsed document with `visitor`, allowing it to change any part of the document in
+  /// place. Keys and values are looked up from the changed document afterwards. See the `ast` module for details.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::ast::{VisitorMut, Comment};
+  ///
+  /// struct Shout;
+  ///
+  /// impl<'a> VisitorMut<'a> for Shout {
+  ///   fn visit_comment_mut(&mut self, comment: &mut Comment<'a>) {
+  ///     let text = comment.text().to_uppercase();
+  ///     comment.set_text(text);
+  ///   }
+  /// }
+  ///
+  /// let parser = TOMLParser::new();
+  /// let (mut parser, result) = parser.parse("key = 1 # a comment\n");
+  /// parser.visit_mut(&mut Shout);
+  /// assert_eq!("key = 1 # A COMMENT\n", format!("{}", parser));
+  /// ```
+  pub fn visit_mut<V>(self: &mut TOMLParser<'a>, visitor: &mut V) where V: VisitorMut<'a> {
+    // Table headers are shared with the parser's table tracking state, which isn't needed once parsing is done
+    self.parser.last_table = None;
+    self.parser.last_array_tables.borrow_mut().clear();
+    visitor.visit_toml_mut(&mut self.parser.root.borrow_mut());
+    self.parser.rebuild_map();
+  }
 }
 
 impl<'a> Default for TOMLParser<'a> {
@@ -0,0 +1,632 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Display;
+use crate::TOMLParser;
+use crate::types::{ParseResult, ParseError, Value, Children, StrType, TOMLError};
+use crate::ast::{Visitor, NLExpression, Expression, KeyVal, TableType, TOMLValue, Array, InlineTable};
+use crate::internals::keypath::key_name;
+
+/// A check made by a `Linter`. Each rule can be turned on or off on its own.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum Rule {
+  /// An error the parser reports, like a duplicate key, an invalid value or invalid syntax.
+  ParseError,
+  /// A key or table defined twice with different spellings, like `a` and `"a"`, which the parser treats as different
+  /// keys.
+  DuplicateKey,
+  /// A table header that adds to a table after the headers of other tables, like `[a.c]` after `[a]` and `[b]`.
+  ReopenedTable,
+  /// A string quoted with `'` in a document whose strings are mostly quoted with `"`, or the other way around.
+  /// Strings that can't be written the other way without escapes are left alone.
+  StringQuotes,
+  /// A key of a `dependencies`, `dev-dependencies` or `build-dependencies` table that isn't in alphabetical order.
+  UnsortedDependencies,
+  /// Spaces or tabs at the end of a line, outside of multi-line strings.
+  TrailingWhitespace,
+  /// A line ending in `\r\n` in a document whose lines mostly end in `\n`, or the other way around.
+  LineEndings,
+}
+
+impl Rule {
+  /// Every rule, in the order they're described.
+  pub const ALL: [Rule; 7] = [Rule::ParseError, Rule::DuplicateKey, Rule::ReopenedTable, Rule::StringQuotes,
+    Rule::UnsortedDependencies, Rule::TrailingWhitespace, Rule::LineEndings];
+
+  /// Returns the rule called `name`, like `trailing-whitespace`, or `None` if there isn't one.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::lint::Rule;
+  ///
+  /// assert_eq!(Some(Rule::TrailingWhitespace), Rule::from_name("trailing-whitespace"));
+  /// assert_eq!(None, Rule::from_name("tabs"));
+  /// ```
+  pub fn from_name(name: &str) -> Option<Rule> {
+    Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+  }
+
+  /// Returns the name of the rule, as used in lint configuration files and printed with each `Lint`.
+  pub fn name(self) -> &'static str {
+    match self {
+      Rule::ParseError => "parse-error",
+      Rule::DuplicateKey => "duplicate-key",
+      Rule::ReopenedTable => "reopened-table",
+      Rule::StringQuotes => "string-quotes",
+      Rule::UnsortedDependencies => "unsorted-dependencies",
+      Rule::TrailingWhitespace => "trailing-whitespace",
+      Rule::LineEndings => "line-endings",
+    }
+  }
+}
+
+impl Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// A problem found in a document by a `Linter`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Lint {
+  /// The rule that found the problem.
+  pub rule: Rule,
+  /// The line of the problem, starting at 1.
+  pub line: usize,
+  /// The column of the problem in characters, starting at 1, or 0 when the rest of the document couldn't be parsed.
+  pub column: usize,
+  /// What the problem is.
+  pub message: String,
+}
+
+/// Formats a `Lint` as `line:column: message (rule)`.
+impl Display for Lint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}: {} ({})", self.line, self.column, self.message, self.rule)
+  }
+}
+
+/// Finds things in TOML documents that the parser accepts but that are likely mistakes or make the document harder to
+/// read. See `Rule` for what's checked. Every rule is on unless it's turned off with `rule` or in the configuration
+/// read by `from_toml`.
+///
+/// # Examples
+///
+/// ```
+/// use tomllib::lint::{Linter, Rule};
+///
+/// let linter = Linter::new().rule(Rule::StringQuotes, false);
+/// let lints: Vec<String> = linter.lint("a = 1\n\"a\" = 2 \n").iter().map(ToString::to_string).collect();
+/// assert_eq!(vec!["2:1: \"a\" is the same key as a on line 1 (duplicate-key)",
+///   "2:8: trailing whitespace (trailing-whitespace)"], lints);
+/// ```
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Linter {
+  disabled: HashSet<Rule>,
+}
+
+impl Default for Linter {
+  fn default() -> Linter {
+    Linter::new()
+  }
+}
+
+impl Linter {
+  /// Creates a `Linter` with every rule turned on.
+  pub fn new() -> Linter {
+    Linter{disabled: HashSet::new()}
+  }
+
+  /// Turns `rule` on or off.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::lint::{Linter, Rule};
+  ///
+  /// let linter = Linter::new().rule(Rule::TrailingWhitespace, false);
+  /// assert!(!linter.is_enabled(Rule::TrailingWhitespace));
+  /// assert!(linter.lint("a = 1 \n").is_empty());
+  /// ```
+  #[must_use]
+  pub fn rule(mut self, rule: Rule, enabled: bool) -> Linter {
+    if enabled {
+      self.disabled.remove(&rule);
+    } else {
+      self.disabled.insert(rule);
+    }
+    self
+  }
+
+  /// Returns whether `rule` is turned on.
+  pub fn is_enabled(&self, rule: Rule) -> bool {
+    !self.disabled.contains(&rule)
+  }
+
+  /// Reads a `Linter` from a TOML document that turns rules on or off by name, e.g. `string-quotes = false`. Rules that
+  /// aren't mentioned are on.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::lint::{Linter, Rule};
+  ///
+  /// let linter = Linter::from_toml("string-quotes = false\nline-endings = true\n").unwrap();
+  /// assert_eq!(Linter::new().rule(Rule::StringQuotes, false), linter);
+  /// assert!(Linter::from_toml("tabs = false\n").is_err());
+  /// assert!(Linter::from_toml("string-quotes = \"no\"\n").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `toml` isn't valid TOML, or sets a key that isn't the name of a rule or to a value
+  /// that isn't a boolean.
+  pub fn from_toml(toml: &str) -> Result<Linter, TOMLError> {
+    let (parser, result) = TOMLParser::new().parse(toml);
+    if result != ParseResult::Full {
+      return Err(TOMLError::new("Invalid lint configuration: the document has syntax errors".to_string()));
+    }
+    let names = match parser.get_children("") {
+      Some(Children::Keys(keys)) => keys.borrow().clone(),
+      _ => vec![],
+    };
+    let mut linter = Linter::new();
+    for name in names {
+      let Some(rule) = Rule::from_name(&name) else {
+        return Err(TOMLError::new(format!("Unknown lint rule \"{name}\"")));
+      };
+      match parser.get_value(name.as_str()) {
+        Some(Value::Boolean(enabled)) => linter = linter.rule(rule, enabled),
+        _ => return Err(TOMLError::new(format!("Invalid lint rule \"{name}\": expected true or false"))),
+      }
+    }
+    Ok(linter)
+  }
+
+  /// Parses `doc` and returns every problem found by the rules that are turned on, ordered by line and column.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::lint::{Linter, Rule};
+  ///
+  /// let lints = Linter::new().lint("[a.b]\nx = 1\n[c]\n[a.d]\n");
+  /// assert_eq!(1, lints.len());
+  /// assert_eq!((Rule::ReopenedTable, 4, 1), (lints[0].rule, lints[0].line, lints[0].column));
+  /// assert_eq!("table \"a.d\" reopens table \"a\" from line 1 after other tables", lints[0].message);
+  /// ```
+  pub fn lint(&self, doc: &str) -> Vec<Lint> {
+    let (parser, result) = TOMLParser::new().parse(doc);
+    let mut visitor = LintVisitor::new();
+    parser.visit(&mut visitor);
+    let mut lints = parse_error_lints(&result, &visitor.keys);
+    lints.append(&mut visitor.lints);
+    lints.append(&mut string_quote_lints(&visitor.strings));
+    lints.append(&mut line_lints(doc, &visitor.ml_string_lines));
+    lints.retain(|lint| self.is_enabled(lint.rule));
+    lints.sort_by_key(|lint| (lint.line, lint.column));
+    debug!("Found {} lints", lints.len());
+    lints
+  }
+}
+
+/// Returns a lint for every error in `result`. The parser doesn't know the column of duplicate keys and invalid tables,
+/// so it's taken from `keys`, the line, key and column of every key and table header.
+fn parse_error_lints(result: &ParseResult, keys: &[(usize, String, usize)]) -> Vec<Lint> {
+  let error_lint = |line, column, message| Lint{rule: Rule::ParseError, line, column, message};
+  let (errors, stopped) = match *result {
+    ParseResult::Full => (None, None),
+    ParseResult::FullError(ref errors) => (Some(errors), None),
+    ParseResult::Partial(_, line, col) => (None, Some((line, col))),
+    ParseResult::PartialError(_, line, col, ref errors) => (Some(errors), Some((line, col))),
+    ParseResult::Failure(line, col) => (None, Some((line, col))),
+  };
+  let mut lints: Vec<Lint> = match errors {
+    Some(errors) => errors.borrow().iter().map(|err| {
+      let (line, mut col) = err.position();
+      if let ParseError::DuplicateKey(ref key, ..) | ParseError::InvalidTable(ref key, ..) = *err {
+        // A key defined twice on one line is an inline table's, and it's the later one that's the duplicate
+        let on_line = || keys.iter().rev().filter(|k| k.0 == line);
+        if let Some(k) = on_line().find(|k| k.1 == *key).or_else(|| on_line().next()) {
+          col = k.2;
+        }
+      }
+      error_lint(line, col, parse_error_message(err))
+    }).collect(),
+    None => vec![],
+  };
+  if let Some((line, col)) = stopped {
+    lints.push(error_lint(line, col, "the rest of the document couldn't be parsed".to_string()));
+  }
+  lints
+}
+
+/// Describes `err` without its position, which is part of the `Lint`.
+fn parse_error_message(err: &ParseError) -> String {
+  match *err {
+    ParseError::MixedArray(ref key, ..) => format!("array \"{key}\" has values of different types"),
+    ParseError::DuplicateKey(ref key, ..) => format!("duplicate key \"{key}\""),
+    ParseError::InvalidTable(ref key, ..) => format!("invalid or duplicate table \"{key}\""),
+    ParseError::InvalidDateTime(ref key, _, _, ref text) => format!("invalid datetime {text} for \"{key}\""),
+    ParseError::IntegerOverflow(ref key, _, _, ref text) => format!("integer {text} of \"{key}\" is too large"),
+    ParseError::IntegerUnderflow(ref key, _, _, ref text) => format!("integer {text} of \"{key}\" is too small"),
+    ParseError::InvalidInteger(ref key, _, _, ref text) => format!("invalid integer {text} for \"{key}\""),
+    ParseError::Infinity(ref key, _, _, ref text) => format!("float {text} of \"{key}\" is too large"),
+    ParseError::NegativeInfinity(ref key, _, _, ref text) => format!("float {text} of \"{key}\" is too small"),
+    ParseError::LossOfPrecision(ref key, _, _, ref text) => {
+      format!("float {text} of \"{key}\" can't be represented exactly")
+    },
+    ParseError::InvalidFloat(ref key, _, _, ref text) => format!("invalid float {text} for \"{key}\""),
+    ParseError::InvalidBoolean(ref key, _, _, ref text) => format!("boolean {text} of \"{key}\" isn't lowercase"),
+    ParseError::InvalidString(ref key, _, _, ref text, _) => format!("invalid string {text} for \"{key}\""),
+    ParseError::InvalidSyntax(_, _, ref text) => format!("invalid syntax: {}", text.trim()),
+    ParseError::GenericError(_, _, _, _, ref message) => message.clone(),
+  }
+}
+
+/// Returns a lint for every single-line string that's quoted differently from most of them and could be quoted the
+/// same way without escapes. Each string is its line, column, type and text.
+fn string_quote_lints(strings: &[(usize, usize, StrType, String)]) -> Vec<Lint> {
+  let basic = strings.iter().filter(|s| s.2 == StrType::Basic).count();
+  let literal = strings.len() - basic;
+  if basic == 0 || literal == 0 {
+    return vec![];
+  }
+  let (minority, message) = if literal > basic {
+    (StrType::Basic, "string is quoted with \" but most strings are quoted with '")
+  } else {
+    (StrType::Literal, "string is quoted with ' but most strings are quoted with \"")
+  };
+  strings.iter().filter(|&&(_, _, st, ref text)| st == minority && match st {
+    StrType::Basic => !text.contains(['\\', '\'']),
+    _ => !text.contains(['\\', '"']),
+  }).map(|&(line, column, ..)| Lint{rule: Rule::StringQuotes, line, column, message: message.to_string()}).collect()
+}
+
+/// Returns a lint for every line with trailing whitespace and every line ending that isn't the most common one.
+/// Lines in `ml_string_lines` end inside a multi-line string, so their trailing whitespace is part of the string.
+fn line_lints(doc: &str, ml_string_lines: &HashSet<usize>) -> Vec<Lint> {
+  let crlf = doc.matches("\r\n").count();
+  let lf = doc.matches('\n').count() - crlf;
+  let mut lints = vec![];
+  for (i, text) in doc.split_inclusive('\n').enumerate() {
+    let line = i + 1;
+    let (content, ending) = match text.strip_suffix("\r\n") {
+      Some(content) => (content, "\r\n"),
+      None => (text.strip_suffix('\n').unwrap_or(text), "\n"),
+    };
+    let trimmed = content.trim_end_matches([' ', '\t']);
+    if trimmed.len() < content.len() && !ml_string_lines.contains(&line) {
+      lints.push(Lint{rule: Rule::TrailingWhitespace, line, column: trimmed.chars().count() + 1,
+        message: "trailing whitespace".to_string()});
+    }
+    let column = content.chars().count() + 1;
+    if crlf > 0 && lf > 0 && content.len() < text.len() {
+      if ending == "\r\n" && crlf <= lf {
+        lints.push(Lint{rule: Rule::LineEndings, line, column,
+          message: "line ends with \\r\\n but most lines end with \\n".to_string()});
+      } else if ending == "\n" && lf < crlf {
+        lints.push(Lint{rule: Rule::LineEndings, line, column,
+          message: "line ends with \\n but most lines end with \\r\\n".to_string()});
+      }
+    }
+  }
+  lints
+}
+
+/// Walks a document keeping track of the line and column of everything it visits, and finds the problems that need
+/// the structure of the document.
+struct LintVisitor {
+  line: usize,
+  column: usize,
+  lints: Vec<Lint>,
+  /// The current table, as key names without quotes and `[i]` for an element of an array of tables, and as written.
+  table: Vec<String>,
+  table_text: String,
+  /// The keys of the inline tables and arrays being visited, below the current table.
+  path: Vec<String>,
+  path_text: String,
+  /// Every key and table header defined so far, as written and the line it's on.
+  defined: HashMap<Vec<String>, (String, usize)>,
+  array_counts: HashMap<Vec<String>, usize>,
+  /// Every table started so far, including the ones a header implies, and the line it started on.
+  opened: HashMap<Vec<String>, usize>,
+  last_header: Vec<String>,
+  last_dependency: Option<String>,
+  /// Every single-line string: its line, column, type and text.
+  strings: Vec<(usize, usize, StrType, String)>,
+  /// Every key and table header: its line, key names joined the way the parser names them in errors, and column.
+  keys: Vec<(usize, String, usize)>,
+  ml_string_lines: HashSet<usize>,
+}
+
+impl LintVisitor {
+  fn new() -> LintVisitor {
+    LintVisitor{line: 1, column: 1, lints: vec![], table: vec![], table_text: String::new(), path: vec![],
+      path_text: String::new(), defined: HashMap::new(), array_counts: HashMap::new(), opened: HashMap::new(),
+      last_header: vec![], last_dependency: None, strings: vec![], ml_string_lines: HashSet::new(), keys: vec![]}
+  }
+
+  /// Moves past `text` in the document.
+  fn advance<T: Display>(&mut self, text: T) {
+    for c in text.to_string().chars() {
+      if c == '\n' {
+        self.line += 1;
+        self.column = 1;
+      } else {
+        self.column += 1;
+      }
+    }
+  }
+
+  fn push_lint(&mut self, rule: Rule, line: usize, column: usize, message: String) {
+    self.lints.push(Lint{rule, line, column, message});
+  }
+
+  /// Records that the key or table `key`, written as `text`, is defined on `line`, and returns a lint if it was
+  /// already defined with a different spelling. A key defined twice with the same spelling is a parse error.
+  fn define(&mut self, key: Vec<String>, text: String, line: usize, column: usize) {
+    match self.defined.get(&key) {
+      Some(&(ref first, first_line)) if *first != text => {
+        let message = format!("{text} is the same key as {first} on line {first_line}");
+        self.push_lint(Rule::DuplicateKey, line, column, message);
+      },
+      Some(_) => (),
+      None => {
+        self.defined.insert(key, (text, line));
+      },
+    }
+  }
+
+  /// Resolves the keys of a table header to its key names, adding the index of the current element of every array of
+  /// tables on the way.
+  fn resolve_header(&mut self, table: &TableType) -> Vec<String> {
+    let (t, is_array) = match *table {
+      TableType::Standard(ref t) => (t, false),
+      TableType::Array(ref t) => (t, true),
+    };
+    let mut key = vec![];
+    for (i, segment) in t.keys.iter().enumerate() {
+      key.push(key_name(&segment.key));
+      if i + 1 < t.keys.len() {
+        if let Some(count) = self.array_counts.get(&key) {
+          key.push(format!("[{}]", count - 1));
+        }
+      }
+    }
+    if is_array {
+      let count = self.array_counts.entry(key.clone()).or_insert(0);
+      *count += 1;
+      key.push(format!("[{}]", *count - 1));
+    }
+    key
+  }
+}
+
+/// Joins a key written as `parent` with the key segment `child`, which is either a key or an `[i]` index.
+fn join_text(parent: &str, child: &str) -> String {
+  if parent.is_empty() || child.is_empty() || child.starts_with('[') {
+    format!("{parent}{child}")
+  } else {
+    format!("{parent}.{child}")
+  }
+}
+
+/// Joins key names and `[i]` indices into one key.
+fn join_names(names: &[String]) -> String {
+  names.iter().fold(String::new(), |parent, name| join_text(&parent, name))
+}
+
+fn is_dependencies(table: &[String]) -> bool {
+  match table.last() {
+    Some(name) => name == "dependencies" || name == "dev-dependencies" || name == "build-dependencies",
+    None => false,
+  }
+}
+
+impl<'a> Visitor<'a> for LintVisitor {
+  fn visit_nl_expression(&mut self, nl_expr: &NLExpression<'a>) {
+    self.advance(&nl_expr.nl);
+    self.visit_expression(&nl_expr.expr);
+  }
+
+  fn visit_expression(&mut self, expr: &Expression<'a>) {
+    self.advance(&expr.ws.ws1);
+    if let Some(ref keyval) = expr.keyval {
+      self.visit_keyval(keyval);
+    }
+    if let Some(ref table) = expr.table {
+      self.visit_table(table);
+    }
+    if expr.keyval.is_some() || expr.table.is_some() {
+      self.advance(&expr.ws.ws2);
+    }
+    if let Some(ref comment) = expr.comment {
+      self.advance(comment);
+    }
+    if let Some(ref invalid) = expr.invalid {
+      self.advance(invalid);
+    }
+  }
+
+  fn visit_keyval(&mut self, keyval: &KeyVal<'a>) {
+    let (line, column) = (self.line, self.column);
+    let name = key_name(&keyval.key);
+    let mut key = self.table.clone();
+    key.extend(self.path.iter().cloned());
+    key.push(name.clone());
+    let text = join_text(&join_text(&self.table_text, &self.path_text), &keyval.key);
+    self.keys.push((line, join_names(&key), column));
+    self.define(key, text, line, column);
+    if self.path.is_empty() && is_dependencies(&self.table) {
+      if let Some(ref last) = self.last_dependency {
+        if name.to_lowercase() < last.to_lowercase() {
+          let message = format!("dependency \"{name}\" should come before \"{last}\"");
+          self.lints.push(Lint{rule: Rule::UnsortedDependencies, line, column, message});
+        }
+      }
+      self.last_dependency = Some(name.clone());
+    }
+    self.advance(&keyval.key);
+    self.advance(format!("{}={}", keyval.keyval_sep.ws1, keyval.keyval_sep.ws2));
+    let path_text = self.path_text.clone();
+    self.path.push(name);
+    self.path_text = join_text(&path_text, &keyval.key);
+    self.visit_value(&keyval.val.borrow());
+    self.path.pop();
+    self.path_text = path_text;
+  }
+
+  fn visit_table(&mut self, table: &TableType<'a>) {
+    let (line, column) = (self.line, self.column);
+    let key = self.resolve_header(table);
+    let (t, is_array) = match *table {
+      TableType::Standard(ref t) => (t, false),
+      TableType::Array(ref t) => (t, true),
+    };
+    let text = t.keys.iter().map(|k| k.key.to_string()).collect::<Vec<_>>().join(".");
+    self.keys.push((line, join_names(&key), column));
+    if !is_array {
+      self.define(key.clone(), text.clone(), line, column);
+    }
+    // The array itself when adding an element to an array of tables, which doesn't reopen it
+    let array_len = if is_array { key.len() - 1 } else { key.len() };
+    for len in (1..array_len).rev() {
+      if let Some(&opened_line) = self.opened.get(&key[..len]) {
+        if !self.last_header.starts_with(&key[..len]) {
+          let parent = join_names(&key[..len]);
+          let message =
+            format!("table \"{text}\" reopens table \"{parent}\" from line {opened_line} after other tables");
+          self.push_lint(Rule::ReopenedTable, line, column, message);
+        }
+        break;
+      }
+    }
+    for len in 1..=key.len() {
+      self.opened.entry(key[..len].to_vec()).or_insert(line);
+    }
+    self.last_header = key.clone();
+    self.table = key;
+    self.table_text = text;
+    self.last_dependency = None;
+    self.advance(table);
+  }
+
+  fn visit_value(&mut self, val: &TOMLValue<'a>) {
+    match *val {
+      TOMLValue::Array(ref arr) => self.visit_array(&arr.borrow()),
+      TOMLValue::InlineTable(ref it) => self.visit_inline_table(&it.borrow()),
+      TOMLValue::String(ref text, st @ (StrType::Basic | StrType::Literal)) => {
+        self.strings.push((self.line, self.column, st, text.to_string()));
+        self.advance(val);
+      },
+      TOMLValue::String(..) => {
+        let start = self.line;
+        self.advance(val);
+        self.ml_string_lines.extend(start..self.line);
+      },
+      _ => self.advance(val),
+    }
+  }
+
+  fn visit_array(&mut self, array: &Array<'a>) {
+    self.advance("[");
+    for comment_nl in &array.comment_nls1 {
+      self.advance(comment_nl);
+    }
+    let path_text = self.path_text.clone();
+    for (i, array_value) in array.values.iter().enumerate() {
+      let index = format!("[{i}]");
+      self.path_text = join_text(&path_text, &index);
+      self.path.push(index);
+      self.visit_value(&array_value.val.borrow());
+      self.path.pop();
+      if let Some(ref sep) = array_value.array_sep {
+        self.advance(format!("{},{}", sep.ws1, sep.ws2));
+      }
+      for comment_nl in &array_value.comment_nls {
+        self.advance(comment_nl);
+      }
+    }
+    self.path_text = path_text;
+    for comment_nl in &array.comment_nls2 {
+      self.advance(comment_nl);
+    }
+    self.advance("]");
+  }
+
+  fn visit_inline_table(&mut self, inline_table: &InlineTable<'a>) {
+    self.advance(format!("{{{}", inline_table.ws.ws1));
+    for table_keyval in &inline_table.keyvals {
+      self.visit_keyval(&table_keyval.keyval);
+      if let Some(ref sep) = table_keyval.kv_sep {
+        self.advance(format!("{},{}", sep.ws1, sep.ws2));
+      }
+      for comment_nl in &table_keyval.comment_nls {
+        self.advance(comment_nl);
+      }
+    }
+    self.advance(format!("{}}}", inline_table.ws.ws2));
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::lint::{Linter, Rule};
+
+  fn lints(doc: &str) -> Vec<String> {
+    Linter::new().lint(doc).iter().map(ToString::to_string).collect()
+  }
+
+  #[test]
+  fn test_duplicate_key() {
+    assert_eq!(vec!["2:3: \"a\" is the same key as a on line 1 (duplicate-key)",
+      "4:1: \"t\".x is the same key as t.x on line 3 (duplicate-key)",
+      "6:14: \"t\".z.\"y\" is the same key as \"t\".z.y on line 6 (duplicate-key)"],
+      lints("a = 1\n  \"a\" = 2\n[t.x]\n[\"t\".x]\n[\"t\"]\nz = { y = 1, \"y\" = 2 }\n"));
+    assert_eq!(vec!["3:1: t.\"x\" is the same key as t.x on line 2 (duplicate-key)"], lints("[t]\nx = 1\n\"x\" = 2\n"));
+    assert!(lints("[[a]]\nb = 1\n[[a]]\nb = 2\n").is_empty());
+  }
+
+  #[test]
+  fn test_reopened_table() {
+    assert_eq!(vec!["5:1: table \"a.c\" reopens table \"a\" from line 1 after other tables (reopened-table)"],
+      lints("[a.b]\nx = 1\n[z]\ny = 2\n[a.c]\n"));
+    assert!(lints("[a]\n[a.b]\n[a.b.c]\n[a.d]\n[b]\n").is_empty());
+    let linter = Linter::new().rule(Rule::ParseError, false);
+    assert!(linter.lint("[[a]]\nx = 1\n[b]\n[[a]]\nx = 2\n").is_empty());
+  }
+
+  #[test]
+  fn test_string_quotes() {
+    assert_eq!(vec!["3:20: string is quoted with ' but most strings are quoted with \" (string-quotes)"],
+      lints("a = \"x\"\nb = [\"y\", 'C:\\dir', '\"q\"']\nc = { d = \"z\", e = 'w' }\nf = \"v\"\n"));
+    assert!(lints("a = 'x'\nb = \"\"\"y\"\"\"\n").is_empty());
+  }
+
+  #[test]
+  fn test_unsorted_dependencies() {
+    assert_eq!(vec!["4:1: dependency \"log\" should come before \"regex\" (unsorted-dependencies)"],
+      lints("[dependencies]\nnom = \"1.2\"\nregex = \"0.1\"\nlog = \"0.3\"\n\n[dev-dependencies]\nb = 1\nC = 2\n"));
+  }
+
+  #[test]
+  fn test_whitespace_and_line_endings() {
+    assert_eq!(vec!["1:6: trailing whitespace (trailing-whitespace)",
+      "2:6: line ends with \\r\\n but most lines end with \\n (line-endings)",
+      "6:10: trailing whitespace (trailing-whitespace)"],
+      lints("a = 1 \nb = 2\r\nc = \"\"\"x  \ny\"\"\"\nd = 3\n# comment\t\n"));
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_eq!(vec!["2:3: duplicate key \"a\" (parse-error)"], lints("a = 1\n  a = 2\n"));
+    assert_eq!(vec!["2:14: duplicate key \"t.x.y\" (parse-error)",
+      "3:1: invalid or duplicate table \"t\" (parse-error)", "5:2: invalid or duplicate table \"t\" (parse-error)"],
+      lints("[t]\nx = { y = 1, y = 2 }\n[t]\n[u]\n [ t ]\n"));
+    assert_eq!(vec!["4:1: invalid or duplicate table \"arr[0].s\" (parse-error)"],
+      lints("[[arr]]\n[arr.s]\nk = 1\n[arr.s]\n"));
+    let linter = Linter::new().rule(Rule::ParseError, false);
+    assert!(linter.lint("a = 1\na = 2\n").is_empty());
+  }
+}
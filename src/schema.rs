@@ -0,0 +1,835 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use regex::Regex;
+use crate::TOMLParser;
+use crate::types::{Value, ValueKind, Children, ParseResult, StrType, TOMLError};
+use crate::internals::parser::Parser;
+use crate::internals::build::format_key;
+use crate::internals::compare::{values_eq, string_value, escape};
+use crate::internals::keypath::key_name;
+use crate::internals::walk::join_key;
+
+/// The type of table or value a `Schema` accepts.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SchemaType {
+  /// Any table or value.
+  Any,
+  /// A table, an element of an array of tables, or an `InlineTable`.
+  Table,
+  /// An `Array` or an array of tables.
+  Array,
+  /// A `String` of any `StrType`.
+  String,
+  /// An `Integer`.
+  Integer,
+  /// A `Float`.
+  Float,
+  /// An `Integer` or a `Float`.
+  Number,
+  /// A `Boolean`.
+  Boolean,
+  /// A `DateTime`.
+  DateTime,
+}
+
+impl SchemaType {
+  fn matches(self, kind: ValueKind) -> bool {
+    matches!((self, kind),
+      (SchemaType::Any, _) |
+      (SchemaType::Table, ValueKind::Table | ValueKind::InlineTable) |
+      (SchemaType::Array, ValueKind::Array | ValueKind::ArrayOfTables) |
+      (SchemaType::String, ValueKind::String(_)) |
+      (SchemaType::Integer | SchemaType::Number, ValueKind::Integer) |
+      (SchemaType::Float | SchemaType::Number, ValueKind::Float) |
+      (SchemaType::Boolean, ValueKind::Boolean) |
+      (SchemaType::DateTime, ValueKind::DateTime))
+  }
+
+  fn from_name(name: &str) -> Option<SchemaType> {
+    match name {
+      "any" => Some(SchemaType::Any),
+      "object" | "table" => Some(SchemaType::Table),
+      "array" => Some(SchemaType::Array),
+      "string" => Some(SchemaType::String),
+      "integer" => Some(SchemaType::Integer),
+      "float" => Some(SchemaType::Float),
+      "number" => Some(SchemaType::Number),
+      "boolean" => Some(SchemaType::Boolean),
+      "datetime" => Some(SchemaType::DateTime),
+      _ => None,
+    }
+  }
+}
+
+impl Display for SchemaType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match *self {
+      SchemaType::Any => "any",
+      SchemaType::Table => "table",
+      SchemaType::Array => "array",
+      SchemaType::String => "string",
+      SchemaType::Integer => "integer",
+      SchemaType::Float => "float",
+      SchemaType::Number => "number",
+      SchemaType::Boolean => "boolean",
+      SchemaType::DateTime => "datetime",
+    };
+    write!(f, "{name}")
+  }
+}
+
+fn kind_name(kind: ValueKind) -> &'static str {
+  match kind {
+    ValueKind::Table => "table",
+    ValueKind::ArrayOfTables => "array of tables",
+    ValueKind::Array => "array",
+    ValueKind::InlineTable => "inline table",
+    ValueKind::String(_) => "string",
+    ValueKind::Integer => "integer",
+    ValueKind::Float => "float",
+    ValueKind::Boolean => "boolean",
+    ValueKind::DateTime => "datetime",
+  }
+}
+
+fn with_article(name: &str) -> String {
+  if name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+    format!("an {name}")
+  } else {
+    format!("a {name}")
+  }
+}
+
+/// A key of a document that doesn't match its `Schema`, as returned by `Schema::validate`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SchemaError {
+  /// The full key of the table or value as it's written in the document, e.g. `servers[0]."host name"`. `""` for the
+  /// root table.
+  pub key: String,
+  /// The line the key is defined on, starting at 1. For a missing key, the line of the table that should contain it,
+  /// or `None` if that's the root table.
+  pub line: Option<usize>,
+  /// What's wrong with the key.
+  pub message: String,
+}
+
+impl Display for SchemaError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let key = if self.key.is_empty() { "(root)" } else { &self.key };
+    match self.line {
+      Some(line) => write!(f, "{}: {} (line {})", key, self.message, line),
+      None => write!(f, "{}: {}", key, self.message),
+    }
+  }
+}
+
+/// Describes the tables and values a TOML document is allowed to have: required keys, types, numeric ranges, allowed
+/// values, regex patterns for strings, array lengths and whether keys the schema doesn't describe are allowed.
+///
+/// A `Schema` is built by chaining its methods, or loaded from a JSON Schema with `from_json` or from the same schema
+/// written in TOML with `from_toml`. The schema for a document describes its root table.
+///
+/// # Examples
+///
+/// ```
+/// use tomllib::TOMLParser;
+/// use tomllib::schema::{Schema, SchemaType};
+///
+/// let schema = Schema::new(SchemaType::Table)
+///   .required_key("name", Schema::new(SchemaType::String).pattern("^[a-z-]+$").unwrap())
+///   .key("port", Schema::new(SchemaType::Integer).min(1.0).max(65535.0))
+///   .key("servers", Schema::new(SchemaType::Array).min_items(1)
+///     .items(Schema::new(SchemaType::Table).required_key("host", Schema::new(SchemaType::String))))
+///   .deny_unknown_keys();
+///
+/// let (parser, _) = TOMLParser::new().parse(r#"name = "My App"
+/// port = 70000
+/// [[servers]]
+/// hots = "a.example.com"
+/// "#);
+/// let errors: Vec<String> = schema.validate(&parser).iter().map(ToString::to_string).collect();
+/// assert_eq!(vec![
+///   "name: \"My App\" doesn't match the pattern ^[a-z-]+$ (line 1)",
+///   "port: 70000 is greater than the maximum of 65535 (line 2)",
+///   "servers[0].host: required key is missing (line 3)",
+/// ], errors);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+  kind: SchemaType,
+  keys: Vec<(String, Schema)>,
+  required: Vec<String>,
+  deny_unknown: bool,
+  items: Option<Box<Schema>>,
+  min: Option<f64>,
+  max: Option<f64>,
+  min_items: Option<usize>,
+  max_items: Option<usize>,
+  values: Vec<Value<'static>>,
+  pattern: Option<Regex>,
+}
+
+impl Schema {
+  /// Constructs a new `Schema` that accepts any table or value of type `kind`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table);
+  /// ```
+  pub fn new(kind: SchemaType) -> Schema {
+    Schema{kind, keys: vec![], required: vec![], deny_unknown: false, items: None, min: None, max: None,
+      min_items: None, max_items: None, values: vec![], pattern: None}
+  }
+
+  /// Describes the key `name` of a table with `schema`. The key doesn't have to exist, but if it does it must match
+  /// `schema`. `name` is the name of the key without quotes.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("A Key", Schema::new(SchemaType::Boolean));
+  /// let (parser, _) = TOMLParser::new().parse("\"A Key\" = 1\n");
+  /// assert_eq!("\"A Key\": expected a boolean, found an integer (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn key<S>(mut self, name: S, schema: Schema) -> Schema where S: Into<String> {
+    self.keys.push((name.into(), schema));
+    self
+  }
+
+  /// Like `key`, but the key `name` must exist.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table)
+  ///   .key("owner", Schema::new(SchemaType::Table).required_key("name", Schema::new(SchemaType::String)));
+  /// let (parser, _) = TOMLParser::new().parse("[owner]\nage = 30\n");
+  /// assert_eq!("owner.name: required key is missing (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn required_key<S>(mut self, name: S, schema: Schema) -> Schema where S: Into<String> {
+    let name = name.into();
+    self.required.push(name.clone());
+    self.keys.push((name, schema));
+    self
+  }
+
+  /// Makes it an error for a table to have keys that weren't described with `key` or `required_key`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("name", Schema::new(SchemaType::String)).deny_unknown_keys();
+  /// let (parser, _) = TOMLParser::new().parse("name = 'a'\nnmae = 'b'\n");
+  /// assert_eq!("nmae: unknown key (line 2)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn deny_unknown_keys(mut self) -> Schema {
+    self.deny_unknown = true;
+    self
+  }
+
+  /// Describes every element of an array or array of tables with `schema`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table)
+  ///   .key("ports", Schema::new(SchemaType::Array).items(Schema::new(SchemaType::Integer)));
+  /// let (parser, _) = TOMLParser::new().parse("ports = [80, \"443\"]\n");
+  /// assert_eq!("ports[1]: expected an integer, found a string (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn items(mut self, schema: Schema) -> Schema {
+    self.items = Some(Box::new(schema));
+    self
+  }
+
+  /// Sets the smallest number an `Integer` or `Float` can be.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("ratio", Schema::new(SchemaType::Number).min(0.5));
+  /// let (parser, _) = TOMLParser::new().parse("ratio = 0.25\n");
+  /// assert_eq!("ratio: 0.25 is less than the minimum of 0.5 (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn min(mut self, min: f64) -> Schema {
+    self.min = Some(min);
+    self
+  }
+
+  /// Sets the largest number an `Integer` or `Float` can be.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("retries", Schema::new(SchemaType::Integer).max(5.0));
+  /// let (parser, _) = TOMLParser::new().parse("retries = 1_000\n");
+  /// assert_eq!("retries: 1_000 is greater than the maximum of 5 (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn max(mut self, max: f64) -> Schema {
+    self.max = Some(max);
+    self
+  }
+
+  /// Sets the fewest elements an array or array of tables can have.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("tags", Schema::new(SchemaType::Array).min_items(1));
+  /// let (parser, _) = TOMLParser::new().parse("tags = []\n");
+  /// assert_eq!("tags: has 0 elements, fewer than the minimum of 1 (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn min_items(mut self, min_items: usize) -> Schema {
+    self.min_items = Some(min_items);
+    self
+  }
+
+  /// Sets the most elements an array or array of tables can have.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).key("servers", Schema::new(SchemaType::Array).max_items(1));
+  /// let (parser, _) = TOMLParser::new().parse("[[servers]]\n[[servers]]\n");
+  /// assert_eq!("servers: has 2 elements, more than the maximum of 1 (line 1)",
+  ///   schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn max_items(mut self, max_items: usize) -> Schema {
+    self.max_items = Some(max_items);
+    self
+  }
+
+  /// Sets the only values a value can be. Values are compared by meaning, so `'a'` and `"a"` are the same value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let levels = vec![Value::basic_string("debug").unwrap(), Value::basic_string("info").unwrap()];
+  /// let schema = Schema::new(SchemaType::Table).key("level", Schema::new(SchemaType::String).allowed_values(levels));
+  /// let (parser, _) = TOMLParser::new().parse("level = 'trace'\n");
+  /// assert_eq!("level: 'trace' is not one of \"debug\", \"info\" (line 1)", schema.validate(&parser)[0].to_string());
+  /// ```
+  #[must_use]
+  pub fn allowed_values(mut self, values: Vec<Value<'static>>) -> Schema {
+    self.values = values;
+    self
+  }
+
+  /// Sets a regular expression that a `String` must match somewhere in its text. Use `^` and `$` to match the whole
+  /// text.
+  ///
+  /// # Errors
+  ///
+  /// Returns a `TOMLError` if `pattern` isn't a valid regular expression.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let version = Schema::new(SchemaType::String).pattern(r"^\d+\.\d+\.\d+$").unwrap();
+  /// let schema = Schema::new(SchemaType::Table).key("version", version);
+  /// let (parser, _) = TOMLParser::new().parse("version = \"1.2\"\n");
+  /// assert_eq!(r#"version: "1.2" doesn't match the pattern ^\d+\.\d+\.\d+$ (line 1)"#,
+  ///   schema.validate(&parser)[0].to_string());
+  /// assert!(Schema::new(SchemaType::String).pattern("(").is_err());
+  /// ```
+  pub fn pattern(mut self, pattern: &str) -> Result<Schema, TOMLError> {
+    match Regex::new(pattern) {
+      Ok(re) => {
+        self.pattern = Some(re);
+        Ok(self)
+      },
+      Err(e) => Err(TOMLError::new(format!("Invalid pattern \"{pattern}\": {e}"))),
+    }
+  }
+
+  /// Loads a `Schema` from a JSON Schema. The supported keywords are `type`, `properties`, `required`,
+  /// `additionalProperties` (only `false`, to deny unknown keys), `items`, `minimum`, `maximum`, `minItems`,
+  /// `maxItems`, `enum` and `pattern`. Other keywords, such as `title` and `description`, are ignored.
+  ///
+  /// Types are named as in JSON Schema, with `"object"` for tables, plus `"float"` and `"datetime"`. A `"string"` with
+  /// the `format` `"date-time"`, `"date"` or `"time"` is a `DateTime`.
+  ///
+  /// # Errors
+  ///
+  /// Returns a `TOMLError` if `json` isn't valid JSON, or uses a keyword with an unsupported or invalid value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::Schema;
+  ///
+  /// let schema = Schema::from_json(r#"{
+  ///   "type": "object",
+  ///   "required": ["title"],
+  ///   "properties": {
+  ///     "title": { "type": "string" },
+  ///     "released": { "type": "string", "format": "date-time" },
+  ///     "tags": { "type": "array", "items": { "enum": ["toml", "rust"] } }
+  ///   }
+  /// }"#).unwrap();
+  /// let (parser, _) = TOMLParser::new().parse("released = \"2016-02-01\"\ntags = ['toml', 'go']\n");
+  /// let errors: Vec<String> = schema.validate(&parser).iter().map(ToString::to_string).collect();
+  /// assert_eq!(vec![
+  ///   "title: required key is missing",
+  ///   "released: expected a datetime, found a string (line 1)",
+  ///   "tags[1]: 'go' is not one of \"toml\", \"rust\" (line 2)",
+  /// ], errors);
+  /// ```
+  pub fn from_json(json: &str) -> Result<Schema, TOMLError> {
+    match serde_json::from_str(json) {
+      Ok(json) => Schema::from_json_value(&json, ""),
+      Err(e) => Err(TOMLError::new(format!("Invalid JSON schema: {e}"))),
+    }
+  }
+
+  /// Loads a `Schema` written in TOML. The schema uses the same keywords as `from_json`, with tables in place of JSON
+  /// objects, and `"table"` can be used as the type of a table.
+  ///
+  /// # Errors
+  ///
+  /// Returns a `TOMLError` if `toml` isn't a valid TOML document, or uses a keyword with an unsupported or invalid
+  /// value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::Schema;
+  ///
+  /// let schema = Schema::from_toml(r#"
+  /// type = "table"
+  /// additionalProperties = false
+  /// [properties.server]
+  /// type = "table"
+  /// required = ["port"]
+  /// [properties.server.properties.port]
+  /// type = "integer"
+  /// minimum = 1024
+  /// "#).unwrap();
+  /// let (parser, _) = TOMLParser::new().parse("[server]\nport = 80\n[client]\n");
+  /// let errors: Vec<String> = schema.validate(&parser).iter().map(ToString::to_string).collect();
+  /// assert_eq!(vec!["server.port: 80 is less than the minimum of 1024 (line 2)", "client: unknown key (line 3)"],
+  ///   errors);
+  /// ```
+  pub fn from_toml(toml: &str) -> Result<Schema, TOMLError> {
+    let (parser, result) = TOMLParser::new().parse(toml);
+    if result != ParseResult::Full {
+      return Err(TOMLError::new("Invalid TOML schema: the document has syntax errors".to_string()));
+    }
+    Schema::from_json_value(&toml_to_json(&parser.parser, ""), "")
+  }
+
+  fn from_json_value(json: &serde_json::Value, path: &str) -> Result<Schema, TOMLError> {
+    let fail = |msg: &str| Err(TOMLError::new(format!("Invalid schema at \"{}\": {}", schema_path(path), msg)));
+    let Some(obj) = json.as_object() else {
+      return fail("a schema must be an object");
+    };
+    let mut schema = Schema::new(SchemaType::Any);
+    if let Some(kind) = obj.get("type") {
+      schema.kind = match kind.as_str().and_then(SchemaType::from_name) {
+        Some(kind) => kind,
+        None => return fail("\"type\" must be the name of a type"),
+      };
+    }
+    if let Some(format) = obj.get("format").and_then(|f| f.as_str()) {
+      if schema.kind == SchemaType::String && (format == "date-time" || format == "date" || format == "time") {
+        schema.kind = SchemaType::DateTime;
+      }
+    }
+    if let Some(properties) = obj.get("properties") {
+      let Some(properties) = properties.as_object() else {
+        return fail("\"properties\" must be an object");
+      };
+      for (name, property) in properties {
+        let property = Schema::from_json_value(property, &format!("{path}/properties/{name}"))?;
+        schema.keys.push((name.clone(), property));
+      }
+    }
+    if let Some(required) = obj.get("required") {
+      match required.as_array() {
+        Some(names) if names.iter().all(serde_json::Value::is_string) => {
+          schema.required = names.iter().filter_map(serde_json::Value::as_str).map(str::to_string).collect();
+        },
+        _ => return fail("\"required\" must be an array of strings"),
+      }
+    }
+    match obj.get("additionalProperties") {
+      Some(&serde_json::Value::Bool(allowed)) => schema.deny_unknown = !allowed,
+      Some(_) => return fail("\"additionalProperties\" must be a boolean"),
+      None => (),
+    }
+    if let Some(items) = obj.get("items") {
+      schema.items = Some(Box::new(Schema::from_json_value(items, &format!("{path}/items"))?));
+    }
+    let number = |name: &str| match obj.get(name) {
+      Some(n) => n.as_f64().map(Some).ok_or_else(|| format!("\"{name}\" must be a number")),
+      None => Ok(None),
+    };
+    let count = |name: &str| match obj.get(name) {
+      Some(n) => n.as_u64().and_then(|n| usize::try_from(n).ok()).map(Some)
+        .ok_or_else(|| format!("\"{name}\" must be a non-negative integer")),
+      None => Ok(None),
+    };
+    match (number("minimum"), number("maximum"), count("minItems"), count("maxItems")) {
+      (Ok(min), Ok(max), Ok(min_items), Ok(max_items)) => {
+        schema.min = min;
+        schema.max = max;
+        schema.min_items = min_items;
+        schema.max_items = max_items;
+      },
+      (Err(msg), _, _, _) | (_, Err(msg), _, _) | (_, _, Err(msg), _) | (_, _, _, Err(msg)) => return fail(&msg),
+    }
+    if let Some(values) = obj.get("enum") {
+      let Some(values) = values.as_array() else {
+        return fail("\"enum\" must be an array");
+      };
+      for value in values {
+        match json_to_value(value) {
+          Some(v) => schema.values.push(v),
+          None => return fail("\"enum\" values must be strings, numbers or booleans"),
+        }
+      }
+    }
+    if let Some(pattern) = obj.get("pattern") {
+      schema = match pattern.as_str() {
+        Some(p) => schema.pattern(p).or_else(|e| fail(&e.to_string()))?,
+        None => return fail("\"pattern\" must be a string"),
+      };
+    }
+    Ok(schema)
+  }
+
+  /// Checks the document of `parser` against this schema and returns an error for every key that doesn't match it, in
+  /// document order. Returns an empty `Vec` if the whole document matches.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::schema::{Schema, SchemaType};
+  ///
+  /// let schema = Schema::new(SchemaType::Table).required_key("name", Schema::new(SchemaType::String));
+  /// let (parser, _) = TOMLParser::new().parse("name = \"tomllib\"\n");
+  /// assert!(schema.validate(&parser).is_empty());
+  /// ```
+  pub fn validate(&self, parser: &TOMLParser) -> Vec<SchemaError> {
+    let lines = parser.parser.key_lines();
+    let mut errors = vec![];
+    self.check(&parser.parser, "", &lines, &mut errors);
+    errors
+  }
+
+  fn check(&self, doc: &Parser, key: &str, lines: &HashMap<String, usize>, errors: &mut Vec<SchemaError>) {
+    let Some(kind) = doc.get_kind(key) else {
+      return;
+    };
+    if !self.kind.matches(kind) {
+      let message = format!("expected {}, found {}", with_article(&self.kind.to_string()), with_article(kind_name(kind)));
+      push_error(errors, lines, key, message);
+      return;
+    }
+    match kind {
+      ValueKind::Table | ValueKind::InlineTable => self.check_table(doc, key, lines, errors),
+      ValueKind::Array | ValueKind::ArrayOfTables => self.check_array(doc, key, lines, errors),
+      _ => {
+        if let Some(val) = doc.get_value(key) {
+          self.check_value(&val, key, lines, errors);
+        }
+      },
+    }
+  }
+
+  fn check_table(&self, doc: &Parser, key: &str, lines: &HashMap<String, usize>, errors: &mut Vec<SchemaError>) {
+    let children = match doc.get_children(key) {
+      Some(Children::Keys(keys)) => keys.borrow().clone(),
+      _ => vec![],
+    };
+    for name in &self.required {
+      if !children.iter().any(|c| key_name(c) == *name) {
+        let missing_key = join_key(key, &format_key(name).unwrap_or_else(|| format!("\"{}\"", escape(name))));
+        errors.push(SchemaError{key: missing_key, line: lines.get(key).copied(),
+          message: "required key is missing".to_string()});
+      }
+    }
+    for child in &children {
+      let child_key = join_key(key, child);
+      let name = key_name(child);
+      match self.keys.iter().find(|&(n, _)| *n == name) {
+        Some((_, schema)) => schema.check(doc, &child_key, lines, errors),
+        None if self.deny_unknown => push_error(errors, lines, &child_key, "unknown key".to_string()),
+        None => (),
+      }
+    }
+  }
+
+  fn check_array(&self, doc: &Parser, key: &str, lines: &HashMap<String, usize>, errors: &mut Vec<SchemaError>) {
+    let len = match doc.get_children(key) {
+      Some(Children::Count(count)) => count.get(),
+      _ => 0,
+    };
+    if let Some(min_items) = self.min_items {
+      if len < min_items {
+        push_error(errors, lines, key, format!("has {len} elements, fewer than the minimum of {min_items}"));
+      }
+    }
+    if let Some(max_items) = self.max_items {
+      if len > max_items {
+        push_error(errors, lines, key, format!("has {len} elements, more than the maximum of {max_items}"));
+      }
+    }
+    if let Some(ref items) = self.items {
+      for i in 0..len {
+        items.check(doc, &Children::combine_keys_index(key, i), lines, errors);
+      }
+    }
+  }
+
+  fn check_value(&self, val: &Value, key: &str, lines: &HashMap<String, usize>, errors: &mut Vec<SchemaError>) {
+    let number = match *val {
+      Value::Integer(ref n) | Value::Float(ref n) => n.replace('_', "").parse::<f64>().ok(),
+      _ => None,
+    };
+    if let Some(n) = number {
+      if let Some(min) = self.min.filter(|&min| n < min) {
+        push_error(errors, lines, key, format!("{val} is less than the minimum of {min}"));
+      }
+      if let Some(max) = self.max.filter(|&max| n > max) {
+        push_error(errors, lines, key, format!("{val} is greater than the maximum of {max}"));
+      }
+    }
+    if !self.values.is_empty() && !self.values.iter().any(|v| values_eq(v, val)) {
+      let allowed: Vec<String> = self.values.iter().map(ToString::to_string).collect();
+      push_error(errors, lines, key, format!("{} is not one of {}", val, allowed.join(", ")));
+    }
+    if let (Some(ref re), &Value::String(ref s, st)) = (&self.pattern, val) {
+      if !re.is_match(&string_value(s, st)) {
+        push_error(errors, lines, key, format!("{val} doesn't match the pattern {re}"));
+      }
+    }
+  }
+}
+
+fn push_error(errors: &mut Vec<SchemaError>, lines: &HashMap<String, usize>, key: &str, message: String) {
+  debug!("Schema error at {key}: {message}");
+  errors.push(SchemaError{key: key.to_string(), line: lines.get(key).copied(), message});
+}
+
+fn schema_path(path: &str) -> &str {
+  if path.is_empty() { "/" } else { path }
+}
+
+/// Converts an `enum` value of a JSON schema to a `Value`.
+fn json_to_value(json: &serde_json::Value) -> Option<Value<'static>> {
+  match *json {
+    serde_json::Value::String(ref s) => Some(Value::String(escape(s).into(), StrType::Basic)),
+    serde_json::Value::Bool(b) => Some(Value::Boolean(b)),
+    serde_json::Value::Number(ref n) => match n.as_i64() {
+      Some(i) => Some(Value::int(i)),
+      None => n.as_f64().map(|f| Value::Float(format!("{f:?}").into())),
+    },
+    _ => None,
+  }
+}
+
+/// Converts the table or value at `key` of a TOML schema to the JSON it stands for.
+fn toml_to_json(doc: &Parser, key: &str) -> serde_json::Value {
+  match doc.get_kind(key) {
+    Some(ValueKind::Table | ValueKind::InlineTable) => {
+      let mut obj = serde_json::Map::new();
+      if let Some(Children::Keys(children)) = doc.get_children(key) {
+        for child in children.borrow().iter() {
+          obj.insert(key_name(child), toml_to_json(doc, &join_key(key, child)));
+        }
+      }
+      serde_json::Value::Object(obj)
+    },
+    Some(ValueKind::Array | ValueKind::ArrayOfTables) => {
+      let len = match doc.get_children(key) {
+        Some(Children::Count(count)) => count.get(),
+        _ => 0,
+      };
+      serde_json::Value::Array((0..len).map(|i| toml_to_json(doc, &Children::combine_keys_index(key, i))).collect())
+    },
+    _ => match doc.get_value(key) {
+      Some(Value::String(ref s, st)) => serde_json::Value::String(string_value(s, st)),
+      Some(Value::Integer(ref n)) => n.replace('_', "").parse::<i64>()
+        .map_or(serde_json::Value::Null, serde_json::Value::from),
+      Some(Value::Float(ref n)) => n.replace('_', "").parse::<f64>().ok().and_then(serde_json::Number::from_f64)
+        .map_or(serde_json::Value::Null, serde_json::Value::Number),
+      Some(Value::Boolean(b)) => serde_json::Value::Bool(b),
+      Some(ref val) => serde_json::Value::String(val.to_string()),
+      None => serde_json::Value::Null,
+    },
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::TOMLParser;
+  use crate::types::Value;
+  use crate::schema::{Schema, SchemaType, SchemaError};
+
+  fn messages(schema: &Schema, doc: &str) -> Vec<String> {
+    let (parser, _) = TOMLParser::new().parse(doc);
+    schema.validate(&parser).iter().map(ToString::to_string).collect()
+  }
+
+  #[test]
+  fn test_validate() {
+    let schema = Schema::new(SchemaType::Table)
+      .required_key("title", Schema::new(SchemaType::String))
+      .key("owner", Schema::new(SchemaType::Table).deny_unknown_keys()
+        .required_key("name", Schema::new(SchemaType::String))
+        .key("dob", Schema::new(SchemaType::DateTime)))
+      .key("ports", Schema::new(SchemaType::Array).min_items(1).max_items(2)
+        .items(Schema::new(SchemaType::Integer).min(1.0).max(65535.0)))
+      .key("products", Schema::new(SchemaType::Array).items(Schema::new(SchemaType::Table)
+        .required_key("sku", Schema::new(SchemaType::Integer))
+        .key("color", Schema::new(SchemaType::String)
+          .allowed_values(vec![Value::basic_string("red").unwrap(), Value::basic_string("gray").unwrap()]))));
+    assert!(messages(&schema, r#"title = "TOML"
+ports = [80]
+[owner]
+name = "Tom"
+dob = 1979-05-27T07:32:00Z
+[[products]]
+sku = 1
+color = 'gray'
+"#).is_empty());
+    assert_eq!(vec![
+      "title: required key is missing",
+      "ports: has 3 elements, more than the maximum of 2 (line 1)",
+      "ports[2]: 0 is less than the minimum of 1 (line 4)",
+      "owner.dob: expected a datetime, found a string (line 8)",
+      "owner.nick: unknown key (line 9)",
+      "products[0].sku: required key is missing (line 10)",
+      "products[1].sku: expected an integer, found a float (line 13)",
+      "products[1].color: \"blue\" is not one of \"red\", \"gray\" (line 14)",
+    ], messages(&schema, r#"ports = [
+  80,
+  443,
+  0,
+]
+[owner]
+name = "Tom"
+dob = "1979-05-27"
+nick = "T"
+[[products]]
+
+[[products]]
+sku = 2.0
+color = "blue"
+"#));
+    assert_eq!(vec!["owner: expected a table, found an integer (line 2)"],
+      messages(&schema, "title = 'a'\nowner = 1\n"));
+  }
+
+  #[test]
+  fn test_validate_inline_tables() {
+    let point = Schema::new(SchemaType::Table).deny_unknown_keys()
+      .required_key("x", Schema::new(SchemaType::Number))
+      .required_key("the y", Schema::new(SchemaType::Number));
+    let schema = Schema::new(SchemaType::Table).key("points", Schema::new(SchemaType::Array).items(point));
+    let (parser, _) = TOMLParser::new().parse("points = [\n  {x = 1, \"the y\" = 2.5},\n  {x = 1, z = 2},\n]\n");
+    assert_eq!(vec![
+      SchemaError{key: "points[1].\"the y\"".to_string(), line: Some(3), message: "required key is missing".to_string()},
+      SchemaError{key: "points[1].z".to_string(), line: Some(3), message: "unknown key".to_string()},
+    ], schema.validate(&parser));
+  }
+
+  #[test]
+  fn test_from_json() {
+    let schema = Schema::from_json(r#"{
+      "$schema": "http://json-schema.org/draft-07/schema#",
+      "type": "object",
+      "required": ["name"],
+      "additionalProperties": false,
+      "properties": {
+        "name": { "type": "string", "pattern": "^[a-z]+$" },
+        "weight": { "type": "number", "minimum": 0.5, "maximum": 10 },
+        "sizes": { "type": "array", "maxItems": 2, "items": { "enum": [1, 2.5, true] } }
+      }
+    }"#).unwrap();
+    assert!(messages(&schema, "name = 'abc'\nweight = 1\nsizes = [2.5, true]\n").is_empty());
+    assert_eq!(vec![
+      "name: 'ABC' doesn't match the pattern ^[a-z]+$ (line 1)",
+      "weight: 1.0e2 is greater than the maximum of 10 (line 2)",
+      "sizes: has 3 elements, more than the maximum of 2 (line 3)",
+      "sizes[2]: 3 is not one of 1, 2.5, true (line 3)",
+      "extra: unknown key (line 4)",
+    ], messages(&schema, "name = 'ABC'\nweight = 1.0e2\nsizes = [1, true, 3]\nextra = 1\n"));
+    for bad in &["[]", "{\"type\": \"text\"}", "{\"required\": [1]}", "{\"minimum\": \"1\"}", "{\"maxItems\": -1}",
+      "{\"pattern\": \"(\"}", "{\"properties\": {\"a\": {\"enum\": [null]}}}", "{\"type\": "] {
+      assert!(Schema::from_json(bad).is_err(), "schema {}", bad);
+    }
+    assert_eq!("Invalid schema at \"/properties/a\": \"enum\" values must be strings, numbers or booleans",
+      Schema::from_json("{\"properties\": {\"a\": {\"enum\": [null]}}}").unwrap_err().to_string());
+  }
+
+  #[test]
+  fn test_from_toml() {
+    let schema = Schema::from_toml(r#"type = "table"
+required = ["servers"]
+[properties.servers.items.properties]
+host = { type = "string" }
+port = { type = "integer", maximum = 65_535 }
+[properties.servers.items]
+type = "table"
+[properties.servers]
+type = "array"
+minItems = 1
+"#).unwrap();
+    assert!(messages(&schema, "[[servers]]\nhost = \"a\"\nport = 80\n").is_empty());
+    assert_eq!(vec!["servers[0].port: 99999 is greater than the maximum of 65535 (line 2)"],
+      messages(&schema, "[[servers]]\nport = 99999\n"));
+    assert_eq!(vec!["servers: has 0 elements, fewer than the minimum of 1 (line 1)"],
+      messages(&schema, "servers = []\n"));
+    assert!(Schema::from_toml("type = \n").is_err());
+    assert!(Schema::from_toml("type = 'text'\n").is_err());
+  }
+}
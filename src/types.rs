@@ -7,6 +7,10 @@ use std::fmt::Display;
 use std::str::FromStr;
 use std::borrow::Cow;
 use crate::internals::parser::Parser;
+use crate::internals::ast::structs::TOMLValue;
+use crate::internals::keypath::{parse_key_path, key_name};
+use crate::internals::compare::escape;
+use crate::internals::primitives::is_keychar;
 use nom::IResult;
 
 /// Conveys the result of a parse operation on a TOML document
@@ -14,18 +18,23 @@ use nom::IResult;
 pub enum ParseResult<'a> {
   /// The entire input was parsed without error.
   Full,
-  /// The entire input was parsed, but there were errors. Contains an `Rc<RefCell<Vec>>` of `ParseError`s.
+  /// The entire input was parsed, but there were errors. Contains an `Rc<RefCell<Vec>>` of `ParseError`s. With
+  /// `ParseMode::Recovering`, the mode of `TOMLParser::new`, this includes documents with syntax errors: each line
+  /// that can't be parsed is skipped and reported as a `ParseError::InvalidSyntax` with its line and column.
   FullError(Rc<RefCell<Vec<ParseError<'a>>>>),
   /// Part of the input was parsed successfully without any errors. Contains a `Cow<str>`, with the leftover, unparsed
-  /// input, the line number and column (currently column reporting is unimplemented and will always report `0`) where
-  /// parsing stopped.
+  /// input, and the line number and column where parsing stopped. The column is always `0`, since parsing stops at the
+  /// start of a line. Syntax errors don't end parsing in either `ParseMode`: `ParseMode::Recovering` reports them with
+  /// `FullError` and `ParseMode::Strict` with `Failure`.
   Partial(Cow<'a, str>, usize, usize),
   /// Part of the input was parsed successfully with errors. Contains a `Cow<str>`, with the leftover, unparsed input,
-  /// the line number and column (currently column reporting is unimplemented and will always report `0`) where parsing
-  /// stopped, and an `Rc<RefCell<Vec>>` of `ParseError`s.
+  /// the line number and column where parsing stopped, which is always `0` like for `Partial`, and an
+  /// `Rc<RefCell<Vec>>` of `ParseError`s.
   PartialError(Cow<'a, str>, usize, usize, Rc<RefCell<Vec<ParseError<'a>>>>),
-  /// The parser failed to parse any of the input as a complete TOML document. Contains the line number and column
-  /// (currently column reporting is unimplemented and will always report `0`) where parsing stopped.
+  /// The parser failed to parse the input as a complete TOML document. Contains the line number and column where
+  /// parsing stopped. With `ParseMode::Strict` that's the position of the first error, as given by
+  /// `ParseError::position`, so the column is `0` for errors that aren't reported with one. Otherwise the column is
+  /// always `0`.
   Failure(usize, usize),
 }
 
@@ -58,34 +67,99 @@ pub enum ParseError<'a> {
   /// * Greater than 23 for offset hour
   /// * Greater than 59 for offset minute
   ///
-  /// Contains the `String` key of the invalid `DateTime`, the line number and column (currently column reporting is
-  /// unimplemented and will always report `0`) where the invalid `DateTime` was found, and a Cow<str> containing the
-  /// invalid `DateTime` string.
+  /// Contains the `String` key of the invalid `DateTime`, the line number and column where the invalid `DateTime` was
+  /// found, and a Cow<str> containing the invalid `DateTime` string.
   InvalidDateTime(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an integer overflow is detected.
+  /// An integer greater than the largest `i64` was encountered. Contains the `String` key of the integer, the line
+  /// number and column where it was found, and a `Cow<str>` containing the integer.
   IntegerOverflow(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an integer underflow is detected.
+  /// An integer less than the smallest `i64` was encountered. Contains the `String` key of the integer, the line number
+  /// and column where it was found, and a `Cow<str>` containing the integer.
   IntegerUnderflow(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an invalid integer representation is detected.
+  /// An integer with leading zeros or misplaced underscores was encountered. Contains the `String` key of the integer,
+  /// the line number and column where it was found, and a `Cow<str>` containing the integer.
   InvalidInteger(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when a float value of infinity is detected.
+  /// A float too large to be represented by an `f64` was encountered. Contains the `String` key of the float, the line
+  /// number and column where it was found, and a `Cow<str>` containing the float.
   Infinity(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when a float value of negative infinity is detected.
+  /// A negative float too large to be represented by an `f64` was encountered. Contains the `String` key of the float,
+  /// the line number and column where it was found, and a `Cow<str>` containing the float.
   NegativeInfinity(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when a float string conversion to an `f64` would result in a loss
-  /// of precision.
+  /// A float that can't be converted to an `f64` and back without changing its value was encountered, like one with
+  /// more significant digits than an `f64` holds. Contains the `String` key of the float, the line number and column
+  /// where it was found, and a `Cow<str>` containing the float.
   LossOfPrecision(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an invalid float representation is detected.
+  /// A float with leading zeros, misplaced underscores or a missing fraction or exponent was encountered, like `1.` or
+  /// `01.5`. Contains the `String` key of the float, the line number and column where it was found, and a `Cow<str>`
+  /// containing the float.
   InvalidFloat(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an invalid `true` or `false` string is detected.
+  /// A boolean that isn't written in lowercase, like `True`, was encountered. The value is read as the boolean it
+  /// spells and written back as it was found. Contains the `String` key of the boolean, the line number and column
+  /// where it was found, and a `Cow<str>` containing the boolean as written.
   InvalidBoolean(String, usize, usize, Cow<'a, str>),
-  /// *Currently unimplemented*. Reserved for future use when an invalid string representation is detected.
+  /// A basic or multi-line basic string with an invalid escape sequence or an unescaped control character was
+  /// encountered. The string is kept as written. Contains the `String` key of the string, the line number and column
+  /// where it was found, a `Cow<str>` containing the string with its quotes, and the `StrType` of the string.
   InvalidString(String, usize, usize, Cow<'a, str>, StrType),
-  /// *Currently unimplemented*. Reserved for future use when new error types are added without resorting to a breaking
-  /// change.
+  /// Text that isn't valid TOML syntax was encountered. Parsing skips the rest of the line and carries on with the next
+  /// one, so every syntax error in a document is reported. Contains the line number and column (starting at `1`) where
+  /// the invalid text starts, and a `Cow<str>` containing the invalid text.
+  InvalidSyntax(usize, usize, Cow<'a, str>),
+  /// Reserved for errors added later without resorting to a breaking change. The parser doesn't currently report it.
   GenericError(String, usize, usize, Option<Cow<'a, str>>, String),
 }
 
+impl ParseError<'_> {
+  /// Returns the line number and column where the error was found. Columns start at `1`; `MixedArray`, `DuplicateKey`
+  /// and `InvalidTable` errors don't report a column yet and always report `0`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::ParseResult;
+  ///
+  /// let (_, result) = TOMLParser::new().parse("a = 1\na = 2\n");
+  /// if let ParseResult::FullError(errors) = result {
+  ///   assert_eq!((2, 0), errors.borrow()[0].position());
+  /// } else {
+  ///   panic!("Expected a duplicate key error");
+  /// }
+  /// ```
+  pub fn position(&self) -> (usize, usize) {
+    match *self {
+      ParseError::MixedArray(_, line, col) |
+      ParseError::DuplicateKey(_, line, col, _) |
+      ParseError::InvalidTable(_, line, col, _) |
+      ParseError::InvalidDateTime(_, line, col, _) |
+      ParseError::IntegerOverflow(_, line, col, _) |
+      ParseError::IntegerUnderflow(_, line, col, _) |
+      ParseError::InvalidInteger(_, line, col, _) |
+      ParseError::Infinity(_, line, col, _) |
+      ParseError::NegativeInfinity(_, line, col, _) |
+      ParseError::LossOfPrecision(_, line, col, _) |
+      ParseError::InvalidFloat(_, line, col, _) |
+      ParseError::InvalidBoolean(_, line, col, _) |
+      ParseError::InvalidString(_, line, col, _, _) |
+      ParseError::InvalidSyntax(line, col, _) |
+      ParseError::GenericError(_, line, col, _, _) => (line, col),
+    }
+  }
+}
+
+/// Selects how a `TOMLParser` handles errors in a document. See `TOMLParser::with_mode`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum ParseMode {
+  /// Keep parsing after an error, so that as much of the document as possible can be read and changed. A syntax error
+  /// is reported as a `ParseError::InvalidSyntax` and parsing resumes on the next line, so every error in the document
+  /// is reported with `ParseResult::FullError`. This is the default.
+  #[default]
+  Recovering,
+  /// Stop at the first error, like a conforming TOML decoder. Any document with errors results in a
+  /// `ParseResult::Failure` with the position of the first error.
+  Strict,
+}
+
 // Represents the 7 different types of values that can exist in a TOML document.
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -266,6 +340,584 @@ impl Children {
     }
     all_keys
   }
+
+  /// Adds each child of an instance of `Children` to `base_path` to form a `Vec` of full `KeyPath`s, removing any
+  /// quotes from child keys.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::KeyPath;
+  /// let (parser, _) = TOMLParser::new().parse("[servers]\n\"main server\" = 1\nbackup = 2\n");
+  /// let servers = KeyPath::new().key("servers");
+  /// assert_eq!(vec![servers.clone().key("main server"), servers.clone().key("backup")],
+  ///   parser.children(&servers).unwrap().combine_child_paths(&servers));
+  /// ```
+  pub fn combine_child_paths(&self, base_path: &KeyPath) -> Vec<KeyPath> {
+    match *self {
+      Children::Count(ref c) => (0..c.get()).map(|i| base_path.clone().index(i)).collect(),
+      Children::Keys(ref keys) => keys.borrow().iter().map(|k| base_path.clone().key(key_name(k))).collect(),
+    }
+  }
+}
+
+/// What kind of thing a key refers to, as returned by `TOMLParser::get_kind`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ValueKind {
+  /// A table, defined with a `[table]` header, implied by the header of a sub-table, or an element of an array of
+  /// tables. The root table has the key `""`.
+  Table,
+  /// An array of tables, defined with `[[table]]` headers.
+  ArrayOfTables,
+  /// An `Array` value.
+  Array,
+  /// An `InlineTable` value.
+  InlineTable,
+  /// A `String` value of the contained `StrType`.
+  String(StrType),
+  /// An `Integer` value.
+  Integer,
+  /// A `Float` value.
+  Float,
+  /// A `Boolean` value.
+  Boolean,
+  /// A `DateTime` value.
+  DateTime,
+}
+
+/// A segment of a `KeyPath`.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum KeySegment {
+  /// A key of a table or inline table, without quotes and with any escape sequences replaced by the characters they
+  /// stand for.
+  Key(String),
+  /// An index into an array or array of tables.
+  Index(usize),
+}
+
+/// A key parsed into its segments, so that keys written with different quoting, e.g. `"A Key"` and `'A Key'`, are the
+/// same `KeyPath`.
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Default)]
+pub struct KeyPath {
+  segments: Vec<KeySegment>,
+}
+
+impl KeyPath {
+  /// Parses a key like `fruit[0]."physical traits".'color'` into a `KeyPath`. Segments are separated by dots and can be
+  /// bare, basic-quoted or literal-quoted keys, each followed by any number of `[n]` array indices. Whitespace around
+  /// dots and indices is ignored. An empty string is the root table. A path can also start with indices, like
+  /// `[0].a`, so that every `KeyPath` parses back from how it's displayed, but such a path never matches a key since
+  /// the root table isn't an array.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::{KeyPath, KeySegment};
+  ///
+  /// let path = KeyPath::parse("fruit[0].\"physical traits\".'color'").unwrap();
+  /// assert_eq!(&[
+  ///   KeySegment::Key("fruit".to_string()),
+  ///   KeySegment::Index(0),
+  ///   KeySegment::Key("physical traits".to_string()),
+  ///   KeySegment::Key("color".to_string()),
+  /// ], path.segments());
+  /// assert_eq!(path, KeyPath::parse("'fruit' [0].'physical traits'.\"color\"").unwrap());
+  /// assert!(KeyPath::parse("fruit[0].physical traits").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` describing the problem and where it is if `path` isn't a valid key path.
+  pub fn parse(path: &str) -> Result<KeyPath, TOMLError> {
+    Ok(KeyPath{segments: parse_key_path(path)?})
+  }
+
+  /// Creates an empty `KeyPath`, which is the root table. Use `key` and `index` to add segments to it.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::KeyPath;
+  ///
+  /// let path = KeyPath::new().key("servers").key("main.server").index(2).key("ip");
+  /// assert_eq!("servers.\"main.server\"[2].ip", path.to_string());
+  /// ```
+  pub fn new() -> KeyPath {
+    KeyPath{segments: vec![]}
+  }
+
+  /// Returns the `KeyPath` with the table or inline table key `key` added to the end. `key` is used as is, so it
+  /// doesn't need any quotes or escaping.
+  #[must_use]
+  pub fn key<S>(mut self, key: S) -> KeyPath where S: Into<String> {
+    self.segments.push(KeySegment::Key(key.into()));
+    self
+  }
+
+  /// Returns the `KeyPath` with the array or array of tables index `index` added to the end.
+  #[must_use]
+  pub fn index(mut self, index: usize) -> KeyPath {
+    self.segments.push(KeySegment::Index(index));
+    self
+  }
+
+  /// Returns the segments of the key path.
+  pub fn segments(&self) -> &[KeySegment] {
+    &self.segments
+  }
+}
+
+impl From<Vec<KeySegment>> for KeyPath {
+  fn from(segments: Vec<KeySegment>) -> KeyPath {
+    KeyPath{segments}
+  }
+}
+
+/// Parses a `KeyPath` with `KeyPath::parse`.
+impl FromStr for KeyPath {
+  type Err = TOMLError;
+
+  fn from_str(s: &str) -> Result<KeyPath, TOMLError> {
+    KeyPath::parse(s)
+  }
+}
+
+/// Formats a `KeyPath` in canonical form: keys are bare when possible and basic-quoted otherwise, and indices follow
+/// their key without whitespace, e.g. `fruit[0]."physical traits".color`. The root table is an empty string.
+impl Display for KeyPath {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, segment) in self.segments.iter().enumerate() {
+      match *segment {
+        KeySegment::Key(ref key) => {
+          if i > 0 {
+            write!(f, ".")?;
+          }
+          if !key.is_empty() && key.chars().all(is_keychar) {
+            write!(f, "{key}")?;
+          } else {
+            write!(f, "\"{}\"", escape(key))?;
+          }
+        },
+        KeySegment::Index(index) => write!(f, "[{index}]")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+/// An entry of a parsed TOML document, as yielded by `TOMLParser::iter`.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DocEntry<'a> {
+  /// A table, either defined with a `[table]` header or implied by the header of one of its sub-tables.
+  Table,
+  /// A table in an array of tables, defined with a `[[table]]` header. Its key ends with its index in the array, e.g.
+  /// `fruit[1]`.
+  ArrayTable,
+  /// An array of tables as a whole. Only returned by `TOMLParser::query`, `TOMLParser::iter` yields its tables
+  /// instead.
+  ArrayOfTables,
+  /// A key/value pair. Contains the `Value`. `Array`s and `InlineTable`s are yielded as a single entry.
+  Value(Value<'a>),
+}
+
+/// A difference between two TOML documents, as returned by `TOMLParser::diff`. Each variant contains the full key of
+/// the value that differs.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Change<'a> {
+  /// The key only exists in the new document. Contains the key and its `Value`.
+  Added(String, Value<'a>),
+  /// The key only exists in the old document. Contains the key and its `Value`.
+  Removed(String, Value<'a>),
+  /// The key exists in both documents with values that mean different things. Contains the key, the old `Value` and
+  /// the new `Value`.
+  Changed(String, Value<'a>, Value<'a>),
+}
+
+/// Formats a `Change` as a line of a diff: `+ key = value` for an added key, `- key = value` for a removed key and
+/// `~ key = old -> new` for a changed key.
+impl Display for Change<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Change::Added(ref key, ref val) => write!(f, "+ {key} = {val}"),
+      Change::Removed(ref key, ref val) => write!(f, "- {key} = {val}"),
+      Change::Changed(ref key, ref old, ref new) => write!(f, "~ {key} = {old} -> {new}"),
+    }
+  }
+}
+
+/// A key that was changed differently in both documents of a three-way merge, as returned by `TOMLParser::merge`.
+/// Each `Value` is `None` if the key doesn't exist in that document.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Conflict<'a> {
+  /// The full key, as written in the base document, or in "their" document if the key was added.
+  pub key: String,
+  /// The `Value` in the common ancestor of both documents.
+  pub base: Option<Value<'a>>,
+  /// The `Value` in the document being merged into, which is kept.
+  pub ours: Option<Value<'a>>,
+  /// The `Value` in the document being merged from.
+  pub theirs: Option<Value<'a>>,
+}
+
+/// Formats a `Conflict` as `key: base = value, ours = value, theirs = value`, with `(none)` for a missing value.
+impl<'a> Display for Conflict<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let fmt_val = |val: &Option<Value<'a>>| match *val {
+      Some(ref v) => v.to_string(),
+      None => "(none)".to_string(),
+    };
+    write!(f, "{}: base = {}, ours = {}, theirs = {}", self.key, fmt_val(&self.base), fmt_val(&self.ours),
+      fmt_val(&self.theirs))
+  }
+}
+
+/// How `TOMLParser::format` lays out a document. Only whitespace changes: keys, values and comments are kept as
+/// written.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct FormatOptions {
+  /// The number of spaces before each key/value pair and comment in a table. Keys of the root table aren't indented.
+  pub indent: usize,
+  /// The number of spaces each element of an array that spans several lines is indented by, relative to the line the
+  /// array starts on.
+  pub array_indent: usize,
+  /// Whether there's a space on both sides of the `=` of a key/value pair.
+  pub space_around_eq: bool,
+  /// Whether arrays on one line have a space after `[` and before `]`.
+  pub array_padding: bool,
+  /// Whether inline tables have a space after `{` and before `}`.
+  pub inline_table_padding: bool,
+  /// The number of blank lines before each table header, or before the comments right above it.
+  pub blank_lines_between_tables: usize,
+  /// The most blank lines kept in a row anywhere else.
+  pub max_blank_lines: usize,
+}
+
+impl Default for FormatOptions {
+  fn default() -> FormatOptions {
+    FormatOptions{indent: 0, array_indent: 2, space_around_eq: true, array_padding: false, inline_table_padding: true,
+      blank_lines_between_tables: 1, max_blank_lines: 1}
+  }
+}
+
+// The largest number of spaces or blank lines FormatOptions::from_toml accepts, so that a typo can't make format
+// allocate gigabytes of whitespace
+const MAX_FORMAT_NUMBER: usize = 100;
+
+impl FormatOptions {
+  /// Reads `FormatOptions` from a TOML document that sets any of their fields by name, e.g. `indent = 2`. Fields that
+  /// aren't set keep their default value. Numbers can be at most 100.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::FormatOptions;
+  ///
+  /// let opts = FormatOptions::from_toml("indent = 2\narray_padding = true\n").unwrap();
+  /// assert_eq!(FormatOptions{indent: 2, array_padding: true, ..FormatOptions::default()}, opts);
+  /// assert!(FormatOptions::from_toml("indent = -1\n").is_err());
+  /// assert!(FormatOptions::from_toml("indent = 4000000000\n").is_err());
+  /// assert!(FormatOptions::from_toml("tabs = true\n").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `toml` isn't valid TOML, sets a key that isn't a field, sets a field to a value of the
+  /// wrong type, or sets a number larger than 100.
+  pub fn from_toml(toml: &str) -> Result<FormatOptions, TOMLError> {
+    let (parser, result) = Parser::new().parse(toml);
+    if result != ParseResult::Full {
+      return Err(TOMLError::new("Invalid format options: the document has syntax errors".to_string()));
+    }
+    let mut opts = FormatOptions::default();
+    let keys = match parser.get_children("") {
+      Some(Children::Keys(keys)) => keys.borrow().clone(),
+      _ => vec![],
+    };
+    for key in keys {
+      let value = parser.get_value(key.as_str());
+      let fail = |expected: &str| {
+        Err(TOMLError::new(format!("Invalid format option \"{key}\": expected {expected}")))
+      };
+      let (number, flag) = match value {
+        Some(Value::Integer(ref i)) => (i.parse::<usize>().ok().filter(|&n| n <= MAX_FORMAT_NUMBER), None),
+        Some(Value::Boolean(b)) => (None, Some(b)),
+        _ => (None, None),
+      };
+      match (key.as_str(), number, flag) {
+        ("indent", Some(n), _) => opts.indent = n,
+        ("array_indent", Some(n), _) => opts.array_indent = n,
+        ("blank_lines_between_tables", Some(n), _) => opts.blank_lines_between_tables = n,
+        ("max_blank_lines", Some(n), _) => opts.max_blank_lines = n,
+        ("space_around_eq", _, Some(b)) => opts.space_around_eq = b,
+        ("array_padding", _, Some(b)) => opts.array_padding = b,
+        ("inline_table_padding", _, Some(b)) => opts.inline_table_padding = b,
+        ("indent" | "array_indent" | "blank_lines_between_tables" | "max_blank_lines", ..) => {
+          return fail(&format!("an integer from 0 to {MAX_FORMAT_NUMBER}"));
+        },
+        ("space_around_eq" | "array_padding" | "inline_table_padding", ..) => return fail("a boolean"),
+        _ => return Err(TOMLError::new(format!("Unknown format option \"{key}\""))),
+      }
+    }
+    Ok(opts)
+  }
+}
+
+/// Iterator over every entry of a parsed TOML document, depth-first. Created by `TOMLParser::iter`.
+pub struct DocIter<'a> {
+  pub(crate) entries: std::vec::IntoIter<(String, DocEntry<'a>)>,
+}
+
+impl<'a> Iterator for DocIter<'a> {
+  type Item = (String, DocEntry<'a>);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.entries.next()
+  }
+}
+
+/// Iterator over the tables of a parsed TOML document in the order their headers appear. Created by
+/// `TOMLParser::tables`.
+pub struct TableIter<'a> {
+  pub(crate) tables: std::vec::IntoIter<(String, Vec<(String, Value<'a>)>)>,
+}
+
+impl<'a> Iterator for TableIter<'a> {
+  type Item = (String, Vec<(String, Value<'a>)>);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.tables.next()
+  }
+}
+
+/// A view of a value in a parsed TOML document that reads the value in place instead of copying it like
+/// `TOMLParser::get_value` does. Nested `Array` and `InlineTable` values are viewed the same way, and the value can be
+/// copied into a `Value` with `to_value` when needed. Created by `TOMLParser::get_value_ref`.
+///
+/// A `ValueRef` shares the value with the document, so it sees any later changes to the value made with
+/// `TOMLParser::set_value`.
+#[derive(Debug, Clone)]
+pub struct ValueRef<'a> {
+  pub(crate) val: Rc<RefCell<TOMLValue<'a>>>,
+}
+
+impl<'a> ValueRef<'a> {
+  pub(crate) fn new(val: Rc<RefCell<TOMLValue<'a>>>) -> ValueRef<'a> {
+    ValueRef{val}
+  }
+
+  /// Returns what kind of value this is.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::{ValueKind, StrType};
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("name = 'Rex'\nages = [3, 4]\n");
+  /// assert_eq!(ValueKind::String(StrType::Literal), parser.get_value_ref("name").unwrap().kind());
+  /// assert_eq!(ValueKind::Array, parser.get_value_ref("ages").unwrap().kind());
+  /// ```
+  pub fn kind(&self) -> ValueKind {
+    match *self.val.borrow() {
+      TOMLValue::Integer(_) => ValueKind::Integer,
+      TOMLValue::Float(_) => ValueKind::Float,
+      TOMLValue::Boolean(..) => ValueKind::Boolean,
+      TOMLValue::DateTime(_) => ValueKind::DateTime,
+      TOMLValue::Array(_) => ValueKind::Array,
+      TOMLValue::String(_, st) => ValueKind::String(st),
+      TOMLValue::InlineTable(_) => ValueKind::InlineTable,
+      TOMLValue::Table => ValueKind::Table,
+    }
+  }
+
+  /// Returns the number of values in an `Array` or key/value pairs in an `InlineTable`, or 0 for any other value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("point = { x = 1, y = 2 }\nsizes = [1, 2, 3]\n");
+  /// assert_eq!(2, parser.get_value_ref("point").unwrap().len());
+  /// assert_eq!(3, parser.get_value_ref("sizes").unwrap().len());
+  /// assert_eq!(0, parser.get_value_ref("point.x").unwrap().len());
+  /// ```
+  pub fn len(&self) -> usize {
+    match *self.val.borrow() {
+      TOMLValue::Array(ref arr) => arr.borrow().values.len(),
+      TOMLValue::InlineTable(ref it) => it.borrow().keyvals.len(),
+      _ => 0,
+    }
+  }
+
+  /// Returns `true` if this isn't an `Array` or `InlineTable`, or is one without any values.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns a view of the value at `index` of an `Array`, or `None` if this isn't an `Array` or `index` is out of
+  /// bounds.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("matrix = [[1, 2], [3, 4]]\n");
+  /// let matrix = parser.get_value_ref("matrix").unwrap();
+  /// assert_eq!(Value::int(3), matrix.get(1).unwrap().get(0).unwrap().to_value());
+  /// assert!(matrix.get(2).is_none());
+  /// ```
+  pub fn get(&self, index: usize) -> Option<ValueRef<'a>> {
+    match *self.val.borrow() {
+      TOMLValue::Array(ref arr) => arr.borrow().values.get(index).map(|av| ValueRef::new(av.val.clone())),
+      _ => None,
+    }
+  }
+
+  /// Returns a view of the value of `key` in an `InlineTable`, or `None` if this isn't an `InlineTable` or it doesn't
+  /// have the key. The key is matched by its name, so it can be given without quotes.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("point = { x = 1, \"the y\" = 2 }\n");
+  /// let point = parser.get_value_ref("point").unwrap();
+  /// assert_eq!(Value::int(1), point.get_key("x").unwrap().to_value());
+  /// assert_eq!(Value::int(2), point.get_key("the y").unwrap().to_value());
+  /// assert!(point.get_key("z").is_none());
+  /// ```
+  pub fn get_key<S>(&self, key: S) -> Option<ValueRef<'a>> where S: AsRef<str> {
+    match *self.val.borrow() {
+      TOMLValue::InlineTable(ref it) => it.borrow().keyvals.iter()
+        .find(|kv| key_name(&kv.keyval.key) == key.as_ref())
+        .map(|kv| ValueRef::new(kv.keyval.val.clone())),
+      _ => None,
+    }
+  }
+
+  /// Returns an iterator over views of the values of an `Array`. The iterator is empty for any other value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("names = ['a', \"b\", 'c']\n");
+  /// let names: Vec<String> = parser.get_value_ref("names").unwrap().iter().map(|v| v.to_string()).collect();
+  /// assert_eq!(vec!["'a'", "\"b\"", "'c'"], names);
+  /// ```
+  pub fn iter(&self) -> ValueRefIter<'a> {
+    ValueRefIter{val: self.clone(), index: 0}
+  }
+
+  /// Returns an iterator over the keys and views of the values of an `InlineTable`, with keys as they're written in
+  /// the document. The iterator is empty for any other value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("point = { x = 1, y = 2 }\n");
+  /// let entries: Vec<(String, Value)> = parser.get_value_ref("point").unwrap().entries()
+  ///   .map(|(k, v)| (k.into_owned(), v.to_value())).collect();
+  /// assert_eq!(vec![("x".to_string(), Value::int(1)), ("y".to_string(), Value::int(2))], entries);
+  /// ```
+  pub fn entries(&self) -> EntryRefIter<'a> {
+    EntryRefIter{val: self.clone(), index: 0}
+  }
+
+  /// Copies the value into a `Value`, the same as `TOMLParser::get_value` returns.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  ///
+  /// let (parser, _) = TOMLParser::new().parse("sizes = [1, 2]\n");
+  /// assert_eq!(parser.get_value("sizes").unwrap(), parser.get_value_ref("sizes").unwrap().to_value());
+  /// ```
+  pub fn to_value(&self) -> Value<'a> {
+    match *self.val.borrow() {
+      TOMLValue::Integer(ref v) => Value::Integer(v.clone()),
+      TOMLValue::Float(ref v) => Value::Float(v.clone()),
+      TOMLValue::Boolean(v, _) => Value::Boolean(v),
+      TOMLValue::DateTime(ref v) => Value::DateTime(v.clone()),
+      TOMLValue::Array(ref arr) => Parser::sanitize_array(arr.clone()),
+      TOMLValue::String(ref s, t) => Value::String(s.clone(), t),
+      TOMLValue::InlineTable(ref it) => Parser::sanitize_inline_table(it.clone()),
+      TOMLValue::Table => unreachable!("A ValueRef is never created for a Table"),
+    }
+  }
+}
+
+impl<'a> IntoIterator for &ValueRef<'a> {
+  type Item = ValueRef<'a>;
+  type IntoIter = ValueRefIter<'a>;
+
+  fn into_iter(self) -> ValueRefIter<'a> {
+    self.iter()
+  }
+}
+
+/// Formats the value exactly as it's written in the document, including any whitespace and comments inside an `Array`
+/// or `InlineTable`.
+impl Display for ValueRef<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", *self.val.borrow())
+  }
+}
+
+impl<'a> PartialEq<Value<'a>> for ValueRef<'a> {
+  fn eq(&self, other: &Value<'a>) -> bool {
+    self.to_value() == *other
+  }
+}
+
+/// Iterator over views of the values of an `Array`. Created by `ValueRef::iter`.
+pub struct ValueRefIter<'a> {
+  val: ValueRef<'a>,
+  index: usize,
+}
+
+impl<'a> Iterator for ValueRefIter<'a> {
+  type Item = ValueRef<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let next = self.val.get(self.index)?;
+    self.index += 1;
+    Some(next)
+  }
+}
+
+/// Iterator over the keys and views of the values of an `InlineTable`. Created by `ValueRef::entries`.
+pub struct EntryRefIter<'a> {
+  val: ValueRef<'a>,
+  index: usize,
+}
+
+impl<'a> Iterator for EntryRefIter<'a> {
+  type Item = (Cow<'a, str>, ValueRef<'a>);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let next = match *self.val.val.borrow() {
+      TOMLValue::InlineTable(ref it) => it.borrow().keyvals.get(self.index)
+        .map(|kv| (kv.keyval.key.clone(), ValueRef::new(kv.keyval.val.clone())))?,
+      _ => return None,
+    };
+    self.index += 1;
+    Some(next)
+  }
 }
 
 /// Formats a `Value` for display. Uses default rust formatting for for `i64` for `Integer`s, `f64` for `Float`s, bool
@@ -281,7 +933,7 @@ impl<'a> Display for Value<'a> {
       Value::DateTime(ref v) => write!(f, "{}", v),
       Value::Array(ref arr) => {
         write!(f, "[")?;
-        for i in 0..arr.len() - 1 {
+        for i in 0..arr.len().saturating_sub(1) {
           write!(f, "{}, ", arr[i])?;
         }
         if arr.len() > 0 {
@@ -299,7 +951,7 @@ impl<'a> Display for Value<'a> {
       },
       Value::InlineTable(ref it) => {
         write!(f, "{{")?;
-        for i in 0..it.len() - 1 {
+        for i in 0..it.len().saturating_sub(1) {
           write!(f, "{} = {}, ", it[i].0, it[i].1)?;
         }
         if it.len() > 0 {
@@ -404,6 +1056,21 @@ impl<'a> Value<'a> {
       Value::Boolean(b)
   }
 
+  /// Convenience function for creating a basic `Value::String` containing exactly `text`, escaping any characters
+  /// that can't be written as they are. Cannot fail since any text can be written as an escaped basic string.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::{Value, StrType};
+  ///
+  /// assert_eq!(Value::String("say \\\"hi\\\"\\n".into(), StrType::Basic), Value::string("say \"hi\"\n"));
+  /// assert_eq!(Some("say \"hi\"\n".to_string()), Value::string("say \"hi\"\n").string_value());
+  /// ```
+  pub fn string<S>(text: S) -> Value<'a> where S: AsRef<str> {
+    Value::String(escape(text.as_ref()).into(), StrType::Basic)
+  }
+
   /// # Errors
   ///
   /// Will return `TOMLError` is result doesn't validate.
@@ -931,6 +1598,71 @@ impl<'a> Value<'a> {
     }
   }
 
+  /// Parses `text` as any kind of value, written the way it would be written in a TOML document after `key = `, and
+  /// returns it. Unlike `datetime_parse` the returned value doesn't borrow from `text`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::{Value, StrType};
+  /// use std::rc::Rc;
+  ///
+  /// assert_eq!(Value::int(42), Value::parse("42").unwrap());
+  /// assert_eq!(Value::date_from_int(2016, 1, 1).unwrap(), Value::parse("2016-01-01").unwrap());
+  /// assert_eq!(Value::Array(Rc::new(vec![Value::int(1), Value::int(2)])), Value::parse("[1,2]").unwrap());
+  /// assert_eq!(Value::InlineTable(Rc::new(vec![("a".into(), Value::int(1))])), Value::parse("{a=1}").unwrap());
+  /// assert_eq!(Value::String("x".into(), StrType::Literal), Value::parse("'x'").unwrap());
+  /// assert!(Value::parse("x").is_err());
+  /// assert!(Value::parse("1 # one").is_err());
+  /// ```
+  ///
+  /// # Errors
+  ///
+  /// Will return `TOMLError` if `text` isn't exactly one valid value.
+  pub fn parse<S>(text: S) -> Result<Value<'a>, TOMLError> where S: AsRef<str> {
+    let text = text.as_ref();
+    let doc = format!("value = {}", text.trim());
+    let (parser, result) = Parser::new().parse(&doc);
+    let is_one_value = {
+      let root = parser.root.borrow();
+      root.exprs.len() == 1 && root.exprs[0].expr.keyval.is_some() && root.exprs[0].expr.comment.is_none()
+    };
+    match (result, parser.get_value("value")) {
+      (ParseResult::Full, Some(value)) if is_one_value => Ok(value.into_owned()),
+      _ => Err(TOMLError::new(format!("Error parsing value. Argument: {text}"))),
+    }
+  }
+
+  /// Returns the value with all of its text copied, so that it no longer borrows from the document it came from.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::TOMLParser;
+  /// use tomllib::types::Value;
+  /// use std::rc::Rc;
+  ///
+  /// let value: Value<'static> = {
+  ///   let doc = String::from("a = [1979-05-27, 2016-01-01]");
+  ///   let (parser, _) = TOMLParser::new().parse(&doc);
+  ///   parser.get_value("a").unwrap().into_owned()
+  /// };
+  /// assert_eq!(Value::Array(Rc::new(vec![Value::date_from_int(1979, 5, 27).unwrap(),
+  ///   Value::date_from_int(2016, 1, 1).unwrap()])), value);
+  /// ```
+  pub fn into_owned(self) -> Value<'static> {
+    match self {
+      Value::Integer(i) => Value::Integer(i.into_owned().into()),
+      Value::Float(f) => Value::Float(f.into_owned().into()),
+      Value::Boolean(b) => Value::Boolean(b),
+      Value::DateTime(dt) => Value::DateTime(dt.into_owned()),
+      Value::String(s, st) => Value::String(s.into_owned().into(), st),
+      Value::Array(values) => Value::Array(Rc::new(values.iter().cloned().map(Value::into_owned).collect())),
+      Value::InlineTable(keyvals) => Value::InlineTable(Rc::new(keyvals.iter().cloned()
+        .map(|(key, val)| (key.into_owned().into(), val.into_owned())).collect())),
+    }
+  }
+
   /// Convenience function for creating a `Value::String` with `StrType::Basic`. Returns Ok() on success and Err() on
   /// failure.
   ///
@@ -1024,6 +1756,43 @@ impl<'a> Value<'a> {
     }
   }
 
+  /// Returns true if `self` and `other` mean the same thing, even if they're written differently. Unlike `==`, which
+  /// compares values as they're written, this compares `Integer`s and `Float`s by their numeric value, `String`s by
+  /// their text after escapes are replaced, whatever their `StrType`, and `InlineTable`s regardless of key order.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::{Value, StrType};
+  ///
+  /// assert!(Value::Integer("1_000".into()).semantic_eq(&Value::int(1000)));
+  /// assert!(Value::String("a\\tb".into(), StrType::Basic).semantic_eq(&Value::String("a\tb".into(), StrType::Literal)));
+  /// assert!(!Value::int(1).semantic_eq(&Value::float(1.0)));
+  /// ```
+  pub fn semantic_eq(&self, other: &Value) -> bool {
+    crate::internals::compare::values_eq(self, other)
+  }
+
+  /// Returns the text a `String` value stands for, without its quotes, with escape sequences replaced by the
+  /// characters they stand for, and without the newline that may follow the opening quotes of a multi-line string.
+  /// Returns `None` for any other type of value.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use tomllib::types::{Value, StrType};
+  ///
+  /// assert_eq!(Some("Tab\tseparated".to_string()), Value::String("Tab\\tseparated".into(), StrType::Basic).string_value());
+  /// assert_eq!(Some("C:\\temp".to_string()), Value::String("\nC:\\temp".into(), StrType::MLLiteral).string_value());
+  /// assert_eq!(None, Value::int(5).string_value());
+  /// ```
+  pub fn string_value(&self) -> Option<String> {
+    match *self {
+      Value::String(ref s, st) => Some(crate::internals::compare::string_value(s, st)),
+      _ => None,
+    }
+  }
+
   /// Parses and validates a `Value`, returns true if the value is valid and false if it is invalid.
   ///
   /// # Examples
@@ -1123,7 +1892,7 @@ impl Display for TOMLError {
 }
 
 impl TOMLError {
-  fn new(msg: String) -> TOMLError {
+  pub(crate) fn new(msg: String) -> TOMLError {
     warn!("{}", msg);
     TOMLError{message: msg}
   }
@@ -1552,6 +2321,25 @@ impl<'a> DateTime<'a> {
     DateTime{date, time}
   }
 
+  /// Returns the date and time with all of their text copied, so that they no longer borrow from the document they
+  /// came from.
+  pub fn into_owned(self) -> DateTime<'static> {
+    let owned = |c: Cow<str>| -> Cow<'static, str> { c.into_owned().into() };
+    let date = Date{year: owned(self.date.year), month: owned(self.date.month), day: owned(self.date.day)};
+    let time = self.time.map(|t| Time{
+      hour: owned(t.hour),
+      minute: owned(t.minute),
+      second: owned(t.second),
+      fraction: t.fraction.map(owned),
+      offset: t.offset.map(|o| match o {
+        TimeOffset::Zulu => TimeOffset::Zulu,
+        TimeOffset::Time(amount) => TimeOffset::Time(TimeOffsetAmount{pos_neg: amount.pos_neg, hour: owned(amount.hour),
+          minute: owned(amount.minute)}),
+      }),
+    });
+    DateTime{date, time}
+  }
+
   /// Validates a created `DateTime`.
   ///
   /// # Examples
@@ -1580,7 +2368,7 @@ impl<'a> DateTime<'a> {
 mod test {
   use std::cell::{Cell, RefCell};
   use std::rc::Rc;
-  use crate::types::{Children, Value, Date, Time, DateTime, TimeOffset, TimeOffsetAmount, StrType};
+  use crate::types::{Children, Value, Date, Time, DateTime, TimeOffset, TimeOffsetAmount, StrType, FormatOptions};
 
   #[test]
   fn test_combine_keys() {
@@ -2011,4 +2799,38 @@ bar"#).is_err());
     assert!(Value::ml_literal_string("foobar").is_err());
   }
 
+  #[test]
+  fn test_parse() {
+    assert_eq!(Value::int(42), Value::parse(" 42 ").unwrap());
+    assert_eq!(Value::float_from_str("3.5").unwrap(), Value::parse("3.5").unwrap());
+    assert_eq!(Value::bool(true), Value::parse("true").unwrap());
+    assert_eq!(Value::datetime_parse("2012-01-03T03:30:30.3030+07:45").unwrap(),
+      Value::parse("2012-01-03T03:30:30.3030+07:45").unwrap());
+    assert_eq!(Value::String("a\\tb".into(), StrType::Basic), Value::parse(r#""a\tb""#).unwrap());
+    assert_eq!(Value::Array(Rc::new(vec![Value::Array(Rc::new(vec![Value::int(1)])), Value::Array(Rc::new(vec![]))])),
+      Value::parse("[[1], []]").unwrap());
+    assert_eq!(Value::InlineTable(Rc::new(vec![("a".into(), Value::int(1)), ("b".into(),
+      Value::InlineTable(Rc::new(vec![("c".into(), Value::bool(false))])))])),
+      Value::parse("{a = 1, b = {c = false}}").unwrap());
+  }
+
+  #[test]
+  fn test_parse_fail() {
+    for bad in &["", "x", "1 2", "1 # one", "[1, 2", "1\nb = 2", "1\n[t]", "\"a"] {
+      assert!(Value::parse(bad).is_err(), "value {}", bad);
+    }
+  }
+
+  #[test]
+  fn test_format_options_bounds() {
+    let opts = FormatOptions::from_toml("indent = 100\narray_indent = 0\nmax_blank_lines = 100\n").unwrap();
+    assert_eq!(FormatOptions{indent: 100, array_indent: 0, max_blank_lines: 100, ..FormatOptions::default()}, opts);
+    for key in &["indent", "array_indent", "blank_lines_between_tables", "max_blank_lines"] {
+      for bad in &["101", "4000000000"] {
+        let err = FormatOptions::from_toml(&(key.to_string() + " = " + bad)).unwrap_err();
+        assert_eq!(format!("Invalid format option \"{key}\": expected an integer from 0 to 100"), err.to_string());
+      }
+    }
+  }
+
 }
\ No newline at end of file
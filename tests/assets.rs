@@ -3,7 +3,7 @@ use std::fs::File;
 extern crate tomllib;
 extern crate env_logger;
 use tomllib::TOMLParser;
-use tomllib::types::ParseResult;
+use tomllib::types::{ParseResult, ParseMode};
 use std::io::{Read, BufReader};
 
 fn verify_valid(input: String) -> (bool, Option<(String, String)>) {
@@ -28,6 +28,16 @@ fn verify_invalid(input: String) -> (bool, Option<(String, String)>) {
   }
 }
 
+fn verify_invalid_strict(input: String) -> (bool, Option<(String, String)>) {
+  let input_copy = input.clone();
+  let parser = TOMLParser::with_mode(ParseMode::Strict);
+  let (_, result) = parser.parse(&input_copy);
+  match result {
+    ParseResult::Failure(_, _) => (true, None),
+    _                          => (false, Some((input, format!("{:?}", result)))),
+  }
+}
+
 fn test_all_assets(valid: bool) {
   let paths;
   if valid {
@@ -67,7 +77,11 @@ fn test_all_assets(valid: bool) {
             failed.push((filename.clone(), in_out));
           }
         } else {
-          let (success, in_out) = verify_invalid(buffer);
+          let (success, in_out) = verify_invalid(buffer.clone());
+          if !success {
+            failed.push((filename.clone(), in_out));
+          }
+          let (success, in_out) = verify_invalid_strict(buffer);
           if !success {
             failed.push((filename.clone(), in_out));
           }
@@ -470,4 +470,27 @@ key = 1234
       _ => (),
     }
   }
+
+  #[test]
+  fn test_get_set_with_any_quoting() {
+    let parser = TOMLParser::new();
+    let (mut parser, _) = parser.parse(r#""A Key" = 1
+[car]
+"ƭôƥ ƨƥèèδ" = 124.56
+drivers = ["Bob", { "first name" = "Jane" }]
+"#);
+    assert_eq!(Some(Value::int(1)), parser.get_value("'A Key'"));
+    assert_eq!(Some(Value::float_from_str("124.56").unwrap()), parser.get_value("car.'ƭôƥ ƨƥèèδ'"));
+    assert_eq!(Some(Value::basic_string("Jane").unwrap()), parser.get_value("car . drivers[1].'first name'"));
+    assert_eq!(Some(&Children::Keys(RefCell::new(vec!["\"first name\"".to_string()]))),
+      parser.get_children("\"car\".drivers[1]"));
+    assert!(parser.set_value("car.drivers[1].'first name'", Value::basic_string("Sally").unwrap()));
+    assert!(!parser.set_value("car.drivers[1].'first name", Value::basic_string("Sally").unwrap()));
+    assert!(parser.try_get_value("car.drivers[x]").is_err());
+    assert_eq!(r#""A Key" = 1
+[car]
+"ƭôƥ ƨƥèèδ" = 124.56
+drivers = ["Bob", { "first name" = "Sally" }]
+"#, format!("{}", parser));
+  }
 }
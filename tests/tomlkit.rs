@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // Writes contents to a file in the temp directory that no other test uses and returns its path
+  fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tomlkit-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+  }
+
+  fn tomlkit(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tomlkit")).args(args).output().unwrap()
+  }
+
+  fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+  }
+
+  #[test]
+  fn test_check() {
+    let path = temp_file("check.toml", "a = 1\nb = 2\n");
+    let file = path.to_str().unwrap();
+    let output = tomlkit(&["--check", "-i", file, "--set-value", "a,1,int"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("Success\n", stdout(&output));
+    let output = tomlkit(&["--check", "-i", file, "--set-value", "a,3,int"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(format!("Success\n\"{}\" would be changed.\n", file), stdout(&output));
+    assert_eq!("a = 1\nb = 2\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn test_dry_run() {
+    let path = temp_file("dry_run.toml", "a = 1\nb = 2\n");
+    let file = path.to_str().unwrap();
+    let output = tomlkit(&["--dry-run", "-i", file, "--set-value", "b,3,int"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(format!("Success\n--- {0}\n+++ {0}\n@@ -1,2 +1,2 @@\n a = 1\n-b = 2\n+b = 3\n", file),
+      stdout(&output));
+    assert_eq!("a = 1\nb = 2\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn test_print_doc() {
+    let path = temp_file("print_doc.toml", "a = 1\n");
+    let file = path.to_str().unwrap();
+    let output = tomlkit(&["--print-doc", "-i", file, "-o", "-", "--set-value", "a,2,int"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("a = 2\n", stdout(&output));
+    assert_eq!("Success\n", String::from_utf8_lossy(&output.stderr));
+    let output = tomlkit(&["--print-doc", "-i", file, "--set-value", "a,3,int"]);
+    assert_eq!("Success\na = 3\n\n", stdout(&output));
+    assert_eq!("a = 3\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn test_value_warnings() {
+    let path = temp_file("value_warnings.toml", "pi = 3.14159265358979323846\nname = \"x\"\n");
+    let file = path.to_str().unwrap();
+    let output = tomlkit(&["-i", file, "-g", "name"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("\"x\"\n", stdout(&output));
+    assert_eq!(format!("Warning \"{}\" line 1, column 6: LossOfPrecision(\"pi\", 1, 6, \"3.14159265358979323846\")\n",
+      file), String::from_utf8_lossy(&output.stderr));
+    let path = temp_file("value_errors.toml", "a = 1\na = 2\n");
+    let output = tomlkit(&["-i", path.to_str().unwrap(), "-g", "a"]);
+    assert_eq!(Some(255), output.status.code());
+  }
+}